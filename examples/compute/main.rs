@@ -37,6 +37,7 @@ impl ComputeApp {
                 binding: 0,
                 typ: VEDescriptorSetFieldType::StorageBuffer,
                 stage: VEDescriptorSetFieldStage::Compute,
+                count: 1,
             }])
             .unwrap();
 
@@ -45,7 +46,7 @@ impl ComputeApp {
             .unwrap();
 
         let compute_stage = toolkit
-            .create_compute_stage(&[&set_layout], &shader)
+            .create_compute_stage(&[&set_layout], &[], &shader)
             .unwrap();
 
         let set = set_layout.create_descriptor_set().unwrap();
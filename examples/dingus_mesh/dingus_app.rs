@@ -10,15 +10,21 @@ use vengine_rs::core::descriptor_set_layout::{
 use vengine_rs::core::helpers::{clear_color_f32, clear_depth};
 use vengine_rs::core::memory_barrier::{submit_barriers, VEImageMemoryBarrier};
 use vengine_rs::core::memory_properties::VEMemoryProperties;
+use vengine_rs::core::resource_state::VESubresourceRange;
 use vengine_rs::core::semaphore::VESemaphore;
 use vengine_rs::core::shader_module::VEShaderModuleType;
 use vengine_rs::core::toolkit::{App, VEToolkit};
 use vengine_rs::graphics::attachment::VEAttachment;
-use vengine_rs::graphics::render_stage::{VECullMode, VEPrimitiveTopology, VERenderStage};
-use vengine_rs::graphics::vertex_attributes::VertexAttribFormat;
+use vengine_rs::graphics::pipeline_state::VEPipelineState;
+use vengine_rs::graphics::render_stage::{
+    VECullMode, VEPipelineDesc, VEPrimitiveTopology, VERenderStage,
+};
+use vengine_rs::graphics::vertex_attributes::{VEVertexBindingLayout, VertexAttribFormat};
 use vengine_rs::graphics::vertex_buffer::VEVertexBuffer;
 use vengine_rs::image::filtering::VEFiltering;
-use vengine_rs::image::image::{VEImage, VEImageError, VEImageUsage, VEImageViewCreateInfo};
+use vengine_rs::image::image::{
+    VEImage, VEImageDesc, VEImageError, VEImageUsage, VEImageViewAspect, VEImageViewCreateInfo,
+};
 use vengine_rs::image::image_format::VEImageFormat;
 use vengine_rs::image::sampler::{VESampler, VESamplerAddressMode};
 use winit::event::{DeviceEvent, DeviceId, WindowEvent};
@@ -107,6 +113,7 @@ impl DingusApp {
                 binding: 0,
                 typ: VEDescriptorSetFieldType::UniformBuffer,
                 stage: VEDescriptorSetFieldStage::AllGraphics,
+                count: 1,
             }])
             .unwrap();
 
@@ -115,6 +122,7 @@ impl DingusApp {
                 binding: 0,
                 typ: VEDescriptorSetFieldType::Sampler,
                 stage: VEDescriptorSetFieldStage::Fragment,
+                count: 1,
             }])
             .unwrap();
 
@@ -137,13 +145,12 @@ impl DingusApp {
         let height = 480;
 
         let mut color_buffer = toolkit
-            .create_image_full(
+            .create_image_full(&VEImageDesc::new(
                 width,
                 height,
-                1,
                 VEImageFormat::RGBA32f,
                 &[VEImageUsage::ColorAttachment, VEImageUsage::TransferSource],
-            )
+            ))
             .unwrap();
 
         let color_attachment_view = color_buffer
@@ -161,17 +168,16 @@ impl DingusApp {
         let color_buffer = Arc::from(color_buffer);
 
         let mut depth_buffer = toolkit
-            .create_image_full(
+            .create_image_full(&VEImageDesc::new(
                 width,
                 height,
-                1,
                 VEImageFormat::Depth32f,
                 &[VEImageUsage::DepthAttachment],
-            )
+            ))
             .unwrap();
 
         let depth_attachment_view = depth_buffer
-            .get_view(VEImageViewCreateInfo::simple_2d())
+            .get_view(VEImageViewCreateInfo::simple_2d().with_aspect(VEImageViewAspect::All))
             .unwrap();
 
         let depth_attachment = VEAttachment::from_image(
@@ -195,12 +201,19 @@ impl DingusApp {
                     width,
                     height,
                     &[&color_attachment, &depth_attachment],
-                    &[&global_descriptor_set_layout, &mesh_descriptor_set_layout],
-                    &vertex_shader,
-                    &fragment_shader,
-                    &vertex_attributes,
-                    VEPrimitiveTopology::TriangleList,
-                    VECullMode::Back,
+                    &VEPipelineDesc {
+                        set_layouts: &[&global_descriptor_set_layout, &mesh_descriptor_set_layout],
+                        push_constant_ranges: &[],
+                        vertex_shader: &vertex_shader,
+                        fragment_shader: &fragment_shader,
+                        vertex_bindings: &[VEVertexBindingLayout::per_vertex(
+                            0,
+                            &vertex_attributes,
+                        )],
+                        primitive_topology: VEPrimitiveTopology::TriangleList,
+                        cull_mode: VECullMode::Back,
+                        pipeline_state: &VEPipelineState::default(),
+                    },
                 )
                 .unwrap(),
         );
@@ -246,7 +259,7 @@ impl DingusApp {
             .unwrap();
 
         let texture_view = texture
-            .get_view(VEImageViewCreateInfo::simple_2d())
+            .get_view(VEImageViewCreateInfo::mipmapped_2d())
             .unwrap();
 
         descriptor_set
@@ -290,6 +303,7 @@ impl DingusApp {
         let image_memory_barrier = VEImageMemoryBarrier {
             image: self.mesh_stage.color_buffer.handle,
             aspect: ImageAspectFlags::COLOR,
+            range: VESubresourceRange::all(),
             old_layout: ImageLayout::GENERAL,
             new_layout: ImageLayout::GENERAL,
             src_access: AccessFlags::COLOR_ATTACHMENT_WRITE,
@@ -1,8 +1,11 @@
+use crate::buffer::buffer_view::{VEBufferView, VEBufferViewError};
 use crate::core::command_buffer::{VECommandBuffer, VECommandBufferError};
 use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::{VEMainDeviceQueue, VEMainDeviceQueueError};
 use crate::core::memory_properties::{get_memory_properties_flags, VEMemoryProperties};
+use crate::core::resource_state::VEResourceState;
+use crate::image::image_format::VEImageFormat;
 use crate::memory::memory_chunk::{VEMemoryChunkError, VESingleAllocation};
 use crate::memory::memory_manager::{VEMemoryManager, VEMemoryManagerError};
 use ash::vk;
@@ -44,6 +47,10 @@ pub enum VEBufferUsage {
     TransferSource,
     TransferDestination,
     Vertex,
+    Index,
+    Indirect,
+    UniformTexel,
+    StorageTexel,
 }
 
 pub struct VEBuffer {
@@ -55,6 +62,7 @@ pub struct VEBuffer {
     pub buffer: Buffer,
     pub size: u64,
     pub usage: Vec<VEBufferUsage>,
+    pub(crate) state: VEResourceState,
 }
 
 fn get_buffer_usage_flags(usages: &[VEBufferUsage]) -> vk::BufferUsageFlags {
@@ -68,6 +76,14 @@ fn get_buffer_usage_flags(usages: &[VEBufferUsage]) -> vk::BufferUsageFlags {
                 flags = flags | vk::BufferUsageFlags::TRANSFER_DST
             }
             VEBufferUsage::Vertex => flags = flags | vk::BufferUsageFlags::VERTEX_BUFFER,
+            VEBufferUsage::Index => flags = flags | vk::BufferUsageFlags::INDEX_BUFFER,
+            VEBufferUsage::Indirect => flags = flags | vk::BufferUsageFlags::INDIRECT_BUFFER,
+            VEBufferUsage::UniformTexel => {
+                flags = flags | vk::BufferUsageFlags::UNIFORM_TEXEL_BUFFER
+            }
+            VEBufferUsage::StorageTexel => {
+                flags = flags | vk::BufferUsageFlags::STORAGE_TEXEL_BUFFER
+            }
         }
     }
     flags
@@ -118,6 +134,7 @@ impl VEBuffer {
                 allocation,
                 size,
                 usage: usage.to_vec(),
+                state: VEResourceState::default(),
             })
         }
     }
@@ -138,6 +155,16 @@ impl VEBuffer {
             .map_err(VEBufferError::MemoryManagerError)
     }
 
+    // The view keeps the buffer alive.
+    pub fn create_view(
+        self: &Arc<Self>,
+        format: VEImageFormat,
+        offset: u64,
+        range: u64,
+    ) -> Result<VEBufferView, VEBufferViewError> {
+        VEBufferView::new(self.device.clone(), self.clone(), format, offset, range)
+    }
+
     pub fn copy_to(
         &self,
         target: &VEBuffer,
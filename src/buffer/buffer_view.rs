@@ -0,0 +1,61 @@
+use crate::buffer::buffer::{VEBuffer, VEBufferUsage};
+use crate::core::device::VEDevice;
+use crate::image::image_format::{get_image_format, VEImageFormat};
+use ash::vk;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEBufferViewError {
+    #[error("creation failed")]
+    CreationFailed(#[from] vk::Result),
+
+    #[error("buffer has no texel usage")]
+    NoTexelUsage,
+}
+
+pub struct VEBufferView {
+    device: Arc<VEDevice>,
+    pub handle: vk::BufferView,
+    pub usage: Vec<VEBufferUsage>,
+    _buffer: Arc<VEBuffer>,
+}
+
+impl VEBufferView {
+    pub fn new(
+        device: Arc<VEDevice>,
+        buffer: Arc<VEBuffer>,
+        format: VEImageFormat,
+        offset: u64,
+        range: u64,
+    ) -> Result<VEBufferView, VEBufferViewError> {
+        let is_texel = buffer.usage.contains(&VEBufferUsage::UniformTexel)
+            || buffer.usage.contains(&VEBufferUsage::StorageTexel);
+        if !is_texel {
+            return Err(VEBufferViewError::NoTexelUsage);
+        }
+
+        let info = vk::BufferViewCreateInfo::default()
+            .buffer(buffer.buffer)
+            .format(get_image_format(format))
+            .offset(offset)
+            .range(range);
+
+        let handle = unsafe { device.device.create_buffer_view(&info, None)? };
+
+        Ok(VEBufferView {
+            device,
+            handle,
+            usage: buffer.usage.clone(),
+            _buffer: buffer,
+        })
+    }
+}
+
+impl Drop for VEBufferView {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device.destroy_buffer_view(self.handle, None);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::buffer::buffer::VEBufferUsage;
+use crate::buffer::typed_buffer::{VETypedBuffer, VETypedBufferError};
+use crate::core::device::VEDevice;
+use bytemuck::{Pod, Zeroable};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEIndirectCommandError {
+    #[error("buffer was not created with the indirect usage")]
+    NotAnIndirectBuffer,
+
+    #[error("multi draw indirect is not supported by the device")]
+    MultiDrawIndirectNotSupported,
+
+    #[error("draw indirect count is not supported by the device")]
+    DrawIndirectCountNotSupported,
+
+    #[error("draw count {count} exceeds the device limit of {limit}")]
+    DrawCountExceedsDeviceLimit { count: u32, limit: u32 },
+
+    #[error("typed buffer error")]
+    TypedBufferError(#[from] VETypedBufferError),
+}
+
+// Layouts of VkDrawIndirectCommand, VkDrawIndexedIndirectCommand and VkDispatchIndirectCommand, so
+// they can be stored in a VETypedBuffer and written from the CPU or from a shader using the same
+// std430 struct.
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VEDrawIndirectCommand {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub first_vertex: u32,
+    pub first_instance: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VEDrawIndexedIndirectCommand {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    pub first_instance: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VEDispatchIndirectCommand {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+unsafe impl Zeroable for VEDrawIndirectCommand {}
+unsafe impl Pod for VEDrawIndirectCommand {}
+unsafe impl Zeroable for VEDrawIndexedIndirectCommand {}
+unsafe impl Pod for VEDrawIndexedIndirectCommand {}
+unsafe impl Zeroable for VEDispatchIndirectCommand {}
+unsafe impl Pod for VEDispatchIndirectCommand {}
+
+pub(crate) fn validate_indirect_buffer<T: Pod>(
+    device: &VEDevice,
+    buffer: &VETypedBuffer<T>,
+    first: u32,
+    count: u32,
+) -> Result<(), VEIndirectCommandError> {
+    if !buffer.buffer.usage.contains(&VEBufferUsage::Indirect) {
+        return Err(VEIndirectCommandError::NotAnIndirectBuffer);
+    }
+    if count > 1 && !device.features.multi_draw_indirect {
+        return Err(VEIndirectCommandError::MultiDrawIndirectNotSupported);
+    }
+    let limit = device.properties.limits.max_draw_indirect_count;
+    if count > limit {
+        return Err(VEIndirectCommandError::DrawCountExceedsDeviceLimit { count, limit });
+    }
+    buffer.check_bounds(first, count)?;
+    Ok(())
+}
@@ -1 +1,4 @@
 pub mod buffer;
+pub mod buffer_view;
+pub mod indirect_command;
+pub mod typed_buffer;
@@ -0,0 +1,102 @@
+use crate::buffer::buffer::{VEBuffer, VEBufferError, VEBufferUsage};
+use crate::core::command_pool::VECommandPool;
+use crate::core::device::VEDevice;
+use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::memory_properties::VEMemoryProperties;
+use crate::memory::memory_manager::VEMemoryManager;
+use bytemuck::Pod;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VETypedBufferError {
+    #[error("elements {first}..{end} are out of the buffer capacity of {capacity}")]
+    OutOfBounds { first: u32, end: u64, capacity: u32 },
+
+    #[error("buffer error")]
+    BufferError(#[from] VEBufferError),
+}
+
+// A buffer holding `capacity` elements of `T`, reads and writes need host visible memory.
+pub struct VETypedBuffer<T: Pod> {
+    pub buffer: VEBuffer,
+    pub capacity: u32,
+    _element: PhantomData<T>,
+}
+
+impl<T: Pod> VETypedBuffer<T> {
+    pub fn new(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        usage: &[VEBufferUsage],
+        capacity: u32,
+        memory_properties: Option<VEMemoryProperties>,
+    ) -> Result<VETypedBuffer<T>, VETypedBufferError> {
+        let buffer = VEBuffer::new(
+            device,
+            queue,
+            command_pool,
+            memory_manager,
+            usage,
+            Self::element_size() * capacity as u64,
+            memory_properties,
+        )?;
+        Ok(VETypedBuffer {
+            buffer,
+            capacity,
+            _element: PhantomData,
+        })
+    }
+
+    pub fn element_size() -> u64 {
+        size_of::<T>() as u64
+    }
+
+    pub fn offset_of(index: u32) -> u64 {
+        Self::element_size() * index as u64
+    }
+
+    pub fn write(&mut self, first: u32, data: &[T]) -> Result<(), VETypedBufferError> {
+        let count = u32::try_from(data.len()).map_err(|_| VETypedBufferError::OutOfBounds {
+            first,
+            end: first as u64 + data.len() as u64,
+            capacity: self.capacity,
+        })?;
+        self.check_bounds(first, count)?;
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        unsafe {
+            let mem = (self.buffer.map()? as *mut u8).add(Self::offset_of(first) as usize);
+            let slice = std::slice::from_raw_parts_mut(mem, bytes.len());
+            slice.copy_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    pub fn read(&mut self, first: u32, count: u32) -> Result<Vec<T>, VETypedBufferError> {
+        self.check_bounds(first, count)?;
+        let mut result = vec![T::zeroed(); count as usize];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut result);
+        unsafe {
+            let mem = (self.buffer.map()? as *const u8).add(Self::offset_of(first) as usize);
+            let slice = std::slice::from_raw_parts(mem, bytes.len());
+            bytes.copy_from_slice(slice);
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn check_bounds(&self, first: u32, count: u32) -> Result<(), VETypedBufferError> {
+        // u64 so that first + count cannot overflow
+        let end = first as u64 + count as u64;
+        if end > self.capacity as u64 {
+            return Err(VETypedBufferError::OutOfBounds {
+                first,
+                end,
+                capacity: self.capacity,
+            });
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::core::descriptor_set_layout::VEDescriptorSetLayout;
 use crate::core::device::VEDevice;
+use crate::core::push_constants::VEPushConstantRange;
 use crate::core::shader_module::VEShaderModule;
 use ash::vk;
 use std::sync::Arc;
@@ -23,6 +24,7 @@ impl VEComputePipeline {
     pub fn new(
         device: Arc<VEDevice>,
         set_layouts: &[&VEDescriptorSetLayout],
+        push_constant_ranges: &[VEPushConstantRange],
         shader: &VEShaderModule,
     ) -> Result<VEComputePipeline, VEComputePipelineError> {
         let shader_stage_info = vk::PipelineShaderStageCreateInfo::default()
@@ -31,7 +33,12 @@ impl VEComputePipeline {
             .name(c"main");
         let layouts: Vec<vk::DescriptorSetLayout> = set_layouts.iter().map(|x| x.layout).collect();
 
-        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+        let push_constant_ranges: Vec<vk::PushConstantRange> =
+            push_constant_ranges.iter().map(|x| x.build()).collect();
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&layouts)
+            .push_constant_ranges(&push_constant_ranges);
         let pipeline_layout = unsafe {
             device
                 .device
@@ -1,12 +1,26 @@
+use crate::buffer::indirect_command::{
+    validate_indirect_buffer, VEDispatchIndirectCommand, VEIndirectCommandError,
+};
+use crate::buffer::typed_buffer::VETypedBuffer;
 use crate::compute::compute_pipeline::{VEComputePipeline, VEComputePipelineError};
 use crate::core::command_buffer::{VECommandBuffer, VECommandBufferError};
 use crate::core::command_pool::VECommandPool;
 use crate::core::descriptor_set::VEDescriptorSet;
 use crate::core::descriptor_set_layout::VEDescriptorSetLayout;
+use crate::core::descriptor_update_template::{
+    VEDescriptorUpdateTemplate, VEDescriptorUpdateTemplateEntry,
+};
+use crate::core::descriptor_write::VEDescriptorWrite;
 use crate::core::device::VEDevice;
+use crate::core::push_constants::{
+    resolve_push_constant_ranges, validate_push_constants_update, VEPushConstantRange,
+    VEPushConstantsError,
+};
+use crate::core::push_descriptor::{VEPushDescriptorError, VEPushDescriptorSets};
 use crate::core::shader_module::VEShaderModule;
 use ash::vk;
 use ash::vk::CommandBufferUsageFlags;
+use bytemuck::Pod;
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -17,6 +31,15 @@ pub enum VEComputeStageError {
 
     #[error("command buffer error")]
     CommandBufferError(#[from] VECommandBufferError),
+
+    #[error("push constants error")]
+    PushConstantsError(#[from] VEPushConstantsError),
+
+    #[error("push descriptor error")]
+    PushDescriptorError(#[from] VEPushDescriptorError),
+
+    #[error("indirect command error")]
+    IndirectCommandError(#[from] VEIndirectCommandError),
 }
 
 static BIND_POINT: vk::PipelineBindPoint = vk::PipelineBindPoint::COMPUTE;
@@ -24,6 +47,8 @@ static BIND_POINT: vk::PipelineBindPoint = vk::PipelineBindPoint::COMPUTE;
 pub struct VEComputeStage {
     device: Arc<VEDevice>,
     pipeline: Arc<VEComputePipeline>,
+    push_constant_ranges: Vec<VEPushConstantRange>,
+    push_descriptor_sets: VEPushDescriptorSets,
 }
 
 impl VEComputeStage {
@@ -31,12 +56,20 @@ impl VEComputeStage {
         device: Arc<VEDevice>,
         command_pool: Arc<VECommandPool>,
         set_layouts: &[&VEDescriptorSetLayout],
+        push_constant_ranges: &[VEPushConstantRange],
         shader: &VEShaderModule,
     ) -> Result<VEComputeStage, VEComputeStageError> {
-        let pipeline = VEComputePipeline::new(device.clone(), set_layouts, &shader)?;
+        let push_constant_ranges =
+            resolve_push_constant_ranges(&device, push_constant_ranges, &[shader])?;
+        let push_descriptor_sets =
+            VEPushDescriptorSets::new(device.clone(), BIND_POINT, set_layouts);
+        let pipeline =
+            VEComputePipeline::new(device.clone(), set_layouts, &push_constant_ranges, shader)?;
         Ok(VEComputeStage {
             device: device.clone(),
             pipeline: Arc::new(pipeline),
+            push_constant_ranges,
+            push_descriptor_sets,
         })
     }
 
@@ -45,6 +78,16 @@ impl VEComputeStage {
         command_buffer: &VECommandBuffer,
         index: u32,
         set: &VEDescriptorSet,
+    ) {
+        self.set_descriptor_set_with_dynamic_offsets(command_buffer, index, set, &[]);
+    }
+
+    pub fn set_descriptor_set_with_dynamic_offsets(
+        &self,
+        command_buffer: &VECommandBuffer,
+        index: u32,
+        set: &VEDescriptorSet,
+        dynamic_offsets: &[u32],
     ) {
         unsafe {
             self.device.device.cmd_bind_descriptor_sets(
@@ -53,9 +96,80 @@ impl VEComputeStage {
                 self.pipeline.layout,
                 index,
                 &[set.set],
-                &[],
+                dynamic_offsets,
+            );
+        }
+    }
+
+    pub fn push_descriptor(
+        &self,
+        command_buffer: &VECommandBuffer,
+        set: u32,
+        writes: &[VEDescriptorWrite],
+    ) -> Result<(), VEComputeStageError> {
+        self.push_descriptor_sets
+            .push(command_buffer, self.pipeline.layout, set, writes)?;
+        Ok(())
+    }
+
+    pub fn push_descriptor_with_template<T: Copy>(
+        &self,
+        command_buffer: &VECommandBuffer,
+        set: u32,
+        template: &VEDescriptorUpdateTemplate<T>,
+        data: &T,
+    ) -> Result<(), VEComputeStageError> {
+        self.push_descriptor_sets.push_with_template(
+            command_buffer,
+            self.pipeline.layout,
+            set,
+            template,
+            data,
+        )?;
+        Ok(())
+    }
+
+    pub fn create_push_descriptor_template<T: Copy>(
+        &self,
+        set: u32,
+        entries: &[VEDescriptorUpdateTemplateEntry],
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VEComputeStageError> {
+        Ok(self
+            .push_descriptor_sets
+            .create_template(self.pipeline.layout, set, entries)?)
+    }
+
+    // Recycles the descriptor sets allocated when push descriptors are not supported, call it once
+    // per frame after the previous frame's command buffers have completed.
+    pub fn reset_push_descriptors(&self) -> Result<(), VEComputeStageError> {
+        self.push_descriptor_sets.reset()?;
+        Ok(())
+    }
+
+    pub fn push_constants<T: Pod>(
+        &self,
+        command_buffer: &VECommandBuffer,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        value: &T,
+    ) -> Result<(), VEComputeStageError> {
+        let bytes = bytemuck::bytes_of(value);
+        validate_push_constants_update(
+            &self.push_constant_ranges,
+            stage_flags,
+            offset,
+            bytes.len() as u32,
+        )?;
+        unsafe {
+            self.device.device.cmd_push_constants(
+                command_buffer.handle,
+                self.pipeline.layout,
+                stage_flags,
+                offset,
+                bytes,
             );
         }
+        Ok(())
     }
 
     pub fn bind(&self, command_buffer: &VECommandBuffer) {
@@ -84,4 +198,21 @@ impl VEComputeStage {
             );
         }
     }
+
+    pub fn dispatch_indirect(
+        &self,
+        command_buffer: &VECommandBuffer,
+        commands: &VETypedBuffer<VEDispatchIndirectCommand>,
+        command_index: u32,
+    ) -> Result<(), VEComputeStageError> {
+        validate_indirect_buffer(&self.device, commands, command_index, 1)?;
+        unsafe {
+            self.device.device.cmd_dispatch_indirect(
+                command_buffer.handle,
+                commands.buffer.buffer,
+                VETypedBuffer::<VEDispatchIndirectCommand>::offset_of(command_index),
+            );
+        }
+        Ok(())
+    }
 }
@@ -1,7 +1,16 @@
+use crate::buffer::buffer::VEBuffer;
 use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::memory_barrier::{
+    VEBufferMemoryBarrier2, VEDependencyInfo, VEImageMemoryBarrier2,
+};
+use crate::core::resource_state::{
+    get_buffer_access_info, get_image_access_info, VEBufferAccess, VEImageAccess, VEStateBarrier,
+    VESubresourceRange,
+};
 use crate::core::semaphore::{SemaphoreState, VESemaphore};
+use crate::image::image::VEImage;
 use ash::vk;
 use ash::vk::{
     CommandBuffer, CommandBufferAllocateInfo, CommandBufferLevel, CommandBufferUsageFlags,
@@ -29,6 +38,9 @@ pub enum VECommandBufferError {
 
     #[error("waiting for awaited semaphore")]
     WaitingForAwaitedSemaphore,
+
+    #[error("subresource range is outside of the image")]
+    SubresourceRangeOutOfBounds,
 }
 
 pub struct VECommandBuffer {
@@ -156,6 +168,103 @@ impl VECommandBuffer {
         }
         Ok(())
     }
+    // Records the barrier needed before `access` of the whole image, if the image was used in a
+    // conflicting way or layout before.
+    pub fn require(
+        &self,
+        image: &mut VEImage,
+        access: VEImageAccess,
+    ) -> Result<(), VECommandBufferError> {
+        let range = VESubresourceRange::new(0, image.mip_levels, 0, image.array_layers);
+        self.require_range(image, access, range)
+    }
+
+    pub fn require_range(
+        &self,
+        image: &mut VEImage,
+        access: VEImageAccess,
+        range: VESubresourceRange,
+    ) -> Result<(), VECommandBufferError> {
+        if !range.fits(image.mip_levels, image.array_layers) {
+            return Err(VECommandBufferError::SubresourceRangeOutOfBounds);
+        }
+
+        let info = get_image_access_info(access);
+        // Subresources needing the same barrier are merged into runs of layers, then mips.
+        let mut barriers: Vec<(VEStateBarrier, VESubresourceRange)> = vec![];
+        for mip in range.base_mip..range.base_mip + range.mip_count {
+            for layer in range.base_layer..range.base_layer + range.layer_count {
+                let index = (mip * image.array_layers + layer) as usize;
+                let Some(barrier) = image.states[index].require(info, true) else {
+                    continue;
+                };
+                match barriers.last_mut() {
+                    Some((last, last_range))
+                        if *last == barrier
+                            && last_range.base_mip == mip
+                            && last_range.base_layer + last_range.layer_count == layer =>
+                    {
+                        last_range.layer_count += 1
+                    }
+                    _ => barriers.push((barrier, VESubresourceRange::new(mip, 1, layer, 1))),
+                }
+            }
+        }
+        let mut merged: Vec<(VEStateBarrier, VESubresourceRange)> = vec![];
+        for (barrier, range) in barriers {
+            let previous = merged.iter_mut().find(|(b, r)| {
+                *b == barrier
+                    && r.base_layer == range.base_layer
+                    && r.layer_count == range.layer_count
+                    && r.base_mip + r.mip_count == range.base_mip
+            });
+            match previous {
+                Some((_, r)) => r.mip_count += 1,
+                None => merged.push((barrier, range)),
+            }
+        }
+        image.current_layout = image.states[0].layout;
+
+        let mut dependency_info = VEDependencyInfo::new();
+        for (barrier, range) in merged {
+            dependency_info.add_image_barrier(&VEImageMemoryBarrier2 {
+                image: image.handle,
+                aspect: image.aspect,
+                range,
+                old_layout: barrier.old_layout,
+                new_layout: barrier.new_layout,
+                src_stages: barrier.src_stages,
+                src_access: barrier.src_access,
+                dst_stages: barrier.dst_stages,
+                dst_access: barrier.dst_access,
+                queue_transfer: None,
+            });
+        }
+        dependency_info.submit(&self.device, self);
+        Ok(())
+    }
+
+    pub fn require_buffer(
+        &self,
+        buffer: &mut VEBuffer,
+        access: VEBufferAccess,
+    ) -> Result<(), VECommandBufferError> {
+        if let Some(barrier) = buffer.state.require(get_buffer_access_info(access), false) {
+            VEDependencyInfo::new()
+                .with_buffer_barrier(&VEBufferMemoryBarrier2 {
+                    buffer: buffer.buffer,
+                    offset: 0,
+                    size: vk::WHOLE_SIZE,
+                    src_stages: barrier.src_stages,
+                    src_access: barrier.src_access,
+                    dst_stages: barrier.dst_stages,
+                    dst_access: barrier.dst_access,
+                    queue_transfer: None,
+                })
+                .submit(&self.device, self);
+        }
+        Ok(())
+    }
 }
 
 impl Drop for VECommandBuffer {
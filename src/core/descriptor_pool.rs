@@ -0,0 +1,162 @@
+use crate::core::device::VEDevice;
+use ash::vk;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEDescriptorPoolError {
+    #[error("creation failed")]
+    CreationFailed(#[source] vk::Result),
+
+    #[error("reset failed")]
+    ResetFailed(#[source] vk::Result),
+
+    #[error("freeing descriptor sets failed")]
+    FreeFailed(#[source] vk::Result),
+
+    #[error("{0} descriptor sets of the pool are still alive")]
+    SetsInUse(u32),
+
+    #[error("retired set list locking failed")]
+    LockingFailed,
+}
+
+pub struct VEDescriptorPool {
+    device: Arc<VEDevice>,
+    pub handle: vk::DescriptorPool,
+    pub capacity: u32,
+    pub flags: vk::DescriptorPoolCreateFlags,
+    allocated: AtomicU32,
+    live: AtomicU32,
+    retired: Mutex<Vec<vk::DescriptorSet>>,
+}
+
+impl VEDescriptorPool {
+    pub fn new(
+        device: Arc<VEDevice>,
+        pool_sizes: &[vk::DescriptorPoolSize],
+        flags: vk::DescriptorPoolCreateFlags,
+        capacity: u32,
+    ) -> Result<VEDescriptorPool, VEDescriptorPoolError> {
+        let info = vk::DescriptorPoolCreateInfo::default()
+            .flags(flags)
+            .pool_sizes(pool_sizes)
+            .max_sets(capacity);
+        let handle = unsafe {
+            device
+                .device
+                .create_descriptor_pool(&info, None)
+                .map_err(VEDescriptorPoolError::CreationFailed)?
+        };
+        Ok(VEDescriptorPool {
+            device,
+            handle,
+            capacity,
+            flags,
+            allocated: AtomicU32::new(0),
+            live: AtomicU32::new(0),
+            retired: Mutex::new(vec![]),
+        })
+    }
+
+    pub fn allocated_count(&self) -> u32 {
+        self.allocated.load(Ordering::Acquire)
+    }
+
+    // Sets handed out and not dropped yet.
+    pub fn live_count(&self) -> u32 {
+        self.live.load(Ordering::Acquire)
+    }
+
+    pub fn can_free_sets(&self) -> bool {
+        self.flags
+            .contains(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+    }
+
+    // Claims one of the pool's set slots, returns false when the pool is full.
+    pub(crate) fn try_reserve(&self) -> bool {
+        self.allocated
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |allocated| {
+                (allocated < self.capacity).then_some(allocated + 1)
+            })
+            .is_ok()
+    }
+
+    pub(crate) fn release(&self) {
+        let _ = self
+            .allocated
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |allocated| {
+                allocated.checked_sub(1)
+            });
+    }
+
+    pub(crate) fn acquire(&self) {
+        self.live.fetch_add(1, Ordering::AcqRel);
+    }
+
+    // Called when a set is dropped. The set may still be referenced by a command buffer in flight,
+    // so it is only freed by `free_retired`.
+    pub(crate) fn retire(&self, set: vk::DescriptorSet) {
+        self.live.fetch_sub(1, Ordering::AcqRel);
+        if self.can_free_sets() {
+            if let Ok(mut retired) = self.retired.lock() {
+                retired.push(set);
+            }
+        }
+    }
+
+    // Must be called once the command buffers using the dropped sets have finished executing.
+    pub fn free_retired(&self) -> Result<(), VEDescriptorPoolError> {
+        let mut retired = self
+            .retired
+            .lock()
+            .map_err(|_| VEDescriptorPoolError::LockingFailed)?;
+        if retired.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            self.device
+                .device
+                .free_descriptor_sets(self.handle, &retired)
+                .map_err(VEDescriptorPoolError::FreeFailed)?
+        };
+        for _ in retired.drain(..) {
+            self.release();
+        }
+        Ok(())
+    }
+
+    // Returns every set allocated from this pool at once. Fails while any of them is still alive,
+    // and like `free_retired` must only be called once the command buffers using them have
+    // finished executing.
+    pub fn reset(&self) -> Result<(), VEDescriptorPoolError> {
+        let live = self.live_count();
+        if live > 0 {
+            return Err(VEDescriptorPoolError::SetsInUse(live));
+        }
+        let mut retired = self
+            .retired
+            .lock()
+            .map_err(|_| VEDescriptorPoolError::LockingFailed)?;
+        unsafe {
+            self.device
+                .device
+                .reset_descriptor_pool(self.handle, vk::DescriptorPoolResetFlags::empty())
+                .map_err(VEDescriptorPoolError::ResetFailed)?
+        };
+        retired.clear();
+        self.allocated.store(0, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for VEDescriptorPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .device
+                .destroy_descriptor_pool(self.handle, None)
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use crate::buffer::buffer::{VEBuffer, VEBufferUsage};
+use crate::buffer::buffer::VEBuffer;
+use crate::buffer::buffer_view::VEBufferView;
+use crate::core::descriptor_pool::VEDescriptorPool;
+use crate::core::descriptor_set_layout::{VEDescriptorSetFieldType, VEDescriptorSetLayoutHandle};
+use crate::core::descriptor_write::{
+    build_descriptor_writes, guess_buffer_descriptor_type, prepare_descriptor_writes,
+    VEDescriptorWrite, VEDescriptorWriteError,
+};
 use crate::core::device::VEDevice;
 use crate::image::image::VEImage;
 use crate::image::sampler::VESampler;
@@ -14,27 +21,43 @@ pub enum VEDescriptorSetError {
     #[error("image view not found when binding an image")]
     ImageViewNotFound,
 
-    #[error("invalid buffer type for descriptor set")]
-    InvalidBufferType,
+    #[error("descriptor write failed")]
+    WriteFailed(#[from] VEDescriptorWriteError),
 }
 
 pub struct VEDescriptorSet {
     device: Arc<VEDevice>,
     pub set: vk::DescriptorSet,
+    pool: Arc<VEDescriptorPool>,
+    layout: Arc<VEDescriptorSetLayoutHandle>,
 }
 
 impl VEDescriptorSet {
     pub fn new(
         device: Arc<VEDevice>,
-        layout: vk::DescriptorSetLayout,
-        pool: &vk::DescriptorPool,
+        layout: Arc<VEDescriptorSetLayoutHandle>,
+        pool: Arc<VEDescriptorPool>,
+        variable_descriptor_count: Option<u32>,
     ) -> Result<VEDescriptorSet, VEDescriptorSetError> {
-        let layouts = [layout];
-        let info = vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(*pool)
+        let layouts = [layout.handle];
+        let counts = [variable_descriptor_count.unwrap_or(0)];
+        let mut variable_count_info =
+            vk::DescriptorSetVariableDescriptorCountAllocateInfo::default()
+                .descriptor_counts(&counts);
+        let mut info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool.handle)
             .set_layouts(&layouts);
+        if variable_descriptor_count.is_some() {
+            info = info.push_next(&mut variable_count_info);
+        }
         let set = unsafe { device.device.allocate_descriptor_sets(&info)?[0] };
-        Ok(VEDescriptorSet { device, set })
+        pool.acquire();
+        Ok(VEDescriptorSet {
+            device,
+            set,
+            pool,
+            layout,
+        })
     }
 
     pub fn bind_image_sampler(
@@ -44,16 +67,24 @@ impl VEDescriptorSet {
         view: vk::ImageView,
         sampler: &VESampler,
     ) -> Result<(), VEDescriptorSetError> {
-        let infos = [vk::DescriptorImageInfo::default()
-            .image_view(view)
-            .sampler(sampler.handle)
-            .image_layout(image.current_layout)];
-        Ok(self.write(
-            vk::WriteDescriptorSet::default()
-                .dst_binding(binding)
-                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .image_info(&infos),
-        ))
+        self.bind_image_sampler_at(binding, 0, image, view, sampler)
+    }
+
+    pub fn bind_image_sampler_at(
+        &self,
+        binding: u32,
+        array_element: u32,
+        image: &VEImage,
+        view: vk::ImageView,
+        sampler: &VESampler,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::ImageSampler {
+            binding,
+            array_element,
+            image,
+            view,
+            sampler,
+        }])
     }
 
     pub fn bind_image_storage(
@@ -62,44 +93,171 @@ impl VEDescriptorSet {
         image: &VEImage,
         view: vk::ImageView,
     ) -> Result<(), VEDescriptorSetError> {
-        let infos = [vk::DescriptorImageInfo::default()
-            .image_view(view)
-            .image_layout(image.current_layout)];
-        Ok(self.write(
-            vk::WriteDescriptorSet::default()
-                .dst_binding(binding)
-                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-                .image_info(&infos),
-        ))
+        self.bind_image_storage_at(binding, 0, image, view)
+    }
+
+    pub fn bind_image_storage_at(
+        &self,
+        binding: u32,
+        array_element: u32,
+        image: &VEImage,
+        view: vk::ImageView,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::StorageImage {
+            binding,
+            array_element,
+            image,
+            view,
+        }])
+    }
+
+    pub fn bind_sampler(
+        &self,
+        binding: u32,
+        sampler: &VESampler,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.bind_sampler_at(binding, 0, sampler)
+    }
+
+    pub fn bind_sampler_at(
+        &self,
+        binding: u32,
+        array_element: u32,
+        sampler: &VESampler,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::Sampler {
+            binding,
+            array_element,
+            sampler,
+        }])
+    }
+
+    pub fn bind_sampled_image(
+        &self,
+        binding: u32,
+        image: &VEImage,
+        view: vk::ImageView,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.bind_sampled_image_at(binding, 0, image, view)
+    }
+
+    pub fn bind_sampled_image_at(
+        &self,
+        binding: u32,
+        array_element: u32,
+        image: &VEImage,
+        view: vk::ImageView,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::SampledImage {
+            binding,
+            array_element,
+            image,
+            view,
+        }])
+    }
+
+    pub fn bind_input_attachment(
+        &self,
+        binding: u32,
+        image: &VEImage,
+        view: vk::ImageView,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::InputAttachment {
+            binding,
+            array_element: 0,
+            image,
+            view,
+        }])
+    }
+
+    pub fn bind_texel_buffer(
+        &self,
+        binding: u32,
+        view: &VEBufferView,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.bind_texel_buffer_at(binding, 0, view)
+    }
+
+    pub fn bind_texel_buffer_at(
+        &self,
+        binding: u32,
+        array_element: u32,
+        view: &VEBufferView,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::TexelBuffer {
+            binding,
+            array_element,
+            view,
+        }])
+    }
+
+    // Binds `range` bytes of the buffer; the per-draw position inside it is chosen with dynamic
+    // offsets when the set is bound.
+    pub fn bind_buffer_dynamic(
+        &self,
+        binding: u32,
+        buffer: &VEBuffer,
+        range: u64,
+    ) -> Result<(), VEDescriptorSetError> {
+        let typ = guess_buffer_descriptor_type(buffer, true)?;
+        self.bind_buffer_range(binding, buffer, 0, range, typ)
     }
 
     pub fn bind_buffer(&self, binding: u32, buffer: &VEBuffer) -> Result<(), VEDescriptorSetError> {
-        let infos = [vk::DescriptorBufferInfo::default()
-            .buffer(buffer.buffer)
-            .offset(0)
-            .range(buffer.size)];
-        let is_usage_uniform = buffer.usage.contains(&VEBufferUsage::Uniform);
-        let is_usage_storage = buffer.usage.contains(&VEBufferUsage::Storage);
-        let typ = if is_usage_uniform {
-            Ok(vk::DescriptorType::UNIFORM_BUFFER)
-        } else if is_usage_storage {
-            Ok(vk::DescriptorType::STORAGE_BUFFER)
-        } else {
-            Err(VEDescriptorSetError::InvalidBufferType)
-        }?;
-        Ok(self.write(
-            vk::WriteDescriptorSet::default()
-                .dst_binding(binding)
-                .descriptor_type(typ)
-                .buffer_info(&infos),
-        ))
-    }
-
-    fn write(&self, write: vk::WriteDescriptorSet) {
-        let writes = [write.dst_set(self.set)];
+        self.bind_buffer_at(binding, 0, buffer)
+    }
+
+    pub fn bind_buffer_at(
+        &self,
+        binding: u32,
+        array_element: u32,
+        buffer: &VEBuffer,
+    ) -> Result<(), VEDescriptorSetError> {
+        let typ = guess_buffer_descriptor_type(buffer, false)?;
+        self.update(&[VEDescriptorWrite::Buffer {
+            binding,
+            array_element,
+            buffer,
+            offset: 0,
+            range: buffer.size,
+            typ,
+        }])
+    }
+
+    // The offset has to respect the device's min uniform/storage buffer offset alignment for the
+    // given type, which also decides between uniform and storage for buffers created with both.
+    pub fn bind_buffer_range(
+        &self,
+        binding: u32,
+        buffer: &VEBuffer,
+        offset: u64,
+        range: u64,
+        typ: VEDescriptorSetFieldType,
+    ) -> Result<(), VEDescriptorSetError> {
+        self.update(&[VEDescriptorWrite::Buffer {
+            binding,
+            array_element: 0,
+            buffer,
+            offset,
+            range,
+            typ,
+        }])
+    }
+
+    // Validates all writes first and then applies them in a single vkUpdateDescriptorSets call.
+    pub fn update(&self, writes: &[VEDescriptorWrite]) -> Result<(), VEDescriptorSetError> {
+        let prepared = prepare_descriptor_writes(&self.device, &self.layout.fields, writes)?;
+        let writes = build_descriptor_writes(&prepared, self.set);
         let copies = [];
         unsafe {
             self.device.device.update_descriptor_sets(&writes, &copies);
         }
+        Ok(())
+    }
+}
+
+impl Drop for VEDescriptorSet {
+    fn drop(&mut self) {
+        self.pool.retire(self.set);
     }
 }
@@ -1,9 +1,11 @@
+use crate::core::descriptor_pool::{VEDescriptorPool, VEDescriptorPoolError};
 use crate::core::descriptor_set::{VEDescriptorSet, VEDescriptorSetError};
 use crate::core::device::VEDevice;
 use ash::vk;
 use std::sync::Arc;
 use thiserror::Error;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VEDescriptorSetFieldStage {
     All,
     AllGraphics,
@@ -12,24 +14,95 @@ pub enum VEDescriptorSetFieldStage {
     Fragment,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VEDescriptorSetFieldType {
     Sampler,
+    SeparateSampler,
+    SampledImage,
     UniformBuffer,
+    UniformBufferDynamic,
     StorageBuffer,
+    StorageBufferDynamic,
     StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    InputAttachment,
 }
 
-pub struct VEDescriptorSetLayout {
+pub(crate) fn get_descriptor_type(typ: &VEDescriptorSetFieldType) -> vk::DescriptorType {
+    match typ {
+        VEDescriptorSetFieldType::Sampler => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        VEDescriptorSetFieldType::SeparateSampler => vk::DescriptorType::SAMPLER,
+        VEDescriptorSetFieldType::SampledImage => vk::DescriptorType::SAMPLED_IMAGE,
+        VEDescriptorSetFieldType::UniformBuffer => vk::DescriptorType::UNIFORM_BUFFER,
+        VEDescriptorSetFieldType::UniformBufferDynamic => {
+            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+        }
+        VEDescriptorSetFieldType::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
+        VEDescriptorSetFieldType::StorageBufferDynamic => {
+            vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
+        }
+        VEDescriptorSetFieldType::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
+        VEDescriptorSetFieldType::UniformTexelBuffer => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+        VEDescriptorSetFieldType::StorageTexelBuffer => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+        VEDescriptorSetFieldType::InputAttachment => vk::DescriptorType::INPUT_ATTACHMENT,
+    }
+}
+
+pub(crate) fn get_descriptor_stage_flags(
+    stage: &VEDescriptorSetFieldStage,
+) -> vk::ShaderStageFlags {
+    match stage {
+        VEDescriptorSetFieldStage::All => vk::ShaderStageFlags::ALL,
+        VEDescriptorSetFieldStage::AllGraphics => vk::ShaderStageFlags::ALL_GRAPHICS,
+        VEDescriptorSetFieldStage::Compute => vk::ShaderStageFlags::COMPUTE,
+        VEDescriptorSetFieldStage::Vertex => vk::ShaderStageFlags::VERTEX,
+        VEDescriptorSetFieldStage::Fragment => vk::ShaderStageFlags::FRAGMENT,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VEDescriptorSetLayoutMode {
+    Default,
+    Bindless,
+    PushDescriptor,
+    // Requested as a push descriptor layout on a device without VK_KHR_push_descriptor, sets are
+    // allocated from per-frame pools instead.
+    PushDescriptorFallback,
+}
+
+// The layout handle is shared with the allocators, sets and push descriptor sets created from it,
+// and destroyed once the last of them is dropped.
+pub struct VEDescriptorSetLayoutHandle {
     device: Arc<VEDevice>,
-    allocation_counter: u32,
+    pub handle: vk::DescriptorSetLayout,
+    pub fields: Vec<VEDescriptorSetLayoutField>,
+}
+
+pub struct VEDescriptorSetLayout {
     pub layout: vk::DescriptorSetLayout,
-    pools: Vec<Arc<vk::DescriptorPool>>,
+    pub(crate) handle: Arc<VEDescriptorSetLayoutHandle>,
+    pub mode: VEDescriptorSetLayoutMode,
+    allocator: VEDescriptorSetAllocator,
+    transient_allocator: VEDescriptorSetAllocator,
 }
 
+pub(crate) struct VEDescriptorSetAllocator {
+    device: Arc<VEDevice>,
+    pub layout: Arc<VEDescriptorSetLayoutHandle>,
+    pools: Vec<Arc<VEDescriptorPool>>,
+    pool_sizes: Vec<vk::DescriptorPoolSize>,
+    pool_flags: vk::DescriptorPoolCreateFlags,
+    sets_per_pool: u32,
+    variable_descriptor_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VEDescriptorSetLayoutField {
     pub binding: u32,
     pub typ: VEDescriptorSetFieldType,
     pub stage: VEDescriptorSetFieldStage,
+    pub count: u32,
 }
 
 #[derive(Error, Debug)]
@@ -37,48 +110,145 @@ pub enum VEDescriptorSetLayoutError {
     #[error("creation failed")]
     CreationFailed(#[source] vk::Result),
 
-    #[error("pool creation failed")]
-    PoolCreationFailed(#[source] vk::Result),
-
-    #[error("no pool found")]
-    NoPoolFound,
+    #[error("pool error")]
+    PoolError(#[from] VEDescriptorPoolError),
 
     #[error("descriptor set creation failed")]
     DescriptorSetCreationFailed(#[source] VEDescriptorSetError),
+
+    #[error("descriptor indexing is not supported by the device")]
+    DescriptorIndexingNotSupported,
+
+    #[error("update after bind is not supported for {0:?}")]
+    UpdateAfterBindNotSupported(vk::DescriptorType),
+
+    #[error("descriptor sets cannot be allocated from a push descriptor layout")]
+    PushDescriptorLayout,
+
+    #[error("descriptor count of binding {0} overflows the pool size")]
+    DescriptorCountOverflow(u32),
 }
 
 static DEFAULT_POOL_SIZE: u32 = 256;
+static BINDLESS_POOL_SIZE: u32 = 1;
+
+// Descriptors of every type needed for `sets_per_pool` sets, bindings without descriptors take
+// no room in the pool.
+fn get_pool_sizes(
+    fields: &[VEDescriptorSetLayoutField],
+    sets_per_pool: u32,
+) -> Result<Vec<vk::DescriptorPoolSize>, VEDescriptorSetLayoutError> {
+    let mut pool_sizes: Vec<vk::DescriptorPoolSize> = vec![];
+    for field in fields.iter().filter(|field| field.count > 0) {
+        let typ = get_descriptor_type(&field.typ);
+        let overflow = || VEDescriptorSetLayoutError::DescriptorCountOverflow(field.binding);
+        let count = field
+            .count
+            .checked_mul(sets_per_pool)
+            .ok_or_else(overflow)?;
+        match pool_sizes.iter_mut().find(|size| size.ty == typ) {
+            Some(size) => {
+                size.descriptor_count = size
+                    .descriptor_count
+                    .checked_add(count)
+                    .ok_or_else(overflow)?
+            }
+            None => pool_sizes.push(
+                vk::DescriptorPoolSize::default()
+                    .ty(typ)
+                    .descriptor_count(count),
+            ),
+        }
+    }
+    Ok(pool_sizes)
+}
 
 impl VEDescriptorSetLayout {
     pub fn new(
         device: Arc<VEDevice>,
         fields: &[VEDescriptorSetLayoutField],
     ) -> Result<VEDescriptorSetLayout, VEDescriptorSetLayoutError> {
+        Self::create(device, fields, VEDescriptorSetLayoutMode::Default)
+    }
+
+    // Every binding is partially bound and updatable after bind, and the binding with the
+    // highest index gets a variable count, so its `count` is only the upper bound.
+    pub fn new_bindless(
+        device: Arc<VEDevice>,
+        fields: &[VEDescriptorSetLayoutField],
+    ) -> Result<VEDescriptorSetLayout, VEDescriptorSetLayoutError> {
+        if !device.features.descriptor_indexing {
+            return Err(VEDescriptorSetLayoutError::DescriptorIndexingNotSupported);
+        }
+        Self::create(device, fields, VEDescriptorSetLayoutMode::Bindless)
+    }
+
+    // Falls back to a regular layout when VK_KHR_push_descriptor is missing, check `mode` to see
+    // which one was created.
+    pub fn new_push_descriptor(
+        device: Arc<VEDevice>,
+        fields: &[VEDescriptorSetLayoutField],
+    ) -> Result<VEDescriptorSetLayout, VEDescriptorSetLayoutError> {
+        let mode = if device.push_descriptor.is_some() {
+            VEDescriptorSetLayoutMode::PushDescriptor
+        } else {
+            VEDescriptorSetLayoutMode::PushDescriptorFallback
+        };
+        Self::create(device, fields, mode)
+    }
+
+    fn create(
+        device: Arc<VEDevice>,
+        fields: &[VEDescriptorSetLayoutField],
+        mode: VEDescriptorSetLayoutMode,
+    ) -> Result<VEDescriptorSetLayout, VEDescriptorSetLayoutError> {
+        let bindless = mode == VEDescriptorSetLayoutMode::Bindless;
+        let sets_per_pool = if bindless {
+            BINDLESS_POOL_SIZE
+        } else {
+            DEFAULT_POOL_SIZE
+        };
+        let last_binding = fields.iter().map(|field| field.binding).max();
+
         let mut bindings = vec![];
+        let mut binding_flags = vec![];
+        let pool_sizes = get_pool_sizes(fields, sets_per_pool)?;
+        let mut variable_descriptor_count = None;
         for field in fields {
-            let typ = match field.typ {
-                VEDescriptorSetFieldType::Sampler => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                VEDescriptorSetFieldType::UniformBuffer => vk::DescriptorType::UNIFORM_BUFFER,
-                VEDescriptorSetFieldType::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
-                VEDescriptorSetFieldType::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
-            };
-            let stage = match field.stage {
-                VEDescriptorSetFieldStage::All => vk::ShaderStageFlags::ALL,
-                VEDescriptorSetFieldStage::AllGraphics => vk::ShaderStageFlags::ALL_GRAPHICS,
-                VEDescriptorSetFieldStage::Compute => vk::ShaderStageFlags::COMPUTE,
-                VEDescriptorSetFieldStage::Vertex => vk::ShaderStageFlags::VERTEX,
-                VEDescriptorSetFieldStage::Fragment => vk::ShaderStageFlags::FRAGMENT,
-            };
+            let typ = get_descriptor_type(&field.typ);
+            let stage = get_descriptor_stage_flags(&field.stage);
             bindings.push(
                 vk::DescriptorSetLayoutBinding::default()
                     .binding(field.binding)
-                    .descriptor_count(1)
+                    .descriptor_count(field.count)
                     .descriptor_type(typ)
                     .stage_flags(stage),
-            )
+            );
+            if bindless {
+                if !device.features.update_after_bind_types.contains(&typ) {
+                    return Err(VEDescriptorSetLayoutError::UpdateAfterBindNotSupported(typ));
+                }
+                let mut flags = vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                    | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND;
+                if Some(field.binding) == last_binding {
+                    flags |= vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT;
+                    variable_descriptor_count = Some(field.count);
+                }
+                binding_flags.push(flags);
+            }
         }
 
-        let info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        let mut binding_flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+        let mut info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        if mode == VEDescriptorSetLayoutMode::PushDescriptor {
+            info = info.flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR);
+        }
+        if bindless {
+            info = info
+                .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+                .push_next(&mut binding_flags_info);
+        }
 
         let layout = unsafe {
             device
@@ -87,67 +257,227 @@ impl VEDescriptorSetLayout {
                 .map_err(VEDescriptorSetLayoutError::CreationFailed)?
         };
 
-        Ok(VEDescriptorSetLayout {
+        let pool_flags = if bindless {
+            vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND
+        } else {
+            vk::DescriptorPoolCreateFlags::empty()
+        };
+        let handle = Arc::new(VEDescriptorSetLayoutHandle {
+            device: device.clone(),
+            handle: layout,
+            fields: fields.to_vec(),
+        });
+        let allocator = VEDescriptorSetAllocator {
             device,
-            layout,
+            layout: handle.clone(),
             pools: vec![],
-            allocation_counter: 0,
+            pool_sizes,
+            pool_flags: pool_flags | vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+            sets_per_pool,
+            variable_descriptor_count,
+        };
+        let transient_allocator = allocator.transient();
+
+        Ok(VEDescriptorSetLayout {
+            layout,
+            handle,
+            mode,
+            allocator,
+            transient_allocator,
         })
     }
 
+    // The set goes back to its pool on the next `free_retired_descriptor_sets` after it is dropped.
     pub fn create_descriptor_set(&mut self) -> Result<VEDescriptorSet, VEDescriptorSetLayoutError> {
-        if self.pools.len() == 0 {
-            self.generate_new_set_pool()?;
-        } else {
-            self.allocation_counter += 1;
-            if self.allocation_counter >= DEFAULT_POOL_SIZE {
-                self.generate_new_set_pool()?;
-                self.allocation_counter = 0;
+        self.check_allocatable()?;
+        self.allocator.allocate()
+    }
+
+    // For sets that live for a single frame. They are not freed individually, all of them are
+    // returned at once by `reset_transient_descriptor_sets` once they have been dropped.
+    pub fn create_transient_descriptor_set(
+        &mut self,
+    ) -> Result<VEDescriptorSet, VEDescriptorSetLayoutError> {
+        self.check_allocatable()?;
+        self.transient_allocator.allocate()
+    }
+
+    // Fails while transient sets are still alive. Must only be called once the command buffers
+    // using them have finished executing.
+    pub fn reset_transient_descriptor_sets(&mut self) -> Result<(), VEDescriptorSetLayoutError> {
+        self.transient_allocator.reset()
+    }
+
+    // Frees the sets dropped since the last call. Must only be called once the command buffers
+    // using them have finished executing.
+    pub fn free_retired_descriptor_sets(&mut self) -> Result<(), VEDescriptorSetLayoutError> {
+        self.allocator.free_retired()
+    }
+
+    // Creates an additional pool up front, `create_descriptor_set` also does it when all pools
+    // are full.
+    pub fn generate_new_set_pool(&mut self) -> Result<(), VEDescriptorSetLayoutError> {
+        self.check_allocatable()?;
+        self.allocator.generate_pool()?;
+        Ok(())
+    }
+
+    pub fn allocated_set_count(&self) -> u32 {
+        self.allocator.allocated_count() + self.transient_allocator.allocated_count()
+    }
+
+    pub(crate) fn create_transient_allocator(&self) -> VEDescriptorSetAllocator {
+        self.transient_allocator.transient()
+    }
+
+    fn check_allocatable(&self) -> Result<(), VEDescriptorSetLayoutError> {
+        if self.mode == VEDescriptorSetLayoutMode::PushDescriptor {
+            return Err(VEDescriptorSetLayoutError::PushDescriptorLayout);
+        }
+        Ok(())
+    }
+}
+
+impl VEDescriptorSetAllocator {
+    // An empty allocator for the same layout whose pools do not allow freeing single sets.
+    pub fn transient(&self) -> VEDescriptorSetAllocator {
+        VEDescriptorSetAllocator {
+            device: self.device.clone(),
+            layout: self.layout.clone(),
+            pools: vec![],
+            pool_sizes: self.pool_sizes.clone(),
+            pool_flags: self.pool_flags & !vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+            sets_per_pool: self.sets_per_pool,
+            variable_descriptor_count: self.variable_descriptor_count,
+        }
+    }
+
+    pub fn allocate(&mut self) -> Result<VEDescriptorSet, VEDescriptorSetLayoutError> {
+        for pool in &self.pools {
+            if !pool.try_reserve() {
+                continue;
+            }
+            match VEDescriptorSet::new(
+                self.device.clone(),
+                self.layout.clone(),
+                pool.clone(),
+                self.variable_descriptor_count,
+            ) {
+                Ok(set) => return Ok(set),
+                Err(VEDescriptorSetError::CreationFailed(
+                    vk::Result::ERROR_FRAGMENTED_POOL | vk::Result::ERROR_OUT_OF_POOL_MEMORY,
+                )) => pool.release(),
+                Err(error) => {
+                    pool.release();
+                    return Err(VEDescriptorSetLayoutError::DescriptorSetCreationFailed(
+                        error,
+                    ));
+                }
             }
         }
-        let pool = self
-            .pools
-            .last()
-            .ok_or_else(|| VEDescriptorSetLayoutError::NoPoolFound)?;
-        VEDescriptorSet::new(self.device.clone(), self.layout, pool)
-            .map_err(VEDescriptorSetLayoutError::DescriptorSetCreationFailed)
+
+        let pool = self.generate_pool()?;
+        pool.try_reserve();
+        VEDescriptorSet::new(
+            self.device.clone(),
+            self.layout.clone(),
+            pool.clone(),
+            self.variable_descriptor_count,
+        )
+        .inspect_err(|_| pool.release())
+        .map_err(VEDescriptorSetLayoutError::DescriptorSetCreationFailed)
     }
 
-    pub fn generate_new_set_pool(&mut self) -> Result<(), VEDescriptorSetLayoutError> {
-        let pool_sizes = [
-            vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::UNIFORM_BUFFER)
-                .descriptor_count(DEFAULT_POOL_SIZE),
-            vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::STORAGE_BUFFER)
-                .descriptor_count(DEFAULT_POOL_SIZE),
-            vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .descriptor_count(DEFAULT_POOL_SIZE),
-            vk::DescriptorPoolSize::default()
-                .ty(vk::DescriptorType::STORAGE_IMAGE)
-                .descriptor_count(DEFAULT_POOL_SIZE),
-        ];
-        let info = vk::DescriptorPoolCreateInfo::default()
-            .pool_sizes(&pool_sizes)
-            .max_sets(DEFAULT_POOL_SIZE); // TODO weird
-        let pool = unsafe {
-            self.device
-                .device
-                .create_descriptor_pool(&info, None)
-                .map_err(VEDescriptorSetLayoutError::PoolCreationFailed)?
-        };
-        self.pools.push(Arc::new(pool));
+    pub fn generate_pool(&mut self) -> Result<Arc<VEDescriptorPool>, VEDescriptorSetLayoutError> {
+        let pool = Arc::new(VEDescriptorPool::new(
+            self.device.clone(),
+            &self.pool_sizes,
+            self.pool_flags,
+            self.sets_per_pool,
+        )?);
+        self.pools.push(pool.clone());
+        Ok(pool)
+    }
+
+    pub fn free_retired(&mut self) -> Result<(), VEDescriptorSetLayoutError> {
+        for pool in &self.pools {
+            pool.free_retired()?;
+        }
         Ok(())
     }
+
+    pub fn reset(&mut self) -> Result<(), VEDescriptorSetLayoutError> {
+        for pool in &self.pools {
+            pool.reset()?;
+        }
+        Ok(())
+    }
+
+    pub fn allocated_count(&self) -> u32 {
+        self.pools.iter().map(|pool| pool.allocated_count()).sum()
+    }
 }
 
-impl Drop for VEDescriptorSetLayout {
+impl Drop for VEDescriptorSetLayoutHandle {
     fn drop(&mut self) {
         unsafe {
             self.device
                 .device
-                .destroy_descriptor_set_layout(self.layout, None)
+                .destroy_descriptor_set_layout(self.handle, None)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(
+        binding: u32,
+        typ: VEDescriptorSetFieldType,
+        count: u32,
+    ) -> VEDescriptorSetLayoutField {
+        VEDescriptorSetLayoutField {
+            binding,
+            typ,
+            stage: VEDescriptorSetFieldStage::Fragment,
+            count,
+        }
+    }
+
+    #[test]
+    fn pool_sizes_merge_bindings_of_the_same_type() {
+        let fields = [
+            field(0, VEDescriptorSetFieldType::Sampler, 2),
+            field(1, VEDescriptorSetFieldType::UniformBuffer, 1),
+            field(2, VEDescriptorSetFieldType::Sampler, 3),
+            field(3, VEDescriptorSetFieldType::StorageBuffer, 0),
+        ];
+        let Ok(pool_sizes) = get_pool_sizes(&fields, DEFAULT_POOL_SIZE) else {
+            panic!("pool sizes failed");
+        };
+        assert_eq!(pool_sizes.len(), 2);
+        assert_eq!(pool_sizes[0].ty, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
+        assert_eq!(pool_sizes[0].descriptor_count, 5 * DEFAULT_POOL_SIZE);
+        assert_eq!(pool_sizes[1].ty, vk::DescriptorType::UNIFORM_BUFFER);
+        assert_eq!(pool_sizes[1].descriptor_count, DEFAULT_POOL_SIZE);
+    }
+
+    #[test]
+    fn pool_sizes_report_overflowing_counts() {
+        let fields = [field(4, VEDescriptorSetFieldType::SampledImage, u32::MAX)];
+        assert!(matches!(
+            get_pool_sizes(&fields, DEFAULT_POOL_SIZE),
+            Err(VEDescriptorSetLayoutError::DescriptorCountOverflow(4))
+        ));
+
+        let fields = [
+            field(0, VEDescriptorSetFieldType::SampledImage, u32::MAX),
+            field(1, VEDescriptorSetFieldType::SampledImage, 1),
+        ];
+        assert!(matches!(
+            get_pool_sizes(&fields, BINDLESS_POOL_SIZE),
+            Err(VEDescriptorSetLayoutError::DescriptorCountOverflow(1))
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use crate::core::descriptor_set::VEDescriptorSet;
+use crate::core::descriptor_set_layout::{
+    get_descriptor_type, VEDescriptorSetFieldType, VEDescriptorSetLayout,
+    VEDescriptorSetLayoutHandle,
+};
+use crate::core::device::VEDevice;
+use ash::vk;
+use std::ffi;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEDescriptorUpdateTemplateError {
+    #[error("creation failed")]
+    CreationFailed(#[source] vk::Result),
+
+    #[error("push descriptor templates can only be used through the stage they were created for")]
+    PushDescriptorTemplate,
+
+    #[error(
+        "entry for binding {binding} reads up to byte {end}, past the {size} bytes of the data"
+    )]
+    EntryOutOfBounds {
+        binding: u32,
+        end: usize,
+        size: usize,
+    },
+}
+
+// `offset` and `stride` are in bytes inside the template data struct, `offset_of!` is the easiest
+// way to get them. Each element there is a vk::DescriptorImageInfo, vk::DescriptorBufferInfo or
+// vk::BufferView, depending on `typ`.
+#[derive(Debug, Clone, Copy)]
+pub struct VEDescriptorUpdateTemplateEntry {
+    pub binding: u32,
+    pub array_element: u32,
+    pub count: u32,
+    pub typ: VEDescriptorSetFieldType,
+    pub offset: usize,
+    pub stride: usize,
+}
+
+// `T` is the #[repr(C)] struct the entries point into.
+pub struct VEDescriptorUpdateTemplate<T: Copy> {
+    device: Arc<VEDevice>,
+    pub handle: vk::DescriptorUpdateTemplate,
+    pub push_descriptor: bool,
+    _data: PhantomData<T>,
+}
+
+impl<T: Copy> VEDescriptorUpdateTemplate<T> {
+    pub fn new(
+        device: Arc<VEDevice>,
+        layout: &VEDescriptorSetLayout,
+        entries: &[VEDescriptorUpdateTemplateEntry],
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VEDescriptorUpdateTemplateError> {
+        Self::from_layout_handle(device, &layout.handle, entries)
+    }
+
+    pub(crate) fn from_layout_handle(
+        device: Arc<VEDevice>,
+        layout: &VEDescriptorSetLayoutHandle,
+        entries: &[VEDescriptorUpdateTemplateEntry],
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VEDescriptorUpdateTemplateError> {
+        let entries = build_entries::<T>(entries)?;
+        let info = vk::DescriptorUpdateTemplateCreateInfo::default()
+            .descriptor_update_entries(&entries)
+            .template_type(vk::DescriptorUpdateTemplateType::DESCRIPTOR_SET)
+            .descriptor_set_layout(layout.handle);
+        Self::create(device, &info, false)
+    }
+
+    pub(crate) fn new_push_descriptor(
+        device: Arc<VEDevice>,
+        bind_point: vk::PipelineBindPoint,
+        pipeline_layout: vk::PipelineLayout,
+        set: u32,
+        entries: &[VEDescriptorUpdateTemplateEntry],
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VEDescriptorUpdateTemplateError> {
+        let entries = build_entries::<T>(entries)?;
+        let info = vk::DescriptorUpdateTemplateCreateInfo::default()
+            .descriptor_update_entries(&entries)
+            .template_type(vk::DescriptorUpdateTemplateType::PUSH_DESCRIPTORS_KHR)
+            .pipeline_bind_point(bind_point)
+            .pipeline_layout(pipeline_layout)
+            .set(set);
+        Self::create(device, &info, true)
+    }
+
+    fn create(
+        device: Arc<VEDevice>,
+        info: &vk::DescriptorUpdateTemplateCreateInfo,
+        push_descriptor: bool,
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VEDescriptorUpdateTemplateError> {
+        let handle = unsafe {
+            device
+                .device
+                .create_descriptor_update_template(info, None)
+                .map_err(VEDescriptorUpdateTemplateError::CreationFailed)?
+        };
+        Ok(VEDescriptorUpdateTemplate {
+            device,
+            handle,
+            push_descriptor,
+            _data: PhantomData,
+        })
+    }
+
+    pub fn update(
+        &self,
+        set: &VEDescriptorSet,
+        data: &T,
+    ) -> Result<(), VEDescriptorUpdateTemplateError> {
+        if self.push_descriptor {
+            return Err(VEDescriptorUpdateTemplateError::PushDescriptorTemplate);
+        }
+        unsafe {
+            self.device.device.update_descriptor_set_with_template(
+                set.set,
+                self.handle,
+                data_pointer(data),
+            );
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn data_pointer<T>(data: &T) -> *const ffi::c_void {
+    (data as *const T).cast()
+}
+
+fn get_descriptor_info_size(typ: &VEDescriptorSetFieldType) -> usize {
+    match typ {
+        VEDescriptorSetFieldType::Sampler
+        | VEDescriptorSetFieldType::SeparateSampler
+        | VEDescriptorSetFieldType::SampledImage
+        | VEDescriptorSetFieldType::StorageImage
+        | VEDescriptorSetFieldType::InputAttachment => mem::size_of::<vk::DescriptorImageInfo>(),
+        VEDescriptorSetFieldType::UniformBuffer
+        | VEDescriptorSetFieldType::UniformBufferDynamic
+        | VEDescriptorSetFieldType::StorageBuffer
+        | VEDescriptorSetFieldType::StorageBufferDynamic => {
+            mem::size_of::<vk::DescriptorBufferInfo>()
+        }
+        VEDescriptorSetFieldType::UniformTexelBuffer
+        | VEDescriptorSetFieldType::StorageTexelBuffer => mem::size_of::<vk::BufferView>(),
+    }
+}
+
+// The driver reads `count` elements starting at `offset`, every one of them must be inside `T`.
+fn validate_entry<T>(
+    entry: &VEDescriptorUpdateTemplateEntry,
+) -> Result<(), VEDescriptorUpdateTemplateError> {
+    let size = mem::size_of::<T>();
+    if entry.count == 0 {
+        return Ok(());
+    }
+    let end = entry
+        .stride
+        .checked_mul(entry.count as usize - 1)
+        .and_then(|last| last.checked_add(entry.offset))
+        .and_then(|last| last.checked_add(get_descriptor_info_size(&entry.typ)));
+    match end {
+        Some(end) if end <= size => Ok(()),
+        end => Err(VEDescriptorUpdateTemplateError::EntryOutOfBounds {
+            binding: entry.binding,
+            end: end.unwrap_or(usize::MAX),
+            size,
+        }),
+    }
+}
+
+fn build_entries<T>(
+    entries: &[VEDescriptorUpdateTemplateEntry],
+) -> Result<Vec<vk::DescriptorUpdateTemplateEntry>, VEDescriptorUpdateTemplateError> {
+    entries
+        .iter()
+        .map(|entry| {
+            validate_entry::<T>(entry)?;
+            Ok(vk::DescriptorUpdateTemplateEntry::default()
+                .dst_binding(entry.binding)
+                .dst_array_element(entry.array_element)
+                .descriptor_count(entry.count)
+                .descriptor_type(get_descriptor_type(&entry.typ))
+                .offset(entry.offset)
+                .stride(entry.stride))
+        })
+        .collect()
+}
+
+impl<T: Copy> Drop for VEDescriptorUpdateTemplate<T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .device
+                .destroy_descriptor_update_template(self.handle, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Data {
+        images: [vk::DescriptorImageInfo; 2],
+        buffer: vk::DescriptorBufferInfo,
+    }
+
+    fn entry(
+        typ: VEDescriptorSetFieldType,
+        count: u32,
+        offset: usize,
+        stride: usize,
+    ) -> VEDescriptorUpdateTemplateEntry {
+        VEDescriptorUpdateTemplateEntry {
+            binding: 0,
+            array_element: 0,
+            count,
+            typ,
+            offset,
+            stride,
+        }
+    }
+
+    #[test]
+    fn entries_inside_the_data_are_accepted() {
+        let image_size = mem::size_of::<vk::DescriptorImageInfo>();
+        let images = entry(VEDescriptorSetFieldType::Sampler, 2, 0, image_size);
+        let buffer = entry(
+            VEDescriptorSetFieldType::UniformBuffer,
+            1,
+            mem::offset_of!(Data, buffer),
+            0,
+        );
+        assert!(build_entries::<Data>(&[images, buffer]).is_ok());
+    }
+
+    #[test]
+    fn entries_past_the_data_are_rejected() {
+        let image_size = mem::size_of::<vk::DescriptorImageInfo>();
+        let images = entry(VEDescriptorSetFieldType::Sampler, 4, 0, image_size);
+        assert!(matches!(
+            validate_entry::<Data>(&images),
+            Err(VEDescriptorUpdateTemplateError::EntryOutOfBounds { .. })
+        ));
+        // a buffer info starting 8 bytes before the end does not fit
+        let buffer = entry(
+            VEDescriptorSetFieldType::StorageBuffer,
+            1,
+            mem::size_of::<Data>() - 8,
+            0,
+        );
+        assert!(validate_entry::<Data>(&buffer).is_err());
+        let overflow = entry(VEDescriptorSetFieldType::SampledImage, 3, 0, usize::MAX);
+        assert!(validate_entry::<Data>(&overflow).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::buffer::buffer::{VEBuffer, VEBufferUsage};
+use crate::buffer::buffer_view::VEBufferView;
+use crate::core::descriptor_set_layout::{
+    get_descriptor_type, VEDescriptorSetFieldType, VEDescriptorSetLayoutField,
+};
+use crate::core::device::VEDevice;
+use crate::image::image::VEImage;
+use crate::image::sampler::VESampler;
+use ash::vk;
+use std::slice;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEDescriptorWriteError {
+    #[error("invalid buffer type for descriptor set")]
+    InvalidBufferType,
+
+    #[error("{0:?} is not a buffer descriptor type")]
+    NotABufferDescriptorType(VEDescriptorSetFieldType),
+
+    #[error("buffer offset {offset} is not aligned to {alignment}")]
+    MisalignedOffset { offset: u64, alignment: u64 },
+
+    #[error("buffer range {offset}..{end} is out of the buffer bounds of {size} bytes")]
+    RangeOutOfBounds { offset: u64, end: u64, size: u64 },
+
+    #[error("buffer range of {range} bytes exceeds the device limit of {limit} bytes")]
+    RangeExceedsDeviceLimit { range: u64, limit: u64 },
+
+    #[error("binding {0} is not part of the set layout")]
+    BindingNotFound(u32),
+
+    #[error("array element {array_element} is out of bounds for binding {binding} of {count} descriptors")]
+    ArrayElementOutOfBounds {
+        binding: u32,
+        array_element: u32,
+        count: u32,
+    },
+
+    #[error("binding {binding} is a {typ:?} binding, not a texel buffer")]
+    NotATexelBufferBinding {
+        binding: u32,
+        typ: VEDescriptorSetFieldType,
+    },
+}
+
+pub enum VEDescriptorWrite<'a> {
+    ImageSampler {
+        binding: u32,
+        array_element: u32,
+        image: &'a VEImage,
+        view: vk::ImageView,
+        sampler: &'a VESampler,
+    },
+    SampledImage {
+        binding: u32,
+        array_element: u32,
+        image: &'a VEImage,
+        view: vk::ImageView,
+    },
+    StorageImage {
+        binding: u32,
+        array_element: u32,
+        image: &'a VEImage,
+        view: vk::ImageView,
+    },
+    InputAttachment {
+        binding: u32,
+        array_element: u32,
+        image: &'a VEImage,
+        view: vk::ImageView,
+    },
+    Sampler {
+        binding: u32,
+        array_element: u32,
+        sampler: &'a VESampler,
+    },
+    Buffer {
+        binding: u32,
+        array_element: u32,
+        buffer: &'a VEBuffer,
+        offset: u64,
+        range: u64,
+        typ: VEDescriptorSetFieldType,
+    },
+    TexelBuffer {
+        binding: u32,
+        array_element: u32,
+        view: &'a VEBufferView,
+    },
+}
+
+pub(crate) enum VEPreparedDescriptorInfo {
+    Image(vk::DescriptorImageInfo),
+    Buffer(vk::DescriptorBufferInfo),
+    TexelBuffer(vk::BufferView),
+}
+
+pub(crate) struct VEPreparedDescriptorWrite {
+    pub binding: u32,
+    pub array_element: u32,
+    pub typ: vk::DescriptorType,
+    pub info: VEPreparedDescriptorInfo,
+}
+
+pub(crate) fn guess_buffer_descriptor_type(
+    buffer: &VEBuffer,
+    dynamic: bool,
+) -> Result<VEDescriptorSetFieldType, VEDescriptorWriteError> {
+    let is_usage_uniform = buffer.usage.contains(&VEBufferUsage::Uniform);
+    let is_usage_storage = buffer.usage.contains(&VEBufferUsage::Storage);
+    match (is_usage_uniform, is_usage_storage, dynamic) {
+        (true, _, false) => Ok(VEDescriptorSetFieldType::UniformBuffer),
+        (true, _, true) => Ok(VEDescriptorSetFieldType::UniformBufferDynamic),
+        (false, true, false) => Ok(VEDescriptorSetFieldType::StorageBuffer),
+        (false, true, true) => Ok(VEDescriptorSetFieldType::StorageBufferDynamic),
+        (false, false, _) => Err(VEDescriptorWriteError::InvalidBufferType),
+    }
+}
+
+fn validate_buffer_range(
+    device: &VEDevice,
+    buffer: &VEBuffer,
+    offset: u64,
+    range: u64,
+    typ: VEDescriptorSetFieldType,
+) -> Result<(), VEDescriptorWriteError> {
+    let limits = &device.properties.limits;
+    let (usage, alignment, limit) = match typ {
+        VEDescriptorSetFieldType::UniformBuffer
+        | VEDescriptorSetFieldType::UniformBufferDynamic => (
+            VEBufferUsage::Uniform,
+            limits.min_uniform_buffer_offset_alignment,
+            limits.max_uniform_buffer_range as u64,
+        ),
+        VEDescriptorSetFieldType::StorageBuffer
+        | VEDescriptorSetFieldType::StorageBufferDynamic => (
+            VEBufferUsage::Storage,
+            limits.min_storage_buffer_offset_alignment,
+            limits.max_storage_buffer_range as u64,
+        ),
+        _ => return Err(VEDescriptorWriteError::NotABufferDescriptorType(typ)),
+    };
+    if !buffer.usage.contains(&usage) {
+        return Err(VEDescriptorWriteError::InvalidBufferType);
+    }
+    if alignment > 0 && !offset.is_multiple_of(alignment) {
+        return Err(VEDescriptorWriteError::MisalignedOffset { offset, alignment });
+    }
+    // WHOLE_SIZE binds from offset to the end of the buffer
+    let range = if range == vk::WHOLE_SIZE {
+        buffer.size.saturating_sub(offset)
+    } else {
+        range
+    };
+    match offset.checked_add(range) {
+        Some(end) if range > 0 && end <= buffer.size => {}
+        end => {
+            return Err(VEDescriptorWriteError::RangeOutOfBounds {
+                offset,
+                end: end.unwrap_or(u64::MAX),
+                size: buffer.size,
+            })
+        }
+    }
+    if range > limit {
+        return Err(VEDescriptorWriteError::RangeExceedsDeviceLimit { range, limit });
+    }
+    Ok(())
+}
+
+fn image_info(image: &VEImage, view: vk::ImageView) -> VEPreparedDescriptorInfo {
+    VEPreparedDescriptorInfo::Image(
+        vk::DescriptorImageInfo::default()
+            .image_view(view)
+            .image_layout(image.current_layout),
+    )
+}
+
+fn find_binding(
+    fields: &[VEDescriptorSetLayoutField],
+    binding: u32,
+) -> Result<&VEDescriptorSetLayoutField, VEDescriptorWriteError> {
+    fields
+        .iter()
+        .find(|field| field.binding == binding)
+        .ok_or(VEDescriptorWriteError::BindingNotFound(binding))
+}
+
+pub(crate) fn prepare_descriptor_writes(
+    device: &VEDevice,
+    fields: &[VEDescriptorSetLayoutField],
+    writes: &[VEDescriptorWrite],
+) -> Result<Vec<VEPreparedDescriptorWrite>, VEDescriptorWriteError> {
+    let mut prepared = Vec::with_capacity(writes.len());
+    for write in writes {
+        let (binding, array_element, typ, info) = match write {
+            VEDescriptorWrite::ImageSampler {
+                binding,
+                array_element,
+                image,
+                view,
+                sampler,
+            } => (
+                *binding,
+                *array_element,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                VEPreparedDescriptorInfo::Image(
+                    vk::DescriptorImageInfo::default()
+                        .image_view(*view)
+                        .sampler(sampler.handle)
+                        .image_layout(image.current_layout),
+                ),
+            ),
+            VEDescriptorWrite::SampledImage {
+                binding,
+                array_element,
+                image,
+                view,
+            } => (
+                *binding,
+                *array_element,
+                vk::DescriptorType::SAMPLED_IMAGE,
+                image_info(image, *view),
+            ),
+            VEDescriptorWrite::StorageImage {
+                binding,
+                array_element,
+                image,
+                view,
+            } => (
+                *binding,
+                *array_element,
+                vk::DescriptorType::STORAGE_IMAGE,
+                image_info(image, *view),
+            ),
+            VEDescriptorWrite::InputAttachment {
+                binding,
+                array_element,
+                image,
+                view,
+            } => (
+                *binding,
+                *array_element,
+                vk::DescriptorType::INPUT_ATTACHMENT,
+                image_info(image, *view),
+            ),
+            VEDescriptorWrite::Sampler {
+                binding,
+                array_element,
+                sampler,
+            } => (
+                *binding,
+                *array_element,
+                vk::DescriptorType::SAMPLER,
+                VEPreparedDescriptorInfo::Image(
+                    vk::DescriptorImageInfo::default().sampler(sampler.handle),
+                ),
+            ),
+            VEDescriptorWrite::Buffer {
+                binding,
+                array_element,
+                buffer,
+                offset,
+                range,
+                typ,
+            } => {
+                validate_buffer_range(device, buffer, *offset, *range, *typ)?;
+                (
+                    *binding,
+                    *array_element,
+                    get_descriptor_type(typ),
+                    VEPreparedDescriptorInfo::Buffer(
+                        vk::DescriptorBufferInfo::default()
+                            .buffer(buffer.buffer)
+                            .offset(*offset)
+                            .range(*range),
+                    ),
+                )
+            }
+            VEDescriptorWrite::TexelBuffer {
+                binding,
+                array_element,
+                view,
+            } => {
+                // the layout decides, a buffer may have both texel usages
+                let field = find_binding(fields, *binding)?;
+                let usage = match field.typ {
+                    VEDescriptorSetFieldType::UniformTexelBuffer => VEBufferUsage::UniformTexel,
+                    VEDescriptorSetFieldType::StorageTexelBuffer => VEBufferUsage::StorageTexel,
+                    typ => {
+                        return Err(VEDescriptorWriteError::NotATexelBufferBinding {
+                            binding: *binding,
+                            typ,
+                        })
+                    }
+                };
+                if !view.usage.contains(&usage) {
+                    return Err(VEDescriptorWriteError::InvalidBufferType);
+                }
+                (
+                    *binding,
+                    *array_element,
+                    get_descriptor_type(&field.typ),
+                    VEPreparedDescriptorInfo::TexelBuffer(view.handle),
+                )
+            }
+        };
+        // every write updates a single descriptor
+        let field = find_binding(fields, binding)?;
+        if array_element
+            .checked_add(1)
+            .is_none_or(|end| end > field.count)
+        {
+            return Err(VEDescriptorWriteError::ArrayElementOutOfBounds {
+                binding,
+                array_element,
+                count: field.count,
+            });
+        }
+        prepared.push(VEPreparedDescriptorWrite {
+            binding,
+            array_element,
+            typ,
+            info,
+        });
+    }
+    Ok(prepared)
+}
+
+pub(crate) fn build_descriptor_writes(
+    prepared: &[VEPreparedDescriptorWrite],
+    dst_set: vk::DescriptorSet,
+) -> Vec<vk::WriteDescriptorSet<'_>> {
+    prepared
+        .iter()
+        .map(|write| {
+            let result = vk::WriteDescriptorSet::default()
+                .dst_set(dst_set)
+                .dst_binding(write.binding)
+                .dst_array_element(write.array_element)
+                .descriptor_type(write.typ);
+            match &write.info {
+                VEPreparedDescriptorInfo::Image(info) => result.image_info(slice::from_ref(info)),
+                VEPreparedDescriptorInfo::Buffer(info) => result.buffer_info(slice::from_ref(info)),
+                VEPreparedDescriptorInfo::TexelBuffer(view) => {
+                    result.texel_buffer_view(slice::from_ref(view))
+                }
+            }
+        })
+        .collect()
+}
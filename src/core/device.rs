@@ -1,11 +1,11 @@
 use crate::window::window::VEWindow;
 use ash::ext::debug_utils;
-use ash::khr::{surface, swapchain};
+use ash::khr::{push_descriptor, surface, swapchain};
 use ash::vk::{
     make_api_version, ApplicationInfo, DebugUtilsMessageSeverityFlagsEXT,
     DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCreateInfoEXT, InstanceCreateFlags,
     InstanceCreateInfo, MemoryPropertyFlags, PhysicalDevice, PhysicalDeviceMemoryProperties,
-    SurfaceKHR,
+    PhysicalDeviceProperties, SurfaceKHR,
 };
 use ash::{vk, Device, Instance};
 use std::borrow::Cow;
@@ -53,6 +53,28 @@ pub enum VEDeviceError {
     DeviceWaitIdleFailed(#[source] vk::Result),
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct VEDeviceFeatures {
+    pub descriptor_indexing: bool,
+    pub update_after_bind_types: Vec<vk::DescriptorType>,
+    pub push_descriptor: bool,
+    pub multi_draw_indirect: bool,
+    pub draw_indirect_count: bool,
+    pub fill_mode_non_solid: bool,
+    pub depth_bounds: bool,
+    pub depth_bias_clamp: bool,
+    pub wide_lines: bool,
+    pub independent_blend: bool,
+    pub logic_op: bool,
+    pub sample_rate_shading: bool,
+    pub depth_stencil_resolve: bool,
+    pub dynamic_rendering: bool,
+    pub synchronization2: bool,
+    pub texture_compression_bc: bool,
+    pub texture_compression_etc2: bool,
+    pub texture_compression_astc_ldr: bool,
+}
+
 pub struct VEDevice {
     pub instance: Instance,
     pub device: Device,
@@ -60,6 +82,9 @@ pub struct VEDevice {
     pub surface_loader: surface::Instance,
     pub surface: SurfaceKHR,
     pub queue_family_index: u32,
+    pub properties: PhysicalDeviceProperties,
+    pub features: VEDeviceFeatures,
+    pub push_descriptor: Option<push_descriptor::Device>,
     device_memory_properties: PhysicalDeviceMemoryProperties,
 }
 
@@ -223,26 +248,163 @@ impl VEDevice {
             .ok_or(VEDeviceError::NoSuitablePhysicalDeviceFound)?;
 
         let queue_family_index = queue_family_index as u32;
-        let device_extension_names_raw = [
+        let supported_extensions = unsafe {
+            instance
+                .enumerate_device_extension_properties(pdevice)
+                .unwrap_or_default()
+        };
+        let is_extension_supported = |name: &ffi::CStr| {
+            supported_extensions
+                .iter()
+                .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+        };
+        let push_descriptor_supported = is_extension_supported(push_descriptor::NAME);
+
+        let mut device_extension_names_raw = vec![
             swapchain::NAME.as_ptr(),
             #[cfg(any(target_os = "macos", target_os = "ios"))]
             ash::khr::portability_subset::NAME.as_ptr(),
         ];
+        if push_descriptor_supported {
+            device_extension_names_raw.push(push_descriptor::NAME.as_ptr());
+        }
+        let supported_features = unsafe { instance.get_physical_device_features(pdevice) };
+        let multi_draw_indirect = supported_features.multi_draw_indirect == vk::TRUE;
         let features = vk::PhysicalDeviceFeatures {
             shader_clip_distance: 1,
             depth_clamp: 1,
+            multi_draw_indirect: supported_features.multi_draw_indirect,
+            fill_mode_non_solid: supported_features.fill_mode_non_solid,
+            depth_bounds: supported_features.depth_bounds,
+            depth_bias_clamp: supported_features.depth_bias_clamp,
+            wide_lines: supported_features.wide_lines,
+            independent_blend: supported_features.independent_blend,
+            logic_op: supported_features.logic_op,
+            sample_rate_shading: supported_features.sample_rate_shading,
+            texture_compression_bc: supported_features.texture_compression_bc,
+            texture_compression_etc2: supported_features.texture_compression_etc2,
+            texture_compression_astc_ldr: supported_features.texture_compression_astc_ldr,
             ..Default::default()
         };
+        let properties = unsafe { instance.get_physical_device_properties(pdevice) };
+
+        let mut supported_vulkan12 = vk::PhysicalDeviceVulkan12Features::default();
+        if properties.api_version >= vk::API_VERSION_1_2 {
+            let mut features2 =
+                vk::PhysicalDeviceFeatures2::default().push_next(&mut supported_vulkan12);
+            unsafe { instance.get_physical_device_features2(pdevice, &mut features2) };
+        }
+
+        let mut supported_vulkan13 = vk::PhysicalDeviceVulkan13Features::default();
+        if properties.api_version >= vk::API_VERSION_1_3 {
+            let mut features2 =
+                vk::PhysicalDeviceFeatures2::default().push_next(&mut supported_vulkan13);
+            unsafe { instance.get_physical_device_features2(pdevice, &mut features2) };
+        }
+        let dynamic_rendering = supported_vulkan13.dynamic_rendering == vk::TRUE;
+        let synchronization2 = supported_vulkan13.synchronization2 == vk::TRUE;
+        let mut vulkan13 = vk::PhysicalDeviceVulkan13Features::default()
+            .dynamic_rendering(dynamic_rendering)
+            .synchronization2(synchronization2);
+
+        let descriptor_indexing = supported_vulkan12.descriptor_indexing == vk::TRUE
+            && supported_vulkan12.runtime_descriptor_array == vk::TRUE
+            && supported_vulkan12.descriptor_binding_partially_bound == vk::TRUE
+            && supported_vulkan12.descriptor_binding_variable_descriptor_count == vk::TRUE
+            && supported_vulkan12.shader_sampled_image_array_non_uniform_indexing == vk::TRUE;
+
+        let sampled_image_update_after_bind =
+            supported_vulkan12.descriptor_binding_sampled_image_update_after_bind == vk::TRUE;
+        let storage_image_update_after_bind =
+            supported_vulkan12.descriptor_binding_storage_image_update_after_bind == vk::TRUE;
+        let storage_buffer_update_after_bind =
+            supported_vulkan12.descriptor_binding_storage_buffer_update_after_bind == vk::TRUE;
+        let uniform_buffer_update_after_bind =
+            supported_vulkan12.descriptor_binding_uniform_buffer_update_after_bind == vk::TRUE;
+
+        let draw_indirect_count = supported_vulkan12.draw_indirect_count == vk::TRUE;
+
+        let mut vulkan12 =
+            vk::PhysicalDeviceVulkan12Features::default().draw_indirect_count(draw_indirect_count);
+        let mut update_after_bind_types = vec![];
+        if descriptor_indexing {
+            vulkan12 = vulkan12
+                .descriptor_indexing(true)
+                .runtime_descriptor_array(true)
+                .descriptor_binding_partially_bound(true)
+                .descriptor_binding_variable_descriptor_count(true)
+                .shader_sampled_image_array_non_uniform_indexing(true)
+                .shader_storage_image_array_non_uniform_indexing(
+                    supported_vulkan12.shader_storage_image_array_non_uniform_indexing == vk::TRUE,
+                )
+                .shader_storage_buffer_array_non_uniform_indexing(
+                    supported_vulkan12.shader_storage_buffer_array_non_uniform_indexing == vk::TRUE,
+                )
+                .descriptor_binding_sampled_image_update_after_bind(sampled_image_update_after_bind)
+                .descriptor_binding_storage_image_update_after_bind(storage_image_update_after_bind)
+                .descriptor_binding_storage_buffer_update_after_bind(
+                    storage_buffer_update_after_bind,
+                )
+                .descriptor_binding_uniform_buffer_update_after_bind(
+                    uniform_buffer_update_after_bind,
+                );
+            if sampled_image_update_after_bind {
+                update_after_bind_types.extend_from_slice(&[
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    vk::DescriptorType::SAMPLED_IMAGE,
+                    vk::DescriptorType::SAMPLER,
+                ]);
+            }
+            if storage_image_update_after_bind {
+                update_after_bind_types.push(vk::DescriptorType::STORAGE_IMAGE);
+            }
+            if storage_buffer_update_after_bind {
+                update_after_bind_types.push(vk::DescriptorType::STORAGE_BUFFER);
+            }
+            if uniform_buffer_update_after_bind {
+                update_after_bind_types.push(vk::DescriptorType::UNIFORM_BUFFER);
+            }
+        }
+
+        let features_enabled = VEDeviceFeatures {
+            descriptor_indexing,
+            update_after_bind_types,
+            push_descriptor: push_descriptor_supported,
+            multi_draw_indirect,
+            draw_indirect_count,
+            fill_mode_non_solid: supported_features.fill_mode_non_solid == vk::TRUE,
+            depth_bounds: supported_features.depth_bounds == vk::TRUE,
+            depth_bias_clamp: supported_features.depth_bias_clamp == vk::TRUE,
+            wide_lines: supported_features.wide_lines == vk::TRUE,
+            independent_blend: supported_features.independent_blend == vk::TRUE,
+            logic_op: supported_features.logic_op == vk::TRUE,
+            sample_rate_shading: supported_features.sample_rate_shading == vk::TRUE,
+            // Render pass 2 and sample zero depth resolve are core in Vulkan 1.2
+            depth_stencil_resolve: properties.api_version >= vk::API_VERSION_1_2,
+            dynamic_rendering,
+            synchronization2,
+            texture_compression_bc: supported_features.texture_compression_bc == vk::TRUE,
+            texture_compression_etc2: supported_features.texture_compression_etc2 == vk::TRUE,
+            texture_compression_astc_ldr: supported_features.texture_compression_astc_ldr
+                == vk::TRUE,
+        };
+
         let priorities = [1.0];
 
         let queue_info = vk::DeviceQueueCreateInfo::default()
             .queue_family_index(queue_family_index)
             .queue_priorities(&priorities);
 
-        let device_create_info = vk::DeviceCreateInfo::default()
+        let mut device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(std::slice::from_ref(&queue_info))
             .enabled_extension_names(&device_extension_names_raw)
             .enabled_features(&features);
+        if properties.api_version >= vk::API_VERSION_1_2 {
+            device_create_info = device_create_info.push_next(&mut vulkan12);
+        }
+        if properties.api_version >= vk::API_VERSION_1_3 {
+            device_create_info = device_create_info.push_next(&mut vulkan13);
+        }
 
         let device: Device = unsafe {
             instance
@@ -253,6 +415,9 @@ impl VEDevice {
         let device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(pdevice) };
 
+        let push_descriptor =
+            push_descriptor_supported.then(|| push_descriptor::Device::new(&instance, &device));
+
         Ok(VEDevice {
             instance,
             physical_device: pdevice,
@@ -260,6 +425,9 @@ impl VEDevice {
             surface_loader,
             surface,
             queue_family_index,
+            properties,
+            features: features_enabled,
+            push_descriptor,
             device_memory_properties,
         })
     }
@@ -280,6 +448,22 @@ impl VEDevice {
         None
     }
 
+    pub fn format_features(
+        &self,
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+    ) -> vk::FormatFeatureFlags {
+        let properties = unsafe {
+            self.instance
+                .get_physical_device_format_properties(self.physical_device, format)
+        };
+        match tiling {
+            vk::ImageTiling::OPTIMAL => properties.optimal_tiling_features,
+            vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+            _ => vk::FormatFeatureFlags::empty(),
+        }
+    }
+
     pub fn wait_idle(&self) -> Result<(), VEDeviceError> {
         unsafe {
             self.device
@@ -1,5 +1,6 @@
 use crate::core::command_buffer::VECommandBuffer;
 use crate::core::device::VEDevice;
+use crate::core::resource_state::VESubresourceRange;
 use ash::vk;
 use std::sync::Arc;
 
@@ -19,6 +20,7 @@ impl VEMemoryBarrier {
 pub struct VEImageMemoryBarrier {
     pub image: vk::Image,
     pub aspect: vk::ImageAspectFlags,
+    pub range: VESubresourceRange,
     pub old_layout: vk::ImageLayout,
     pub new_layout: vk::ImageLayout,
     pub src_access: vk::AccessFlags,
@@ -33,14 +35,7 @@ impl VEImageMemoryBarrier {
             .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
             .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
             .image(self.image)
-            .subresource_range(
-                vk::ImageSubresourceRange::default()
-                    .aspect_mask(self.aspect)
-                    .base_mip_level(0)
-                    .level_count(1) // TODO mip mapping
-                    .base_array_layer(0)
-                    .layer_count(1),
-            )
+            .subresource_range(self.range.build(self.aspect))
             .src_access_mask(self.src_access)
             .dst_access_mask(self.dst_access)
     }
@@ -88,3 +83,310 @@ pub fn submit_barriers(
         )
     }
 }
+
+// Moves ownership of a resource between queue families. Both the releasing and the acquiring
+// queue record the same barrier.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEQueueFamilyTransfer {
+    pub src_queue_family: u32,
+    pub dst_queue_family: u32,
+}
+
+fn get_queue_families(transfer: Option<VEQueueFamilyTransfer>) -> (u32, u32) {
+    transfer.map_or((vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED), |t| {
+        (t.src_queue_family, t.dst_queue_family)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEMemoryBarrier2 {
+    pub src_stages: vk::PipelineStageFlags2,
+    pub src_access: vk::AccessFlags2,
+    pub dst_stages: vk::PipelineStageFlags2,
+    pub dst_access: vk::AccessFlags2,
+}
+
+impl VEMemoryBarrier2 {
+    pub fn build(&self) -> vk::MemoryBarrier2<'static> {
+        vk::MemoryBarrier2::default()
+            .src_stage_mask(self.src_stages)
+            .src_access_mask(self.src_access)
+            .dst_stage_mask(self.dst_stages)
+            .dst_access_mask(self.dst_access)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEImageMemoryBarrier2 {
+    pub image: vk::Image,
+    pub aspect: vk::ImageAspectFlags,
+    pub range: VESubresourceRange,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+    pub src_stages: vk::PipelineStageFlags2,
+    pub src_access: vk::AccessFlags2,
+    pub dst_stages: vk::PipelineStageFlags2,
+    pub dst_access: vk::AccessFlags2,
+    pub queue_transfer: Option<VEQueueFamilyTransfer>,
+}
+
+impl VEImageMemoryBarrier2 {
+    pub fn build(&self) -> vk::ImageMemoryBarrier2<'static> {
+        let (src_queue_family, dst_queue_family) = get_queue_families(self.queue_transfer);
+        vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(self.src_stages)
+            .src_access_mask(self.src_access)
+            .dst_stage_mask(self.dst_stages)
+            .dst_access_mask(self.dst_access)
+            .old_layout(self.old_layout)
+            .new_layout(self.new_layout)
+            .src_queue_family_index(src_queue_family)
+            .dst_queue_family_index(dst_queue_family)
+            .image(self.image)
+            .subresource_range(self.range.build(self.aspect))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEBufferMemoryBarrier2 {
+    pub buffer: vk::Buffer,
+    pub offset: u64,
+    pub size: u64,
+    pub src_stages: vk::PipelineStageFlags2,
+    pub src_access: vk::AccessFlags2,
+    pub dst_stages: vk::PipelineStageFlags2,
+    pub dst_access: vk::AccessFlags2,
+    pub queue_transfer: Option<VEQueueFamilyTransfer>,
+}
+
+impl VEBufferMemoryBarrier2 {
+    pub fn build(&self) -> vk::BufferMemoryBarrier2<'static> {
+        let (src_queue_family, dst_queue_family) = get_queue_families(self.queue_transfer);
+        vk::BufferMemoryBarrier2::default()
+            .src_stage_mask(self.src_stages)
+            .src_access_mask(self.src_access)
+            .dst_stage_mask(self.dst_stages)
+            .dst_access_mask(self.dst_access)
+            .src_queue_family_index(src_queue_family)
+            .dst_queue_family_index(dst_queue_family)
+            .buffer(self.buffer)
+            .offset(self.offset)
+            .size(self.size)
+    }
+}
+
+// Stages that only exist in synchronization2 are widened to the legacy stages containing them.
+fn get_legacy_stages(stages: vk::PipelineStageFlags2) -> vk::PipelineStageFlags {
+    use vk::PipelineStageFlags as S;
+    use vk::PipelineStageFlags2 as S2;
+    let mut legacy = S::from_raw(stages.as_raw() as u32);
+    if stages.intersects(S2::COPY | S2::RESOLVE | S2::BLIT | S2::CLEAR) {
+        legacy |= S::TRANSFER;
+    }
+    if stages.intersects(S2::INDEX_INPUT | S2::VERTEX_ATTRIBUTE_INPUT) {
+        legacy |= S::VERTEX_INPUT;
+    }
+    if stages.contains(S2::PRE_RASTERIZATION_SHADERS) {
+        legacy |= S::VERTEX_SHADER
+            | S::TESSELLATION_CONTROL_SHADER
+            | S::TESSELLATION_EVALUATION_SHADER
+            | S::GEOMETRY_SHADER;
+    }
+    legacy
+}
+
+fn get_legacy_access(access: vk::AccessFlags2) -> vk::AccessFlags {
+    use vk::AccessFlags as A;
+    use vk::AccessFlags2 as A2;
+    let mut legacy = A::from_raw(access.as_raw() as u32);
+    if access.intersects(A2::SHADER_SAMPLED_READ | A2::SHADER_STORAGE_READ) {
+        legacy |= A::SHADER_READ;
+    }
+    if access.contains(A2::SHADER_STORAGE_WRITE) {
+        legacy |= A::SHADER_WRITE;
+    }
+    legacy
+}
+
+// Barriers collected into a single vkCmdPipelineBarrier2, or a vkCmdPipelineBarrier when the
+// device does not have synchronization2 enabled.
+#[derive(Default)]
+pub struct VEDependencyInfo {
+    memory_barriers: Vec<vk::MemoryBarrier2<'static>>,
+    buffer_barriers: Vec<vk::BufferMemoryBarrier2<'static>>,
+    image_barriers: Vec<vk::ImageMemoryBarrier2<'static>>,
+    by_region: bool,
+}
+
+impl VEDependencyInfo {
+    pub fn new() -> VEDependencyInfo {
+        VEDependencyInfo::default()
+    }
+
+    pub fn with_memory_barrier(mut self, barrier: &VEMemoryBarrier2) -> Self {
+        self.add_memory_barrier(barrier);
+        self
+    }
+
+    pub fn with_buffer_barrier(mut self, barrier: &VEBufferMemoryBarrier2) -> Self {
+        self.add_buffer_barrier(barrier);
+        self
+    }
+
+    pub fn with_image_barrier(mut self, barrier: &VEImageMemoryBarrier2) -> Self {
+        self.add_image_barrier(barrier);
+        self
+    }
+
+    pub fn with_by_region(mut self) -> Self {
+        self.by_region = true;
+        self
+    }
+
+    pub fn add_memory_barrier(&mut self, barrier: &VEMemoryBarrier2) {
+        self.memory_barriers.push(barrier.build());
+    }
+
+    pub fn add_buffer_barrier(&mut self, barrier: &VEBufferMemoryBarrier2) {
+        self.buffer_barriers.push(barrier.build());
+    }
+
+    pub fn add_image_barrier(&mut self, barrier: &VEImageMemoryBarrier2) {
+        self.image_barriers.push(barrier.build());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory_barriers.is_empty()
+            && self.buffer_barriers.is_empty()
+            && self.image_barriers.is_empty()
+    }
+
+    fn dependency_flags(&self) -> vk::DependencyFlags {
+        if self.by_region {
+            vk::DependencyFlags::BY_REGION
+        } else {
+            vk::DependencyFlags::empty()
+        }
+    }
+
+    // Does nothing when no barriers were added.
+    pub fn submit(&self, device: &VEDevice, command_buffer: &VECommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        if !device.features.synchronization2 {
+            self.submit_legacy(device, command_buffer);
+            return;
+        }
+        let dependency_info = vk::DependencyInfo::default()
+            .dependency_flags(self.dependency_flags())
+            .memory_barriers(&self.memory_barriers)
+            .buffer_memory_barriers(&self.buffer_barriers)
+            .image_memory_barriers(&self.image_barriers);
+        unsafe {
+            device
+                .device
+                .cmd_pipeline_barrier2(command_buffer.handle, &dependency_info)
+        };
+    }
+
+    // The legacy barrier has a single pair of stage masks, every barrier waits for the union.
+    fn submit_legacy(&self, device: &VEDevice, command_buffer: &VECommandBuffer) {
+        let mut src_stages = vk::PipelineStageFlags2::NONE;
+        let mut dst_stages = vk::PipelineStageFlags2::NONE;
+        let memory_barriers: Vec<vk::MemoryBarrier> = self
+            .memory_barriers
+            .iter()
+            .map(|b| {
+                src_stages |= b.src_stage_mask;
+                dst_stages |= b.dst_stage_mask;
+                vk::MemoryBarrier::default()
+                    .src_access_mask(get_legacy_access(b.src_access_mask))
+                    .dst_access_mask(get_legacy_access(b.dst_access_mask))
+            })
+            .collect();
+        let buffer_barriers: Vec<vk::BufferMemoryBarrier> = self
+            .buffer_barriers
+            .iter()
+            .map(|b| {
+                src_stages |= b.src_stage_mask;
+                dst_stages |= b.dst_stage_mask;
+                vk::BufferMemoryBarrier::default()
+                    .src_access_mask(get_legacy_access(b.src_access_mask))
+                    .dst_access_mask(get_legacy_access(b.dst_access_mask))
+                    .src_queue_family_index(b.src_queue_family_index)
+                    .dst_queue_family_index(b.dst_queue_family_index)
+                    .buffer(b.buffer)
+                    .offset(b.offset)
+                    .size(b.size)
+            })
+            .collect();
+        let image_barriers: Vec<vk::ImageMemoryBarrier> = self
+            .image_barriers
+            .iter()
+            .map(|b| {
+                src_stages |= b.src_stage_mask;
+                dst_stages |= b.dst_stage_mask;
+                vk::ImageMemoryBarrier::default()
+                    .src_access_mask(get_legacy_access(b.src_access_mask))
+                    .dst_access_mask(get_legacy_access(b.dst_access_mask))
+                    .old_layout(b.old_layout)
+                    .new_layout(b.new_layout)
+                    .src_queue_family_index(b.src_queue_family_index)
+                    .dst_queue_family_index(b.dst_queue_family_index)
+                    .image(b.image)
+                    .subresource_range(b.subresource_range)
+            })
+            .collect();
+
+        let src_stages = get_legacy_stages(src_stages);
+        let dst_stages = get_legacy_stages(dst_stages);
+        unsafe {
+            device.device.cmd_pipeline_barrier(
+                command_buffer.handle,
+                if src_stages.is_empty() {
+                    vk::PipelineStageFlags::TOP_OF_PIPE
+                } else {
+                    src_stages
+                },
+                if dst_stages.is_empty() {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    dst_stages
+                },
+                self.dependency_flags(),
+                &memory_barriers,
+                &buffer_barriers,
+                &image_barriers,
+            )
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_stages_widen_synchronization2_stages() {
+        use vk::PipelineStageFlags as S;
+        use vk::PipelineStageFlags2 as S2;
+        assert_eq!(get_legacy_stages(S2::FRAGMENT_SHADER), S::FRAGMENT_SHADER);
+        assert_eq!(get_legacy_stages(S2::COPY | S2::BLIT), S::TRANSFER);
+        assert_eq!(get_legacy_stages(S2::INDEX_INPUT), S::VERTEX_INPUT);
+        assert!(get_legacy_stages(S2::PRE_RASTERIZATION_SHADERS).contains(S::VERTEX_SHADER));
+        assert!(get_legacy_stages(S2::NONE).is_empty());
+    }
+
+    #[test]
+    fn legacy_access_widens_shader_access() {
+        use vk::AccessFlags as A;
+        use vk::AccessFlags2 as A2;
+        assert_eq!(get_legacy_access(A2::SHADER_SAMPLED_READ), A::SHADER_READ);
+        assert_eq!(
+            get_legacy_access(A2::SHADER_STORAGE_READ | A2::SHADER_STORAGE_WRITE),
+            A::SHADER_READ | A::SHADER_WRITE
+        );
+        assert_eq!(get_legacy_access(A2::TRANSFER_WRITE), A::TRANSFER_WRITE);
+    }
+}
@@ -1,12 +1,20 @@
 pub mod command_buffer;
 pub mod command_pool;
+pub mod descriptor_pool;
 pub mod descriptor_set;
 pub mod descriptor_set_layout;
+pub mod descriptor_update_template;
+pub mod descriptor_write;
 pub mod device;
 pub mod helpers;
 pub mod main_device_queue;
 pub mod memory_barrier;
 pub mod memory_properties;
+pub mod push_constants;
+pub mod push_descriptor;
+pub mod resource_state;
 pub mod semaphore;
 pub mod shader_module;
+mod shader_reflection;
+pub mod texture_heap;
 pub mod toolkit;
@@ -0,0 +1,223 @@
+use crate::core::device::VEDevice;
+use crate::core::shader_module::VEShaderModule;
+use ash::vk;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEPushConstantsError {
+    #[error("push constant range {offset}..{end} exceeds device limit of {limit} bytes")]
+    RangeExceedsDeviceLimit { offset: u32, end: u32, limit: u32 },
+
+    #[error("push constant offset and size must be multiples of 4")]
+    MisalignedRange,
+
+    #[error("push constant range must not be empty")]
+    EmptyRange,
+
+    #[error("push constant range at {offset} with size {size} overflows")]
+    RangeOverflow { offset: u32, size: u32 },
+
+    #[error(
+        "push constants {offset}..{end} overlap a range for {range_stages:?}, but only {stages:?} were given"
+    )]
+    MissingOverlappingStages {
+        offset: u32,
+        end: u32,
+        stages: vk::ShaderStageFlags,
+        range_stages: vk::ShaderStageFlags,
+    },
+
+    #[error(
+        "push constants {offset}..{end} for {stages:?} are not covered by the pipeline layout"
+    )]
+    NotCoveredByLayout {
+        offset: u32,
+        end: u32,
+        stages: vk::ShaderStageFlags,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VEPushConstantRange {
+    pub stage_flags: vk::ShaderStageFlags,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl VEPushConstantRange {
+    pub fn build(&self) -> vk::PushConstantRange {
+        vk::PushConstantRange::default()
+            .stage_flags(self.stage_flags)
+            .offset(self.offset)
+            .size(self.size)
+    }
+}
+
+// Uses the explicitly declared ranges if there are any, otherwise merges whatever the shaders declare
+// into a single range visible to all of them.
+pub(crate) fn resolve_push_constant_ranges(
+    device: &VEDevice,
+    declared: &[VEPushConstantRange],
+    shaders: &[&VEShaderModule],
+) -> Result<Vec<VEPushConstantRange>, VEPushConstantsError> {
+    let ranges = if declared.is_empty() {
+        let reflected: Vec<VEPushConstantRange> = shaders
+            .iter()
+            .filter_map(|shader| shader.push_constant_range)
+            .collect();
+        match reflected.first() {
+            None => vec![],
+            Some(first) => {
+                let merged = reflected.iter().fold(*first, |acc, range| {
+                    let start = acc.offset.min(range.offset);
+                    let end = (acc.offset.saturating_add(acc.size))
+                        .max(range.offset.saturating_add(range.size));
+                    VEPushConstantRange {
+                        stage_flags: acc.stage_flags | range.stage_flags,
+                        offset: start,
+                        size: end - start,
+                    }
+                });
+                vec![merged]
+            }
+        }
+    } else {
+        declared.to_vec()
+    };
+
+    let limit = device.properties.limits.max_push_constants_size;
+    for range in &ranges {
+        if range.size == 0 {
+            return Err(VEPushConstantsError::EmptyRange);
+        }
+        if !range.offset.is_multiple_of(4) || !range.size.is_multiple_of(4) {
+            return Err(VEPushConstantsError::MisalignedRange);
+        }
+        let end =
+            range
+                .offset
+                .checked_add(range.size)
+                .ok_or(VEPushConstantsError::RangeOverflow {
+                    offset: range.offset,
+                    size: range.size,
+                })?;
+        if end > limit {
+            return Err(VEPushConstantsError::RangeExceedsDeviceLimit {
+                offset: range.offset,
+                end,
+                limit,
+            });
+        }
+    }
+
+    Ok(ranges)
+}
+
+// Every stage in `stage_flags` needs a range covering the update, and every range that overlaps
+// the update must have all of its stages in `stage_flags`.
+pub(crate) fn validate_push_constants_update(
+    ranges: &[VEPushConstantRange],
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+    size: u32,
+) -> Result<(), VEPushConstantsError> {
+    if size == 0 {
+        return Err(VEPushConstantsError::EmptyRange);
+    }
+    if !offset.is_multiple_of(4) || !size.is_multiple_of(4) {
+        return Err(VEPushConstantsError::MisalignedRange);
+    }
+    let end = offset
+        .checked_add(size)
+        .ok_or(VEPushConstantsError::RangeOverflow { offset, size })?;
+
+    let covers = |range: &VEPushConstantRange| {
+        range.offset <= offset && range.offset as u64 + range.size as u64 >= end as u64
+    };
+    let overlaps = |range: &VEPushConstantRange| {
+        range.offset < end && (offset as u64) < range.offset as u64 + range.size as u64
+    };
+
+    for bit in 0..u32::BITS {
+        let stage = vk::ShaderStageFlags::from_raw(stage_flags.as_raw() & (1 << bit));
+        if !stage.is_empty()
+            && !ranges
+                .iter()
+                .any(|range| range.stage_flags.contains(stage) && covers(range))
+        {
+            return Err(VEPushConstantsError::NotCoveredByLayout {
+                offset,
+                end,
+                stages: stage,
+            });
+        }
+    }
+
+    for range in ranges {
+        if overlaps(range) && !stage_flags.contains(range.stage_flags) {
+            return Err(VEPushConstantsError::MissingOverlappingStages {
+                offset,
+                end,
+                stages: stage_flags,
+                range_stages: range.stage_flags,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(stage_flags: vk::ShaderStageFlags, offset: u32, size: u32) -> VEPushConstantRange {
+        VEPushConstantRange {
+            stage_flags,
+            offset,
+            size,
+        }
+    }
+
+    #[test]
+    fn update_needs_a_covering_range_per_stage() {
+        let ranges = [
+            range(vk::ShaderStageFlags::VERTEX, 0, 16),
+            range(vk::ShaderStageFlags::FRAGMENT, 16, 16),
+        ];
+        assert!(
+            validate_push_constants_update(&ranges, vk::ShaderStageFlags::VERTEX, 0, 16).is_ok()
+        );
+        assert!(matches!(
+            validate_push_constants_update(&ranges, vk::ShaderStageFlags::VERTEX, 8, 16),
+            Err(VEPushConstantsError::NotCoveredByLayout { .. })
+        ));
+    }
+
+    #[test]
+    fn update_must_name_every_stage_of_overlapping_ranges() {
+        let ranges = [
+            range(vk::ShaderStageFlags::VERTEX, 0, 16),
+            range(vk::ShaderStageFlags::FRAGMENT, 0, 16),
+        ];
+        assert!(matches!(
+            validate_push_constants_update(&ranges, vk::ShaderStageFlags::VERTEX, 0, 16),
+            Err(VEPushConstantsError::MissingOverlappingStages { .. })
+        ));
+        assert!(validate_push_constants_update(
+            &ranges,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            0,
+            16
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn update_range_overflow_is_rejected() {
+        let ranges = [range(vk::ShaderStageFlags::VERTEX, 0, 16)];
+        assert!(matches!(
+            validate_push_constants_update(&ranges, vk::ShaderStageFlags::VERTEX, u32::MAX - 3, 8),
+            Err(VEPushConstantsError::RangeOverflow { .. })
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::core::command_buffer::VECommandBuffer;
+use crate::core::descriptor_set::{VEDescriptorSet, VEDescriptorSetError};
+use crate::core::descriptor_set_layout::{
+    VEDescriptorSetAllocator, VEDescriptorSetLayout, VEDescriptorSetLayoutError,
+    VEDescriptorSetLayoutHandle, VEDescriptorSetLayoutMode,
+};
+use crate::core::descriptor_update_template::{
+    data_pointer, VEDescriptorUpdateTemplate, VEDescriptorUpdateTemplateEntry,
+    VEDescriptorUpdateTemplateError,
+};
+use crate::core::descriptor_write::{
+    build_descriptor_writes, prepare_descriptor_writes, VEDescriptorWrite, VEDescriptorWriteError,
+};
+use crate::core::device::VEDevice;
+use ash::vk;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEPushDescriptorError {
+    #[error("set {0} was not created as a push descriptor layout")]
+    NotAPushDescriptorSet(u32),
+
+    #[error("fallback allocator locking failed")]
+    LockingFailed,
+
+    #[error("descriptor write failed")]
+    WriteFailed(#[from] VEDescriptorWriteError),
+
+    #[error("descriptor set error")]
+    DescriptorSetError(#[from] VEDescriptorSetError),
+
+    #[error("descriptor set layout error")]
+    DescriptorSetLayoutError(#[from] VEDescriptorSetLayoutError),
+
+    #[error("descriptor update template error")]
+    DescriptorUpdateTemplateError(#[from] VEDescriptorUpdateTemplateError),
+}
+
+// Push descriptor sets of a pipeline layout. Sets whose layout fell back to regular descriptor sets
+// get transient sets allocated, bound and left in their pool until `reset`.
+pub(crate) struct VEPushDescriptorSets {
+    device: Arc<VEDevice>,
+    bind_point: vk::PipelineBindPoint,
+    native: Vec<(u32, Arc<VEDescriptorSetLayoutHandle>)>,
+    fallbacks: Mutex<Vec<(u32, VEDescriptorSetAllocator)>>,
+}
+
+impl VEPushDescriptorSets {
+    pub fn new(
+        device: Arc<VEDevice>,
+        bind_point: vk::PipelineBindPoint,
+        set_layouts: &[&VEDescriptorSetLayout],
+    ) -> VEPushDescriptorSets {
+        let mut native = vec![];
+        let mut fallbacks = vec![];
+        for (index, layout) in set_layouts.iter().enumerate() {
+            match layout.mode {
+                VEDescriptorSetLayoutMode::PushDescriptor => {
+                    native.push((index as u32, layout.handle.clone()))
+                }
+                VEDescriptorSetLayoutMode::PushDescriptorFallback => {
+                    fallbacks.push((index as u32, layout.create_transient_allocator()))
+                }
+                _ => {}
+            }
+        }
+        VEPushDescriptorSets {
+            device,
+            bind_point,
+            native,
+            fallbacks: Mutex::new(fallbacks),
+        }
+    }
+
+    pub fn push(
+        &self,
+        command_buffer: &VECommandBuffer,
+        pipeline_layout: vk::PipelineLayout,
+        set: u32,
+        writes: &[VEDescriptorWrite],
+    ) -> Result<(), VEPushDescriptorError> {
+        let native = self.native.iter().find(|(index, _)| *index == set);
+        match (&self.device.push_descriptor, native) {
+            (Some(loader), Some((_, layout))) => {
+                let prepared = prepare_descriptor_writes(&self.device, &layout.fields, writes)?;
+                let writes = build_descriptor_writes(&prepared, vk::DescriptorSet::null());
+                unsafe {
+                    loader.cmd_push_descriptor_set(
+                        command_buffer.handle,
+                        self.bind_point,
+                        pipeline_layout,
+                        set,
+                        &writes,
+                    );
+                }
+                Ok(())
+            }
+            _ => {
+                let descriptor_set = self.allocate_fallback(set)?;
+                descriptor_set.update(writes)?;
+                self.bind_fallback(command_buffer, pipeline_layout, set, &descriptor_set);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn push_with_template<T: Copy>(
+        &self,
+        command_buffer: &VECommandBuffer,
+        pipeline_layout: vk::PipelineLayout,
+        set: u32,
+        template: &VEDescriptorUpdateTemplate<T>,
+        data: &T,
+    ) -> Result<(), VEPushDescriptorError> {
+        match &self.device.push_descriptor {
+            Some(loader) if template.push_descriptor => {
+                unsafe {
+                    loader.cmd_push_descriptor_set_with_template(
+                        command_buffer.handle,
+                        template.handle,
+                        pipeline_layout,
+                        set,
+                        data_pointer(data),
+                    );
+                }
+                Ok(())
+            }
+            _ => {
+                let descriptor_set = self.allocate_fallback(set)?;
+                template.update(&descriptor_set, data)?;
+                self.bind_fallback(command_buffer, pipeline_layout, set, &descriptor_set);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn create_template<T: Copy>(
+        &self,
+        pipeline_layout: vk::PipelineLayout,
+        set: u32,
+        entries: &[VEDescriptorUpdateTemplateEntry],
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VEPushDescriptorError> {
+        if self.device.push_descriptor.is_some()
+            && self.native.iter().any(|(index, _)| *index == set)
+        {
+            return Ok(VEDescriptorUpdateTemplate::new_push_descriptor(
+                self.device.clone(),
+                self.bind_point,
+                pipeline_layout,
+                set,
+                entries,
+            )?);
+        }
+        let fallbacks = self
+            .fallbacks
+            .lock()
+            .map_err(|_| VEPushDescriptorError::LockingFailed)?;
+        let (_, allocator) = fallbacks
+            .iter()
+            .find(|(index, _)| *index == set)
+            .ok_or(VEPushDescriptorError::NotAPushDescriptorSet(set))?;
+        Ok(VEDescriptorUpdateTemplate::from_layout_handle(
+            self.device.clone(),
+            &allocator.layout,
+            entries,
+        )?)
+    }
+
+    // Only needed for the fallback path, must be called once the command buffers using the
+    // previously pushed sets have finished executing.
+    pub fn reset(&self) -> Result<(), VEPushDescriptorError> {
+        let mut fallbacks = self
+            .fallbacks
+            .lock()
+            .map_err(|_| VEPushDescriptorError::LockingFailed)?;
+        for (_, allocator) in fallbacks.iter_mut() {
+            allocator.reset()?;
+        }
+        Ok(())
+    }
+
+    fn allocate_fallback(&self, set: u32) -> Result<VEDescriptorSet, VEPushDescriptorError> {
+        let mut fallbacks = self
+            .fallbacks
+            .lock()
+            .map_err(|_| VEPushDescriptorError::LockingFailed)?;
+        let (_, allocator) = fallbacks
+            .iter_mut()
+            .find(|(index, _)| *index == set)
+            .ok_or(VEPushDescriptorError::NotAPushDescriptorSet(set))?;
+        Ok(allocator.allocate()?)
+    }
+
+    fn bind_fallback(
+        &self,
+        command_buffer: &VECommandBuffer,
+        pipeline_layout: vk::PipelineLayout,
+        set: u32,
+        descriptor_set: &VEDescriptorSet,
+    ) {
+        unsafe {
+            self.device.device.cmd_bind_descriptor_sets(
+                command_buffer.handle,
+                self.bind_point,
+                pipeline_layout,
+                set,
+                &[descriptor_set.set],
+                &[],
+            );
+        }
+    }
+}
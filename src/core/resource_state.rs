@@ -0,0 +1,366 @@
+use ash::vk;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEImageAccess {
+    ColorAttachment,
+    DepthAttachment,
+    DepthRead,
+    InputAttachment,
+    VertexSampled,
+    FragmentSampled,
+    ComputeSampled,
+    FragmentStorageRead,
+    FragmentStorageWrite,
+    ComputeStorageRead,
+    ComputeStorageWrite,
+    TransferRead,
+    TransferWrite,
+    Present,
+    // any access from any stage, the layout descriptors are written with
+    General,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEBufferAccess {
+    VertexInput,
+    IndexInput,
+    IndirectRead,
+    VertexUniform,
+    FragmentUniform,
+    ComputeUniform,
+    VertexStorageRead,
+    FragmentStorageRead,
+    FragmentStorageWrite,
+    ComputeStorageRead,
+    ComputeStorageWrite,
+    TransferRead,
+    TransferWrite,
+    HostRead,
+    HostWrite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct VEAccessInfo {
+    pub stages: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+    pub layout: vk::ImageLayout,
+    pub write: bool,
+}
+
+fn access_info(
+    stages: vk::PipelineStageFlags2,
+    access: vk::AccessFlags2,
+    layout: vk::ImageLayout,
+    write: bool,
+) -> VEAccessInfo {
+    VEAccessInfo {
+        stages,
+        access,
+        layout,
+        write,
+    }
+}
+
+pub(crate) fn get_image_access_info(access: VEImageAccess) -> VEAccessInfo {
+    use vk::AccessFlags2 as A;
+    use vk::ImageLayout as L;
+    use vk::PipelineStageFlags2 as S;
+    match access {
+        VEImageAccess::ColorAttachment => access_info(
+            S::COLOR_ATTACHMENT_OUTPUT,
+            A::COLOR_ATTACHMENT_READ | A::COLOR_ATTACHMENT_WRITE,
+            L::COLOR_ATTACHMENT_OPTIMAL,
+            true,
+        ),
+        VEImageAccess::DepthAttachment => access_info(
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::DEPTH_STENCIL_ATTACHMENT_READ | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            true,
+        ),
+        VEImageAccess::DepthRead => access_info(
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::DEPTH_STENCIL_ATTACHMENT_READ,
+            L::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            false,
+        ),
+        VEImageAccess::InputAttachment => access_info(
+            S::FRAGMENT_SHADER,
+            A::INPUT_ATTACHMENT_READ,
+            L::SHADER_READ_ONLY_OPTIMAL,
+            false,
+        ),
+        VEImageAccess::VertexSampled => access_info(
+            S::VERTEX_SHADER,
+            A::SHADER_SAMPLED_READ,
+            L::SHADER_READ_ONLY_OPTIMAL,
+            false,
+        ),
+        VEImageAccess::FragmentSampled => access_info(
+            S::FRAGMENT_SHADER,
+            A::SHADER_SAMPLED_READ,
+            L::SHADER_READ_ONLY_OPTIMAL,
+            false,
+        ),
+        VEImageAccess::ComputeSampled => access_info(
+            S::COMPUTE_SHADER,
+            A::SHADER_SAMPLED_READ,
+            L::SHADER_READ_ONLY_OPTIMAL,
+            false,
+        ),
+        VEImageAccess::FragmentStorageRead => access_info(
+            S::FRAGMENT_SHADER,
+            A::SHADER_STORAGE_READ,
+            L::GENERAL,
+            false,
+        ),
+        VEImageAccess::FragmentStorageWrite => access_info(
+            S::FRAGMENT_SHADER,
+            A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE,
+            L::GENERAL,
+            true,
+        ),
+        VEImageAccess::ComputeStorageRead => {
+            access_info(S::COMPUTE_SHADER, A::SHADER_STORAGE_READ, L::GENERAL, false)
+        }
+        VEImageAccess::ComputeStorageWrite => access_info(
+            S::COMPUTE_SHADER,
+            A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE,
+            L::GENERAL,
+            true,
+        ),
+        VEImageAccess::TransferRead => access_info(
+            S::TRANSFER,
+            A::TRANSFER_READ,
+            L::TRANSFER_SRC_OPTIMAL,
+            false,
+        ),
+        VEImageAccess::TransferWrite => access_info(
+            S::TRANSFER,
+            A::TRANSFER_WRITE,
+            L::TRANSFER_DST_OPTIMAL,
+            true,
+        ),
+        VEImageAccess::Present => access_info(S::NONE, A::NONE, L::PRESENT_SRC_KHR, false),
+        VEImageAccess::General => access_info(
+            S::ALL_COMMANDS,
+            A::MEMORY_READ | A::MEMORY_WRITE,
+            L::GENERAL,
+            true,
+        ),
+    }
+}
+
+pub(crate) fn get_buffer_access_info(access: VEBufferAccess) -> VEAccessInfo {
+    use vk::AccessFlags2 as A;
+    use vk::PipelineStageFlags2 as S;
+    let (stages, access, write) = match access {
+        VEBufferAccess::VertexInput => (S::VERTEX_ATTRIBUTE_INPUT, A::VERTEX_ATTRIBUTE_READ, false),
+        VEBufferAccess::IndexInput => (S::INDEX_INPUT, A::INDEX_READ, false),
+        VEBufferAccess::IndirectRead => (S::DRAW_INDIRECT, A::INDIRECT_COMMAND_READ, false),
+        VEBufferAccess::VertexUniform => (S::VERTEX_SHADER, A::UNIFORM_READ, false),
+        VEBufferAccess::FragmentUniform => (S::FRAGMENT_SHADER, A::UNIFORM_READ, false),
+        VEBufferAccess::ComputeUniform => (S::COMPUTE_SHADER, A::UNIFORM_READ, false),
+        VEBufferAccess::VertexStorageRead => (S::VERTEX_SHADER, A::SHADER_STORAGE_READ, false),
+        VEBufferAccess::FragmentStorageRead => (S::FRAGMENT_SHADER, A::SHADER_STORAGE_READ, false),
+        VEBufferAccess::FragmentStorageWrite => (
+            S::FRAGMENT_SHADER,
+            A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE,
+            true,
+        ),
+        VEBufferAccess::ComputeStorageRead => (S::COMPUTE_SHADER, A::SHADER_STORAGE_READ, false),
+        VEBufferAccess::ComputeStorageWrite => (
+            S::COMPUTE_SHADER,
+            A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE,
+            true,
+        ),
+        VEBufferAccess::TransferRead => (S::TRANSFER, A::TRANSFER_READ, false),
+        VEBufferAccess::TransferWrite => (S::TRANSFER, A::TRANSFER_WRITE, true),
+        VEBufferAccess::HostRead => (S::HOST, A::HOST_READ, false),
+        VEBufferAccess::HostWrite => (S::HOST, A::HOST_WRITE, true),
+    };
+    access_info(stages, access, vk::ImageLayout::UNDEFINED, write)
+}
+
+pub(crate) const WRITE_ACCESS: vk::AccessFlags2 = vk::AccessFlags2::from_raw(
+    vk::AccessFlags2::SHADER_WRITE.as_raw()
+        | vk::AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+        | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags2::TRANSFER_WRITE.as_raw()
+        | vk::AccessFlags2::HOST_WRITE.as_raw()
+        | vk::AccessFlags2::MEMORY_WRITE.as_raw(),
+);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct VEStateBarrier {
+    pub src_stages: vk::PipelineStageFlags2,
+    pub src_access: vk::AccessFlags2,
+    pub dst_stages: vk::PipelineStageFlags2,
+    pub dst_access: vk::AccessFlags2,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+}
+
+// Last known use of a buffer or of one image subresource, as recorded into command buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VEResourceState {
+    pub layout: vk::ImageLayout,
+    write_stages: vk::PipelineStageFlags2,
+    write_access: vk::AccessFlags2,
+    // Readers since the last write.
+    read_stages: vk::PipelineStageFlags2,
+    // Stages and accesses the last write is already visible to.
+    visible_stages: vk::PipelineStageFlags2,
+    visible_access: vk::AccessFlags2,
+}
+
+impl VEResourceState {
+    pub(crate) fn new(layout: vk::ImageLayout) -> VEResourceState {
+        VEResourceState {
+            layout,
+            ..VEResourceState::default()
+        }
+    }
+
+    // State after commands outside of the tracking wrote to the resource, the next use waits for
+    // everything.
+    pub(crate) fn unknown(layout: vk::ImageLayout) -> VEResourceState {
+        VEResourceState {
+            layout,
+            write_stages: vk::PipelineStageFlags2::ALL_COMMANDS,
+            write_access: vk::AccessFlags2::MEMORY_WRITE,
+            ..VEResourceState::default()
+        }
+    }
+
+    // Moves the state to `info` and returns the barrier needed for it, if any. Buffers pass
+    // `tracks_layout` false.
+    pub(crate) fn require(
+        &mut self,
+        info: VEAccessInfo,
+        tracks_layout: bool,
+    ) -> Option<VEStateBarrier> {
+        let transition = tracks_layout && self.layout != info.layout;
+        let visible =
+            self.visible_stages.contains(info.stages) && self.visible_access.contains(info.access);
+
+        let barrier = if transition {
+            // Layout transitions are writes, they wait for the readers as well.
+            self.visible_stages = info.stages;
+            self.visible_access = info.access;
+            Some(VEStateBarrier {
+                src_stages: self.write_stages | self.read_stages,
+                src_access: self.write_access,
+                dst_stages: info.stages,
+                dst_access: info.access,
+                old_layout: self.layout,
+                new_layout: info.layout,
+            })
+        } else if !self.write_stages.is_empty() && (info.write || !visible) {
+            self.visible_stages |= info.stages;
+            self.visible_access |= info.access;
+            Some(VEStateBarrier {
+                src_stages: self.write_stages | self.read_stages,
+                src_access: self.write_access,
+                dst_stages: info.stages,
+                dst_access: info.access,
+                old_layout: self.layout,
+                new_layout: self.layout,
+            })
+        } else if info.write && !self.read_stages.is_empty() {
+            // Write after read only needs an execution dependency.
+            Some(VEStateBarrier {
+                src_stages: self.read_stages,
+                src_access: vk::AccessFlags2::NONE,
+                dst_stages: info.stages,
+                dst_access: vk::AccessFlags2::NONE,
+                old_layout: self.layout,
+                new_layout: self.layout,
+            })
+        } else {
+            None
+        };
+
+        if tracks_layout {
+            self.layout = info.layout;
+        }
+        if info.write {
+            self.write_stages = info.stages;
+            self.write_access = info.access & WRITE_ACCESS;
+            self.read_stages = vk::PipelineStageFlags2::NONE;
+            self.visible_stages = vk::PipelineStageFlags2::NONE;
+            self.visible_access = vk::AccessFlags2::NONE;
+        } else if transition {
+            // Later readers in other stages have to wait for the transition through this reader.
+            self.write_stages = info.stages;
+            self.write_access = vk::AccessFlags2::NONE;
+            self.read_stages = vk::PipelineStageFlags2::NONE;
+        } else {
+            self.read_stages |= info.stages;
+        }
+        barrier
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VESubresourceRange {
+    pub base_mip: u32,
+    pub mip_count: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+}
+
+impl VESubresourceRange {
+    pub fn new(base_mip: u32, mip_count: u32, base_layer: u32, layer_count: u32) -> Self {
+        VESubresourceRange {
+            base_mip,
+            mip_count,
+            base_layer,
+            layer_count,
+        }
+    }
+
+    // Every mip and layer of the image.
+    pub fn all() -> Self {
+        VESubresourceRange::new(0, vk::REMAINING_MIP_LEVELS, 0, vk::REMAINING_ARRAY_LAYERS)
+    }
+
+    pub fn mip(level: u32) -> Self {
+        VESubresourceRange::new(level, 1, 0, 1)
+    }
+
+    // Whether the range is not empty and lies inside an image with these mips and layers.
+    pub(crate) fn fits(&self, mip_levels: u32, array_layers: u32) -> bool {
+        let mip_end = self.base_mip.checked_add(self.mip_count);
+        let layer_end = self.base_layer.checked_add(self.layer_count);
+        self.mip_count > 0
+            && self.layer_count > 0
+            && mip_end.is_some_and(|end| end <= mip_levels)
+            && layer_end.is_some_and(|end| end <= array_layers)
+    }
+
+    pub fn build(&self, aspect: vk::ImageAspectFlags) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange::default()
+            .aspect_mask(aspect)
+            .base_mip_level(self.base_mip)
+            .level_count(self.mip_count)
+            .base_array_layer(self.base_layer)
+            .layer_count(self.layer_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subresource_range_fits_rejects_overflowing_ranges() {
+        assert!(VESubresourceRange::new(0, 4, 0, 6).fits(4, 6));
+        assert!(VESubresourceRange::new(3, 1, 5, 1).fits(4, 6));
+        assert!(!VESubresourceRange::new(0, 0, 0, 1).fits(4, 6));
+        assert!(!VESubresourceRange::new(2, 3, 0, 1).fits(4, 6));
+        assert!(!VESubresourceRange::new(1, u32::MAX, 0, 1).fits(4, 6));
+        assert!(!VESubresourceRange::new(0, 1, u32::MAX, 2).fits(4, 6));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::core::device::VEDevice;
+use crate::core::push_constants::VEPushConstantRange;
+use crate::core::shader_reflection::reflect_push_constant_range;
 use ash::util::read_spv;
 use ash::vk;
 use ash::vk::ShaderModuleCreateInfo;
@@ -24,10 +26,19 @@ pub enum VEShaderModuleType {
     Compute,
 }
 
+pub fn get_shader_stage_flags(typ: &VEShaderModuleType) -> vk::ShaderStageFlags {
+    match typ {
+        VEShaderModuleType::Vertex => vk::ShaderStageFlags::VERTEX,
+        VEShaderModuleType::Fragment => vk::ShaderStageFlags::FRAGMENT,
+        VEShaderModuleType::Compute => vk::ShaderStageFlags::COMPUTE,
+    }
+}
+
 pub struct VEShaderModule {
     device: Arc<VEDevice>,
     pub handle: vk::ShaderModule,
     pub typ: VEShaderModuleType,
+    pub push_constant_range: Option<VEPushConstantRange>,
 }
 
 impl VEShaderModule {
@@ -40,10 +51,18 @@ impl VEShaderModule {
         let info = ShaderModuleCreateInfo::default().code(&spirv);
         let handle = unsafe { device.device.create_shader_module(&info, None)? };
 
+        let push_constant_range =
+            reflect_push_constant_range(&spirv).map(|(offset, size)| VEPushConstantRange {
+                stage_flags: get_shader_stage_flags(&typ),
+                offset,
+                size,
+            });
+
         Ok(VEShaderModule {
             device,
             handle,
             typ,
+            push_constant_range,
         })
     }
 
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+const SPIRV_MAGIC: u32 = 0x07230203;
+const SPIRV_HEADER_WORDS: usize = 5;
+
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_ROW_MAJOR: u32 = 4;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+
+// Types in a module can refer to themselves, real push constant blocks nest far less deep.
+const MAX_TYPE_DEPTH: u32 = 64;
+
+enum SpirvType {
+    Scalar(u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Array(u32, u32),
+    Struct(Vec<u32>),
+    Pointer(u32),
+}
+
+#[derive(Default, Clone)]
+struct MemberLayout {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    row_major: bool,
+}
+
+#[derive(Default)]
+struct SpirvModule {
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    array_strides: HashMap<u32, u32>,
+    members: HashMap<(u32, u32), MemberLayout>,
+    push_constant_pointers: Vec<u32>,
+}
+
+impl SpirvModule {
+    fn parse(spirv: &[u32]) -> Option<SpirvModule> {
+        if spirv.len() < SPIRV_HEADER_WORDS || spirv[0] != SPIRV_MAGIC {
+            return None;
+        }
+        let mut module = SpirvModule::default();
+        let mut cursor = SPIRV_HEADER_WORDS;
+        while cursor < spirv.len() {
+            let word_count = (spirv[cursor] >> 16) as usize;
+            let opcode = spirv[cursor] & 0xffff;
+            if word_count == 0 || cursor + word_count > spirv.len() {
+                return None;
+            }
+            let operands = &spirv[cursor + 1..cursor + word_count];
+            module.read_instruction(opcode, operands);
+            cursor += word_count;
+        }
+        Some(module)
+    }
+
+    fn read_instruction(&mut self, opcode: u32, operands: &[u32]) {
+        match (opcode, operands) {
+            (OP_TYPE_BOOL, [id]) => {
+                self.types.insert(*id, SpirvType::Scalar(4));
+            }
+            (OP_TYPE_INT, [id, width, ..]) | (OP_TYPE_FLOAT, [id, width, ..]) => {
+                self.types.insert(*id, SpirvType::Scalar(width / 8));
+            }
+            (OP_TYPE_VECTOR, [id, component, count]) => {
+                self.types
+                    .insert(*id, SpirvType::Vector(*component, *count));
+            }
+            (OP_TYPE_MATRIX, [id, column, count]) => {
+                self.types.insert(*id, SpirvType::Matrix(*column, *count));
+            }
+            (OP_TYPE_ARRAY, [id, element, length]) => {
+                self.types.insert(*id, SpirvType::Array(*element, *length));
+            }
+            (OP_TYPE_STRUCT, [id, members @ ..]) => {
+                self.types.insert(*id, SpirvType::Struct(members.to_vec()));
+            }
+            (OP_TYPE_POINTER, [id, _, pointee]) => {
+                self.types.insert(*id, SpirvType::Pointer(*pointee));
+            }
+            (OP_CONSTANT, [_, id, value, ..]) => {
+                self.constants.insert(*id, *value);
+            }
+            (OP_VARIABLE, [pointer_type, _, STORAGE_CLASS_PUSH_CONSTANT, ..]) => {
+                self.push_constant_pointers.push(*pointer_type);
+            }
+            (OP_DECORATE, [target, DECORATION_ARRAY_STRIDE, stride]) => {
+                self.array_strides.insert(*target, *stride);
+            }
+            (OP_MEMBER_DECORATE, [structure, member, decoration, literals @ ..]) => {
+                let layout = self.members.entry((*structure, *member)).or_default();
+                match (*decoration, literals) {
+                    (DECORATION_OFFSET, [offset]) => layout.offset = Some(*offset),
+                    (DECORATION_MATRIX_STRIDE, [stride]) => layout.matrix_stride = Some(*stride),
+                    (DECORATION_ROW_MAJOR, []) => layout.row_major = true,
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Sizes that overflow u32, and types nested deeper than any real shader, give None.
+    fn size_of(&self, id: u32, layout: &MemberLayout, depth: u32) -> Option<u32> {
+        let depth = depth.checked_sub(1)?;
+        match self.types.get(&id)? {
+            SpirvType::Scalar(size) => Some(*size),
+            SpirvType::Vector(component, count) => {
+                self.size_of(*component, layout, depth)?.checked_mul(*count)
+            }
+            SpirvType::Matrix(column, columns) => {
+                let rows = match self.types.get(column)? {
+                    SpirvType::Vector(_, rows) => *rows,
+                    _ => return None,
+                };
+                let column_size = self.size_of(*column, layout, depth)?;
+                match (layout.matrix_stride, layout.row_major) {
+                    (Some(stride), true) => stride.checked_mul(rows),
+                    (Some(stride), false) => stride.checked_mul(*columns),
+                    (None, _) => column_size.checked_mul(*columns),
+                }
+            }
+            SpirvType::Array(element, length) => {
+                let length = *self.constants.get(length)?;
+                let stride = match self.array_strides.get(&id) {
+                    Some(stride) => *stride,
+                    None => self.size_of(*element, layout, depth)?,
+                };
+                stride.checked_mul(length)
+            }
+            SpirvType::Struct(_) => Some(self.struct_extent(id, depth)?.1),
+            SpirvType::Pointer(_) => None,
+        }
+    }
+
+    fn struct_extent(&self, id: u32, depth: u32) -> Option<(u32, u32)> {
+        let members = match self.types.get(&id)? {
+            SpirvType::Struct(members) => members,
+            _ => return None,
+        };
+        let mut start = u32::MAX;
+        let mut end = 0;
+        let mut running_offset = 0;
+        for (index, member) in members.iter().enumerate() {
+            let layout = self
+                .members
+                .get(&(id, index as u32))
+                .cloned()
+                .unwrap_or_default();
+            let offset = layout.offset.unwrap_or(running_offset);
+            let member_end = offset.checked_add(self.size_of(*member, &layout, depth)?)?;
+            start = start.min(offset);
+            end = end.max(member_end);
+            running_offset = member_end;
+        }
+        if members.is_empty() {
+            start = 0;
+        }
+        Some((start, end))
+    }
+}
+
+// Returns the (offset, size) byte range of the push constant block used by the module, if any.
+pub(crate) fn reflect_push_constant_range(spirv: &[u32]) -> Option<(u32, u32)> {
+    let module = SpirvModule::parse(spirv)?;
+    let mut result: Option<(u32, u32)> = None;
+    for pointer in &module.push_constant_pointers {
+        let pointee = match module.types.get(pointer)? {
+            SpirvType::Pointer(pointee) => *pointee,
+            _ => continue,
+        };
+        let (start, end) = module.struct_extent(pointee, MAX_TYPE_DEPTH)?;
+        result = Some(match result {
+            None => (start, end),
+            Some((s, e)) => (s.min(start), e.max(end)),
+        });
+    }
+    let (start, end) = result?;
+    let start = start & !3;
+    let end = end.checked_add(3)? & !3;
+    Some((start, end - start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_words(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    fn instruction(words: &mut Vec<u32>, opcode: u32, operands: &[u32]) {
+        words.push(((operands.len() as u32 + 1) << 16) | opcode);
+        words.extend_from_slice(operands);
+    }
+
+    // A block holding a row-major mat4x3 at offset 0 and a float[3] with an array stride of 16.
+    fn block_module(array_stride: u32, self_referential: bool) -> Vec<u32> {
+        let mut words = vec![SPIRV_MAGIC, 0x0001_0300, 0, 10, 0];
+        instruction(
+            &mut words,
+            OP_MEMBER_DECORATE,
+            &[7, 0, DECORATION_OFFSET, 0],
+        );
+        instruction(
+            &mut words,
+            OP_MEMBER_DECORATE,
+            &[7, 0, DECORATION_ROW_MAJOR],
+        );
+        instruction(
+            &mut words,
+            OP_MEMBER_DECORATE,
+            &[7, 0, DECORATION_MATRIX_STRIDE, 16],
+        );
+        instruction(
+            &mut words,
+            OP_MEMBER_DECORATE,
+            &[7, 1, DECORATION_OFFSET, 48],
+        );
+        instruction(
+            &mut words,
+            OP_DECORATE,
+            &[6, DECORATION_ARRAY_STRIDE, array_stride],
+        );
+        instruction(&mut words, OP_TYPE_FLOAT, &[1, 32]);
+        instruction(&mut words, OP_TYPE_VECTOR, &[2, 1, 3]);
+        instruction(&mut words, OP_TYPE_MATRIX, &[3, 2, 4]);
+        instruction(&mut words, OP_TYPE_INT, &[4, 32, 0]);
+        instruction(&mut words, OP_CONSTANT, &[4, 5, 3]);
+        instruction(&mut words, OP_TYPE_ARRAY, &[6, 1, 5]);
+        let second_member = if self_referential { 7 } else { 6 };
+        instruction(&mut words, OP_TYPE_STRUCT, &[7, 3, second_member]);
+        instruction(
+            &mut words,
+            OP_TYPE_POINTER,
+            &[8, STORAGE_CLASS_PUSH_CONSTANT, 7],
+        );
+        instruction(
+            &mut words,
+            OP_VARIABLE,
+            &[8, 9, STORAGE_CLASS_PUSH_CONSTANT],
+        );
+        words
+    }
+
+    #[test]
+    fn equirectangular_to_cube_has_push_constants() {
+        let spirv = read_words(include_bytes!(
+            "../image/shaders/equirectangular_to_cube.spv"
+        ));
+        assert_eq!(reflect_push_constant_range(&spirv), Some((0, 52)));
+    }
+
+    #[test]
+    fn downsample_shaders_have_no_push_constants() {
+        let shaders: [&[u8]; 5] = [
+            include_bytes!("../image/shaders/downsample_r32f.spv"),
+            include_bytes!("../image/shaders/downsample_rgba16f.spv"),
+            include_bytes!("../image/shaders/downsample_rgba32f.spv"),
+            include_bytes!("../image/shaders/downsample_rgba8.spv"),
+            include_bytes!("../image/shaders/downsample_rgba8_snorm.spv"),
+        ];
+        for shader in shaders {
+            assert_eq!(reflect_push_constant_range(&read_words(shader)), None);
+        }
+    }
+
+    #[test]
+    fn row_major_matrices_and_array_strides_size_the_block() {
+        // 16 * 3 rows for the matrix, then 16 * 3 elements for the array.
+        assert_eq!(
+            reflect_push_constant_range(&block_module(16, false)),
+            Some((0, 96))
+        );
+    }
+
+    #[test]
+    fn overflowing_sizes_give_no_range() {
+        assert_eq!(
+            reflect_push_constant_range(&block_module(0x8000_0000, false)),
+            None
+        );
+    }
+
+    #[test]
+    fn self_referential_types_give_no_range() {
+        assert_eq!(reflect_push_constant_range(&block_module(16, true)), None);
+    }
+}
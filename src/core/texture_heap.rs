@@ -0,0 +1,90 @@
+use crate::core::descriptor_set::{VEDescriptorSet, VEDescriptorSetError};
+use crate::core::descriptor_set_layout::{
+    VEDescriptorSetFieldStage, VEDescriptorSetFieldType, VEDescriptorSetLayout,
+    VEDescriptorSetLayoutError, VEDescriptorSetLayoutField,
+};
+use crate::core::device::VEDevice;
+use crate::image::image::VEImage;
+use crate::image::sampler::VESampler;
+use ash::vk;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VETextureHeapError {
+    #[error("descriptor set layout error")]
+    DescriptorSetLayoutError(#[from] VEDescriptorSetLayoutError),
+
+    #[error("descriptor set error")]
+    DescriptorSetError(#[from] VEDescriptorSetError),
+
+    #[error("texture heap is full")]
+    HeapFull,
+
+    #[error("slot {0} is not allocated")]
+    SlotNotAllocated(u32),
+}
+
+// A single bindless descriptor set holding an array of combined image samplers at binding 0.
+// Shaders index into it with the slot returned from `allocate`.
+pub struct VETextureHeap {
+    pub layout: VEDescriptorSetLayout,
+    pub set: VEDescriptorSet,
+    pub capacity: u32,
+    free_slots: Vec<u32>,
+}
+
+impl VETextureHeap {
+    pub fn new(
+        device: Arc<VEDevice>,
+        capacity: u32,
+        stage: VEDescriptorSetFieldStage,
+    ) -> Result<VETextureHeap, VETextureHeapError> {
+        let mut layout = VEDescriptorSetLayout::new_bindless(
+            device,
+            &[VEDescriptorSetLayoutField {
+                binding: 0,
+                typ: VEDescriptorSetFieldType::Sampler,
+                stage,
+                count: capacity,
+            }],
+        )?;
+        let set = layout.create_descriptor_set()?;
+
+        Ok(VETextureHeap {
+            layout,
+            set,
+            capacity,
+            free_slots: (0..capacity).rev().collect(),
+        })
+    }
+
+    pub fn allocate(
+        &mut self,
+        image: &VEImage,
+        view: vk::ImageView,
+        sampler: &VESampler,
+    ) -> Result<u32, VETextureHeapError> {
+        let slot = self.free_slots.pop().ok_or(VETextureHeapError::HeapFull)?;
+        if let Err(error) = self
+            .set
+            .bind_image_sampler_at(0, slot, image, view, sampler)
+        {
+            self.free_slots.push(slot);
+            return Err(error.into());
+        }
+        Ok(slot)
+    }
+
+    pub fn free(&mut self, slot: u32) -> Result<(), VETextureHeapError> {
+        if slot >= self.capacity || self.free_slots.contains(&slot) {
+            return Err(VETextureHeapError::SlotNotAllocated(slot));
+        }
+        self.free_slots.push(slot);
+        Ok(())
+    }
+
+    pub fn allocated_count(&self) -> u32 {
+        self.capacity - self.free_slots.len() as u32
+    }
+}
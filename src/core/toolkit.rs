@@ -1,29 +1,40 @@
 use crate::buffer::buffer::{VEBuffer, VEBufferError, VEBufferUsage};
+use crate::buffer::typed_buffer::{VETypedBuffer, VETypedBufferError};
 use crate::compute::compute_stage::{VEComputeStage, VEComputeStageError};
 use crate::core::command_buffer::{VECommandBuffer, VECommandBufferError};
 use crate::core::command_pool::{VECommandPool, VECommandPoolError};
 use crate::core::descriptor_set_layout::{
-    VEDescriptorSetLayout, VEDescriptorSetLayoutError, VEDescriptorSetLayoutField,
+    VEDescriptorSetFieldStage, VEDescriptorSetLayout, VEDescriptorSetLayoutError,
+    VEDescriptorSetLayoutField,
 };
 use crate::core::device::{VEDevice, VEDeviceError};
 use crate::core::main_device_queue::VEMainDeviceQueue;
 use crate::core::memory_properties::VEMemoryProperties;
+use crate::core::push_constants::VEPushConstantRange;
 use crate::core::semaphore::{VESemaphore, VESemaphoreError};
 use crate::core::shader_module::{VEShaderModule, VEShaderModuleError, VEShaderModuleType};
+use crate::core::texture_heap::{VETextureHeap, VETextureHeapError};
+use crate::graph::graph_resources::{VEGraphResources, VEGraphResourcesError};
+use crate::graph::render_graph::{VECompiledGraph, VERenderGraph};
 use crate::graphics::attachment::VEAttachment;
-use crate::graphics::render_stage::{
-    VECullMode, VEPrimitiveTopology, VERenderStage, VERenderStageError,
-};
+use crate::graphics::index_buffer::{VEIndexBuffer, VEIndexBufferError, VEIndexType};
+use crate::graphics::mesh::VEMesh;
+use crate::graphics::render_pass_desc::VERenderPassDesc;
+use crate::graphics::render_stage::{VEPipelineDesc, VERenderStage, VERenderStageError};
+use crate::graphics::renderpass::{VERenderPass, VERenderPassError};
 use crate::graphics::vertex_attributes::VertexAttribFormat;
 use crate::graphics::vertex_buffer::{VEVertexBuffer, VEVertexBufferError};
 use crate::image::filtering::VEFiltering;
-use crate::image::image::{VEImage, VEImageError, VEImageUsage};
+use crate::image::image::{
+    get_unsupported_usages, VEImage, VEImageDesc, VEImageError, VEImageUsage, VELoadOptions,
+};
 use crate::image::image_format::VEImageFormat;
 use crate::image::sampler::{VESampler, VESamplerAddressMode, VESamplerError};
 use crate::memory::memory_manager::VEMemoryManager;
 use crate::window::swapchain::{VESwapchain, VESwapchainError};
 use crate::window::window::{AppCallback, VEWindow, VEWindowError};
 use ash::vk;
+use bytemuck::Pod;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
 use winit::dpi::PhysicalSize;
@@ -209,70 +220,162 @@ impl VEToolkit {
         VEDescriptorSetLayout::new(self.device.clone(), fields)
     }
 
-    pub fn create_image_full(
+    pub fn create_bindless_descriptor_set_layout(
         &self,
-        width: u32,
-        height: u32,
-        depth: u32,
+        fields: &[VEDescriptorSetLayoutField],
+    ) -> Result<VEDescriptorSetLayout, VEDescriptorSetLayoutError> {
+        VEDescriptorSetLayout::new_bindless(self.device.clone(), fields)
+    }
+
+    pub fn create_push_descriptor_set_layout(
+        &self,
+        fields: &[VEDescriptorSetLayoutField],
+    ) -> Result<VEDescriptorSetLayout, VEDescriptorSetLayoutError> {
+        VEDescriptorSetLayout::new_push_descriptor(self.device.clone(), fields)
+    }
 
-        format: VEImageFormat,
+    pub fn create_texture_heap(
+        &self,
+        capacity: u32,
+        stage: VEDescriptorSetFieldStage,
+    ) -> Result<VETextureHeap, VETextureHeapError> {
+        VETextureHeap::new(self.device.clone(), capacity, stage)
+    }
 
-        usages: &[VEImageUsage],
-    ) -> Result<VEImage, VEImageError> {
+    pub fn create_image_full(&self, desc: &VEImageDesc) -> Result<VEImage, VEImageError> {
         VEImage::from_full(
             self.device.clone(),
             self.queue.clone(),
             self.command_pool.clone(),
             self.memory_manager.clone(),
-            width,
-            height,
-            depth,
-            format,
-            usages,
+            desc,
         )
     }
 
     pub fn create_image_from_data(
         &self,
         data: &[u8],
+        desc: &VEImageDesc,
+    ) -> Result<VEImage, VEImageError> {
+        VEImage::from_data(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            data,
+            desc,
+        )
+    }
+
+    // First of `candidates` that supports all of `usages`, in order of preference.
+    pub fn choose_format(
+        &self,
+        candidates: &[VEImageFormat],
+        usages: &[VEImageUsage],
+    ) -> Option<VEImageFormat> {
+        candidates
+            .iter()
+            .copied()
+            .find(|format| get_unsupported_usages(&self.device, *format, usages).is_empty())
+    }
+
+    pub fn create_image_from_file(
+        &self,
+        path: &str,
+        usages: &[VEImageUsage],
+    ) -> Result<VEImage, VEImageError> {
+        VEImage::from_file(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            path,
+            usages,
+        )
+    }
 
-        width: u32,
-        height: u32,
-        depth: u32,
+    pub fn create_image_from_file_with_options(
+        &self,
+        path: &str,
+        usages: &[VEImageUsage],
+        options: &VELoadOptions,
+    ) -> Result<VEImage, VEImageError> {
+        VEImage::from_file_with_options(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            path,
+            usages,
+            options,
+        )
+    }
 
-        format: VEImageFormat,
+    pub fn create_image_from_container(
+        &self,
+        bytes: &[u8],
+        usages: &[VEImageUsage],
+    ) -> Result<VEImage, VEImageError> {
+        VEImage::from_container(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            bytes,
+            usages,
+        )
+    }
 
+    pub fn create_cube_from_files(
+        &self,
+        paths: &[&str; 6],
         usages: &[VEImageUsage],
     ) -> Result<VEImage, VEImageError> {
-        VEImage::from_data(
+        VEImage::cube_from_files(
             self.device.clone(),
             self.queue.clone(),
             self.command_pool.clone(),
             self.memory_manager.clone(),
-            data,
-            width,
-            height,
-            depth,
-            format,
+            paths,
             usages,
         )
     }
 
-    pub fn create_image_from_file(
+    pub fn create_cube_from_equirectangular(
         &self,
         path: &str,
+        face_size: u32,
         usages: &[VEImageUsage],
     ) -> Result<VEImage, VEImageError> {
-        VEImage::from_file(
+        VEImage::cube_from_equirectangular(
             self.device.clone(),
             self.queue.clone(),
             self.command_pool.clone(),
             self.memory_manager.clone(),
             path,
+            face_size,
             usages,
         )
     }
 
+    pub fn upload_image_layer(
+        &self,
+        image: &mut VEImage,
+        data: &[u8],
+        layer: u32,
+    ) -> Result<(), VEImageError> {
+        image.upload_layer(
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            data,
+            layer,
+        )
+    }
+
+    pub fn generate_mipmaps(&self, image: &mut VEImage) -> Result<(), VEImageError> {
+        image.generate_mipmaps(self.command_pool.clone())
+    }
+
     pub fn create_sampler(
         &self,
         sampler_address_mode: VESamplerAddressMode,
@@ -312,6 +415,23 @@ impl VEToolkit {
         )
     }
 
+    pub fn create_typed_buffer<T: Pod>(
+        &self,
+        usage: &[VEBufferUsage],
+        capacity: u32,
+        memory_properties: Option<VEMemoryProperties>,
+    ) -> Result<VETypedBuffer<T>, VETypedBufferError> {
+        VETypedBuffer::new(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            usage,
+            capacity,
+            memory_properties,
+        )
+    }
+
     pub fn create_vertex_buffer(&self, buffer: VEBuffer, vertex_count: u32) -> VEVertexBuffer {
         VEVertexBuffer::new(self.device.clone(), buffer, vertex_count)
     }
@@ -346,15 +466,64 @@ impl VEToolkit {
         )
     }
 
+    pub fn create_index_buffer(
+        &self,
+        buffer: VEBuffer,
+        index_type: VEIndexType,
+        index_count: u32,
+    ) -> Result<VEIndexBuffer, VEIndexBufferError> {
+        VEIndexBuffer::new(self.device.clone(), buffer, index_type, index_count)
+    }
+
+    pub fn create_index_buffer_from_file(
+        &self,
+        path: &str,
+        index_type: VEIndexType,
+    ) -> Result<VEIndexBuffer, VEIndexBufferError> {
+        VEIndexBuffer::from_file(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            path,
+            index_type,
+        )
+    }
+
+    pub fn create_index_buffer_from_data(
+        &self,
+        data: Vec<u8>,
+        index_type: VEIndexType,
+    ) -> Result<VEIndexBuffer, VEIndexBufferError> {
+        VEIndexBuffer::from_data(
+            self.device.clone(),
+            self.queue.clone(),
+            self.command_pool.clone(),
+            self.memory_manager.clone(),
+            data,
+            index_type,
+        )
+    }
+
+    pub fn create_mesh(
+        &self,
+        vertex_buffer: VEVertexBuffer,
+        index_buffer: VEIndexBuffer,
+    ) -> VEMesh {
+        VEMesh::new(self.device.clone(), vertex_buffer, index_buffer)
+    }
+
     pub fn create_compute_stage(
         &self,
         set_layouts: &[&VEDescriptorSetLayout],
+        push_constant_ranges: &[VEPushConstantRange],
         shader: &VEShaderModule,
     ) -> Result<VEComputeStage, VEComputeStageError> {
         VEComputeStage::new(
             self.device.clone(),
             self.command_pool.clone(),
             set_layouts,
+            push_constant_ranges,
             shader,
         )
     }
@@ -364,24 +533,59 @@ impl VEToolkit {
         viewport_width: u32,
         viewport_height: u32,
         attachments: &[&VEAttachment],
-        set_layouts: &[&VEDescriptorSetLayout],
-        vertex_shader: &VEShaderModule,
-        fragment_shader: &VEShaderModule,
-        vertex_attributes: &[VertexAttribFormat],
-        primitive_topology: VEPrimitiveTopology,
-        cull_mode: VECullMode,
+        pipeline_desc: &VEPipelineDesc,
     ) -> Result<VERenderStage, VERenderStageError> {
         VERenderStage::new(
             self.device.clone(),
             viewport_width,
             viewport_height,
             attachments,
-            set_layouts,
-            vertex_shader,
-            fragment_shader,
-            vertex_attributes,
-            primitive_topology,
-            cull_mode,
+            pipeline_desc,
         )
     }
+
+    pub fn create_graph_resources(
+        &self,
+        graph: &VERenderGraph,
+        compiled: &VECompiledGraph,
+    ) -> Result<VEGraphResources, VEGraphResourcesError> {
+        VEGraphResources::new(
+            self.device.clone(),
+            self.queue.clone(),
+            self.memory_manager.clone(),
+            graph,
+            compiled,
+        )
+    }
+
+    pub fn create_render_pass(
+        &self,
+        desc: &VERenderPassDesc,
+    ) -> Result<VERenderPass, VERenderPassError> {
+        VERenderPass::from_desc(self.device.clone(), desc)
+    }
+
+    pub fn create_subpass_render_stage(
+        &self,
+        render_pass: Arc<VERenderPass>,
+        desc: &VERenderPassDesc,
+        subpass: u32,
+        pipeline_desc: &VEPipelineDesc,
+    ) -> Result<VERenderStage, VERenderStageError> {
+        VERenderStage::from_render_pass(
+            self.device.clone(),
+            render_pass,
+            desc,
+            subpass,
+            pipeline_desc,
+        )
+    }
+
+    pub fn create_dynamic_render_stage(
+        &self,
+        attachments: &[&VEAttachment],
+        pipeline_desc: &VEPipelineDesc,
+    ) -> Result<VERenderStage, VERenderStageError> {
+        VERenderStage::new_dynamic(self.device.clone(), attachments, pipeline_desc)
+    }
 }
@@ -0,0 +1,206 @@
+// Lifetimes are inclusive ranges of graph steps. Two resources may share memory when their
+// lifetimes do not overlap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEAliasRequest {
+    pub first_step: usize,
+    pub last_step: usize,
+    pub size: u64,
+    pub alignment: u64,
+    pub memory_type_bits: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEAliasHeap {
+    pub size: u64,
+    pub memory_type_bits: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEAliasPlacement {
+    pub heap: usize,
+    pub offset: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VEAliasPlan {
+    pub heaps: Vec<VEAliasHeap>,
+    // Same order as the requests.
+    pub placements: Vec<VEAliasPlacement>,
+    // Requests that used the memory of each request before it, the first use has to wait for them.
+    pub previous: Vec<Vec<usize>>,
+}
+
+fn lifetimes_overlap(a: &VEAliasRequest, b: &VEAliasRequest) -> bool {
+    a.first_step <= b.last_step && b.first_step <= a.last_step
+}
+
+pub(crate) fn align_up(value: u64, alignment: u64) -> u64 {
+    let alignment = alignment.max(1);
+    value.div_ceil(alignment) * alignment
+}
+
+// Lowest aligned offset where `size` bytes fit between the occupied ranges.
+fn find_offset(occupied: &mut [(u64, u64)], size: u64, alignment: u64, limit: u64) -> Option<u64> {
+    occupied.sort_unstable();
+    let mut offset = 0;
+    for (start, end) in occupied.iter() {
+        if offset + size <= *start {
+            break;
+        }
+        offset = offset.max(align_up(*end, alignment));
+    }
+    (offset + size <= limit).then_some(offset)
+}
+
+// Greedy first fit, largest requests first. Every heap is as big as the first request placed in it.
+pub fn plan_aliasing(requests: &[VEAliasRequest]) -> VEAliasPlan {
+    let mut order: Vec<usize> = (0..requests.len()).collect();
+    order.sort_by(|a, b| requests[*b].size.cmp(&requests[*a].size).then(a.cmp(b)));
+
+    let mut heaps: Vec<VEAliasHeap> = vec![];
+    let mut placements: Vec<Option<VEAliasPlacement>> = vec![None; requests.len()];
+    for index in order {
+        let request = &requests[index];
+        let mut placement = None;
+        for (heap_index, heap) in heaps.iter().enumerate() {
+            if heap.memory_type_bits & request.memory_type_bits == 0 {
+                continue;
+            }
+            let mut occupied: Vec<(u64, u64)> = placements
+                .iter()
+                .enumerate()
+                .filter_map(|(other, p)| p.map(|p| (other, p)))
+                .filter(|(other, p)| {
+                    p.heap == heap_index && lifetimes_overlap(request, &requests[*other])
+                })
+                .map(|(other, p)| (p.offset, p.offset + requests[other].size))
+                .collect();
+            if let Some(offset) =
+                find_offset(&mut occupied, request.size, request.alignment, heap.size)
+            {
+                placement = Some(VEAliasPlacement {
+                    heap: heap_index,
+                    offset,
+                });
+                break;
+            }
+        }
+
+        let placement = match placement {
+            Some(placement) => {
+                heaps[placement.heap].memory_type_bits &= request.memory_type_bits;
+                placement
+            }
+            None => {
+                heaps.push(VEAliasHeap {
+                    size: request.size,
+                    memory_type_bits: request.memory_type_bits,
+                });
+                VEAliasPlacement {
+                    heap: heaps.len() - 1,
+                    offset: 0,
+                }
+            }
+        };
+        placements[index] = Some(placement);
+    }
+
+    let placements: Vec<VEAliasPlacement> = placements.into_iter().flatten().collect();
+    let previous = (0..requests.len())
+        .map(|index| {
+            let range = (
+                placements[index].offset,
+                placements[index].offset + requests[index].size,
+            );
+            (0..requests.len())
+                .filter(|other| {
+                    let other_range = (
+                        placements[*other].offset,
+                        placements[*other].offset + requests[*other].size,
+                    );
+                    placements[*other].heap == placements[index].heap
+                        && requests[*other].last_step < requests[index].first_step
+                        && other_range.0 < range.1
+                        && range.0 < other_range.1
+                })
+                .collect()
+        })
+        .collect();
+
+    VEAliasPlan {
+        heaps,
+        placements,
+        previous,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(first_step: usize, last_step: usize, size: u64) -> VEAliasRequest {
+        VEAliasRequest {
+            first_step,
+            last_step,
+            size,
+            alignment: 256,
+            memory_type_bits: 0b11,
+        }
+    }
+
+    fn ranges_overlap(plan: &VEAliasPlan, requests: &[VEAliasRequest], a: usize, b: usize) -> bool {
+        let (pa, pb) = (plan.placements[a], plan.placements[b]);
+        pa.heap == pb.heap
+            && pa.offset < pb.offset + requests[b].size
+            && pb.offset < pa.offset + requests[a].size
+    }
+
+    #[test]
+    fn plan_aliasing_shares_memory_between_disjoint_lifetimes() {
+        let requests = [request(0, 1, 1024), request(2, 3, 1024)];
+        let plan = plan_aliasing(&requests);
+
+        assert_eq!(plan.heaps.len(), 1);
+        assert_eq!(plan.heaps[0].size, 1024);
+        assert_eq!(plan.placements[0], plan.placements[1]);
+        assert!(plan.previous[0].is_empty());
+        assert_eq!(plan.previous[1], vec![0]);
+    }
+
+    #[test]
+    fn plan_aliasing_separates_overlapping_lifetimes() {
+        let requests = [
+            request(0, 2, 1000),
+            request(2, 3, 1000),
+            request(1, 1, 500),
+            request(3, 4, 500),
+        ];
+        let plan = plan_aliasing(&requests);
+
+        for a in 0..requests.len() {
+            for b in 0..requests.len() {
+                if a != b && lifetimes_overlap(&requests[a], &requests[b]) {
+                    assert!(!ranges_overlap(&plan, &requests, a, b), "{a} and {b}");
+                }
+            }
+            assert!(plan.placements[a].offset.is_multiple_of(256));
+            let heap = plan.heaps[plan.placements[a].heap];
+            assert!(plan.placements[a].offset + requests[a].size <= heap.size);
+        }
+        // The last request fits where the first one was.
+        assert!(ranges_overlap(&plan, &requests, 0, 3));
+        assert_eq!(plan.previous[3], vec![0]);
+    }
+
+    #[test]
+    fn plan_aliasing_separates_incompatible_memory_types() {
+        let mut other = request(2, 3, 1024);
+        other.memory_type_bits = 0b100;
+        let requests = [request(0, 1, 1024), other];
+        let plan = plan_aliasing(&requests);
+
+        assert_eq!(plan.heaps.len(), 2);
+        assert_ne!(plan.placements[0].heap, plan.placements[1].heap);
+        assert!(plan.previous.iter().all(|p| p.is_empty()));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::core::command_buffer::VECommandBuffer;
+use crate::core::device::VEDevice;
+use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::memory_barrier::{VEDependencyInfo, VEImageMemoryBarrier2, VEMemoryBarrier2};
+use crate::core::memory_properties::{get_memory_properties_flags, VEMemoryProperties};
+use crate::core::resource_state::VESubresourceRange;
+use crate::graph::alias_planner::{align_up, plan_aliasing, VEAliasPlan, VEAliasRequest};
+use crate::graph::render_graph::{
+    VECompiledGraph, VEGraphBarrierBatch, VEGraphImage, VEGraphPass, VERenderGraph,
+};
+use crate::image::image::{VEImage, VEImageDesc, VEImageError};
+use crate::memory::memory_chunk::{VEMemoryChunkError, VESingleAllocation};
+use crate::memory::memory_manager::VEMemoryManager;
+use ash::vk;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEGraphResourcesError {
+    #[error("image error")]
+    ImageError(#[from] VEImageError),
+
+    #[error("no suitable memory type found")]
+    NoSuitableMemoryTypeFound,
+
+    #[error("memory allocation failed")]
+    MemoryAllocationFailed(#[from] VEMemoryChunkError),
+
+    #[error("binding image memory failed")]
+    MemoryBindingFailed(#[source] vk::Result),
+
+    #[error("no image bound for {0}")]
+    MissingImage(String),
+
+    #[error("locking failed")]
+    LockingFailed,
+}
+
+// What the first use of an aliased image has to wait for.
+struct VEAliasHandoff {
+    step: usize,
+    image: u32,
+    src_stages: vk::PipelineStageFlags2,
+    src_access: vk::AccessFlags2,
+}
+
+// Owns the transient images of a compiled graph and records it.
+pub struct VEGraphResources {
+    device: Arc<VEDevice>,
+    memory_manager: Arc<Mutex<VEMemoryManager>>,
+    transient: HashMap<u32, VEImage>,
+    imported: HashMap<u32, vk::Image>,
+    // Memory of every alias heap and the offset the heap starts at inside it.
+    memory: Vec<(vk::DeviceMemory, u64, VESingleAllocation)>,
+    handoffs: Vec<VEAliasHandoff>,
+}
+
+impl VEGraphResources {
+    pub fn new(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        graph: &VERenderGraph,
+        compiled: &VECompiledGraph,
+    ) -> Result<VEGraphResources, VEGraphResourcesError> {
+        let mut resources = VEGraphResources {
+            device: device.clone(),
+            memory_manager: memory_manager.clone(),
+            transient: HashMap::new(),
+            imported: HashMap::new(),
+            memory: vec![],
+            handoffs: vec![],
+        };
+
+        let mut requests = vec![];
+        for lifetime in &compiled.lifetimes {
+            let Some(desc) = graph.transient_desc(lifetime.image) else {
+                continue;
+            };
+            let mut image = VEImage::unbound(
+                device.clone(),
+                queue.clone(),
+                &VEImageDesc::new(
+                    desc.width,
+                    desc.height,
+                    desc.format,
+                    &graph.image_usages(lifetime.image),
+                )
+                .with_samples(desc.samples),
+            )?;
+            // The graph transitions the image before its first pass, attachments created from
+            // it should expect that layout.
+            if let Some(barrier) = compiled.steps[lifetime.first_step]
+                .barriers
+                .images
+                .iter()
+                .find(|b| b.image == lifetime.image)
+            {
+                image.current_layout = barrier.new_layout;
+            }
+
+            let requirements = image.memory_requirements();
+            requests.push(VEAliasRequest {
+                first_step: lifetime.first_step,
+                last_step: lifetime.last_step,
+                size: requirements.size,
+                alignment: requirements.alignment,
+                memory_type_bits: requirements.memory_type_bits,
+            });
+            resources.transient.insert(lifetime.image, image);
+        }
+
+        let VEAliasPlan {
+            heaps,
+            placements,
+            previous,
+        } = plan_aliasing(&requests);
+
+        for (heap_index, heap) in heaps.iter().enumerate() {
+            let memory_type = device
+                .find_memory_type(
+                    heap.memory_type_bits,
+                    get_memory_properties_flags(Some(VEMemoryProperties::DeviceLocal)),
+                )
+                .ok_or(VEGraphResourcesError::NoSuitableMemoryTypeFound)?;
+            // The heap has to start at an offset every image placed in it is aligned to.
+            let alignment = requests
+                .iter()
+                .zip(&placements)
+                .filter(|(_, p)| p.heap == heap_index)
+                .map(|(r, _)| r.alignment)
+                .max()
+                .unwrap_or(1);
+            let (memory, allocation) = memory_manager
+                .lock()
+                .map_err(|_| VEGraphResourcesError::LockingFailed)?
+                .reserve_memory(memory_type, heap.size + alignment)?;
+            let offset = align_up(allocation.offset, alignment);
+            resources.memory.push((memory, offset, allocation));
+        }
+
+        for (index, lifetime) in compiled.lifetimes.iter().enumerate() {
+            let Some(image) = resources.transient.get(&lifetime.image) else {
+                continue;
+            };
+            let placement = placements[index];
+            let (memory, heap_offset, _) = &resources.memory[placement.heap];
+            unsafe {
+                device
+                    .device
+                    .bind_image_memory(image.handle, *memory, heap_offset + placement.offset)
+                    .map_err(VEGraphResourcesError::MemoryBindingFailed)?
+            };
+
+            for other in &previous[index] {
+                let other = &compiled.lifetimes[*other];
+                resources.handoffs.push(VEAliasHandoff {
+                    step: lifetime.first_step,
+                    image: lifetime.image,
+                    src_stages: other.last_stages,
+                    src_access: other.last_access,
+                });
+            }
+        }
+
+        Ok(resources)
+    }
+
+    pub fn bind_image(&mut self, graph_image: VEGraphImage, image: &VEImage) {
+        self.imported.insert(graph_image.id, image.handle);
+    }
+
+    pub fn image(&self, graph_image: VEGraphImage) -> Option<&VEImage> {
+        self.transient.get(&graph_image.id)
+    }
+
+    pub fn image_mut(&mut self, graph_image: VEGraphImage) -> Option<&mut VEImage> {
+        self.transient.get_mut(&graph_image.id)
+    }
+
+    fn submit_batch(
+        &self,
+        compiled: &VECompiledGraph,
+        command_buffer: &VECommandBuffer,
+        batch: &VEGraphBarrierBatch,
+        step: Option<usize>,
+    ) -> Result<(), VEGraphResourcesError> {
+        let handoffs: Vec<&VEAliasHandoff> = self
+            .handoffs
+            .iter()
+            .filter(|h| Some(h.step) == step)
+            .collect();
+        if batch.is_empty() && handoffs.is_empty() {
+            return Ok(());
+        }
+
+        let mut dependency_info = VEDependencyInfo::new();
+        if let Some(memory) = &batch.memory {
+            dependency_info.add_memory_barrier(memory);
+        }
+        for barrier in &batch.images {
+            let image = match self.transient.get(&barrier.image) {
+                Some(image) => image.handle,
+                None => *self.imported.get(&barrier.image).ok_or_else(|| {
+                    VEGraphResourcesError::MissingImage(
+                        compiled.names[barrier.image as usize].clone(),
+                    )
+                })?,
+            };
+            let mut image_barrier = VEImageMemoryBarrier2 {
+                image,
+                aspect: barrier.aspect,
+                range: VESubresourceRange::all(),
+                old_layout: barrier.old_layout,
+                new_layout: barrier.new_layout,
+                src_stages: barrier.src_stages,
+                src_access: barrier.src_access,
+                dst_stages: barrier.dst_stages,
+                dst_access: barrier.dst_access,
+                queue_transfer: None,
+            };
+            for handoff in handoffs.iter().filter(|h| h.image == barrier.image) {
+                image_barrier.src_stages |= handoff.src_stages;
+                image_barrier.src_access |= handoff.src_access;
+            }
+            dependency_info.add_image_barrier(&image_barrier);
+        }
+        // Images that start without a transition still have to wait for the previous owner of
+        // their memory.
+        for handoff in handoffs
+            .iter()
+            .filter(|h| batch.images.iter().all(|b| b.image != h.image))
+        {
+            dependency_info.add_memory_barrier(&VEMemoryBarrier2 {
+                src_stages: handoff.src_stages,
+                src_access: handoff.src_access,
+                dst_stages: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_access: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            });
+        }
+
+        dependency_info.submit(&self.device, command_buffer);
+        Ok(())
+    }
+
+    // Records the barriers of every step and lets `record_pass` record the pass itself.
+    pub fn record<E: From<VEGraphResourcesError>>(
+        &self,
+        compiled: &VECompiledGraph,
+        command_buffer: &VECommandBuffer,
+        mut record_pass: impl FnMut(VEGraphPass) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for (index, step) in compiled.steps.iter().enumerate() {
+            self.submit_batch(compiled, command_buffer, &step.barriers, Some(index))?;
+            record_pass(step.pass)?;
+        }
+        self.submit_batch(compiled, command_buffer, &compiled.final_barriers, None)?;
+        Ok(())
+    }
+}
+
+impl Drop for VEGraphResources {
+    fn drop(&mut self) {
+        // images have to go before the memory they are bound to
+        self.transient.clear();
+        if let Ok(mut memory_manager) = self.memory_manager.lock() {
+            for (_, _, allocation) in &self.memory {
+                let _ = memory_manager.free_allocation(allocation);
+            }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod alias_planner;
+pub mod graph_resources;
+pub mod render_graph;
@@ -0,0 +1,1000 @@
+use crate::core::descriptor_set_layout::VEDescriptorSetFieldStage;
+use crate::core::memory_barrier::VEMemoryBarrier2;
+use crate::core::resource_state::{VEAccessInfo, VEResourceState, WRITE_ACCESS};
+use crate::image::image::{VEImageUsage, VESampleCount};
+use crate::image::image_format::{get_aspect_from_vk, get_format_info, VEImageFormat};
+use ash::vk;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VERenderGraphError {
+    #[error("resource {0} does not belong to this graph")]
+    UnknownResource(u32),
+
+    #[error("resource {0} was written from an outdated version")]
+    OutdatedResourceVersion(String),
+
+    #[error("resource {0} is used with different layouts in the same pass")]
+    ConflictingImageLayouts(String),
+
+    #[error("only imported resources can be exported, {0} is transient")]
+    ExportingTransientResource(String),
+
+    #[error("passes depend on each other in a cycle")]
+    DependencyCycle,
+}
+
+// Handles are versioned, every write produces a new version of the resource. Reading a version
+// makes the pass depend on the pass that wrote it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VEGraphImage {
+    pub(crate) id: u32,
+    pub(crate) version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VEGraphBuffer {
+    pub(crate) id: u32,
+    pub(crate) version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct VEGraphPass(pub(crate) u32);
+
+// Attachment usages keep the images in the attachment optimal layouts, render passes recorded
+// inside the graph should use them as final layouts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEGraphUsage {
+    ColorAttachment,
+    DepthAttachment,
+    DepthRead,
+    InputAttachment,
+    Sampled(VEDescriptorSetFieldStage),
+    StorageRead(VEDescriptorSetFieldStage),
+    StorageWrite(VEDescriptorSetFieldStage),
+    UniformRead(VEDescriptorSetFieldStage),
+    TransferSource,
+    TransferDestination,
+    VertexBuffer,
+    IndexBuffer,
+    IndirectBuffer,
+    Present,
+}
+
+fn get_shader_stages(stage: VEDescriptorSetFieldStage) -> vk::PipelineStageFlags2 {
+    match stage {
+        VEDescriptorSetFieldStage::All => vk::PipelineStageFlags2::ALL_COMMANDS,
+        VEDescriptorSetFieldStage::AllGraphics => vk::PipelineStageFlags2::ALL_GRAPHICS,
+        VEDescriptorSetFieldStage::Compute => vk::PipelineStageFlags2::COMPUTE_SHADER,
+        VEDescriptorSetFieldStage::Vertex => vk::PipelineStageFlags2::VERTEX_SHADER,
+        VEDescriptorSetFieldStage::Fragment => vk::PipelineStageFlags2::FRAGMENT_SHADER,
+    }
+}
+
+pub(crate) fn get_usage_info(usage: VEGraphUsage, write: bool) -> VEAccessInfo {
+    use vk::AccessFlags2 as A;
+    use vk::ImageLayout as L;
+    use vk::PipelineStageFlags2 as S;
+    let (stages, access, layout) = match usage {
+        VEGraphUsage::ColorAttachment => (
+            S::COLOR_ATTACHMENT_OUTPUT,
+            A::COLOR_ATTACHMENT_READ | A::COLOR_ATTACHMENT_WRITE,
+            L::COLOR_ATTACHMENT_OPTIMAL,
+        ),
+        VEGraphUsage::DepthAttachment => (
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::DEPTH_STENCIL_ATTACHMENT_READ | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        ),
+        VEGraphUsage::DepthRead => (
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::DEPTH_STENCIL_ATTACHMENT_READ,
+            L::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        ),
+        VEGraphUsage::InputAttachment => (
+            S::FRAGMENT_SHADER,
+            A::INPUT_ATTACHMENT_READ,
+            L::SHADER_READ_ONLY_OPTIMAL,
+        ),
+        VEGraphUsage::Sampled(stage) => (
+            get_shader_stages(stage),
+            A::SHADER_SAMPLED_READ,
+            L::SHADER_READ_ONLY_OPTIMAL,
+        ),
+        VEGraphUsage::StorageRead(stage) => {
+            (get_shader_stages(stage), A::SHADER_STORAGE_READ, L::GENERAL)
+        }
+        VEGraphUsage::StorageWrite(stage) => (
+            get_shader_stages(stage),
+            A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE,
+            L::GENERAL,
+        ),
+        VEGraphUsage::UniformRead(stage) => {
+            (get_shader_stages(stage), A::UNIFORM_READ, L::UNDEFINED)
+        }
+        VEGraphUsage::TransferSource => (S::TRANSFER, A::TRANSFER_READ, L::TRANSFER_SRC_OPTIMAL),
+        VEGraphUsage::TransferDestination => {
+            (S::TRANSFER, A::TRANSFER_WRITE, L::TRANSFER_DST_OPTIMAL)
+        }
+        VEGraphUsage::VertexBuffer => (
+            S::VERTEX_ATTRIBUTE_INPUT,
+            A::VERTEX_ATTRIBUTE_READ,
+            L::UNDEFINED,
+        ),
+        VEGraphUsage::IndexBuffer => (S::INDEX_INPUT, A::INDEX_READ, L::UNDEFINED),
+        VEGraphUsage::IndirectBuffer => (S::DRAW_INDIRECT, A::INDIRECT_COMMAND_READ, L::UNDEFINED),
+        // Presentation is synchronized by the semaphore, the barrier only orders the transition
+        VEGraphUsage::Present => (S::NONE, A::NONE, L::PRESENT_SRC_KHR),
+    };
+    VEAccessInfo {
+        stages,
+        access,
+        layout,
+        write,
+    }
+}
+
+pub(crate) fn get_image_usage(usage: VEGraphUsage) -> Option<VEImageUsage> {
+    match usage {
+        VEGraphUsage::ColorAttachment => Some(VEImageUsage::ColorAttachment),
+        VEGraphUsage::DepthAttachment | VEGraphUsage::DepthRead => {
+            Some(VEImageUsage::DepthAttachment)
+        }
+        VEGraphUsage::InputAttachment => Some(VEImageUsage::InputAttachment),
+        VEGraphUsage::Sampled(_) => Some(VEImageUsage::Sampled),
+        VEGraphUsage::StorageRead(_) | VEGraphUsage::StorageWrite(_) => Some(VEImageUsage::Storage),
+        VEGraphUsage::TransferSource => Some(VEImageUsage::TransferSource),
+        VEGraphUsage::TransferDestination => Some(VEImageUsage::TransferDestination),
+        VEGraphUsage::UniformRead(_)
+        | VEGraphUsage::VertexBuffer
+        | VEGraphUsage::IndexBuffer
+        | VEGraphUsage::IndirectBuffer
+        | VEGraphUsage::Present => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VETransientImageDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: VEImageFormat,
+    pub samples: VESampleCount,
+}
+
+#[derive(Clone, Debug)]
+enum VEGraphResourceKind {
+    ImportedImage {
+        aspect: vk::ImageAspectFlags,
+        layout: vk::ImageLayout,
+    },
+    TransientImage(VETransientImageDesc),
+    ImportedBuffer,
+}
+
+#[derive(Clone, Debug)]
+struct VEGraphResource {
+    name: String,
+    kind: VEGraphResourceKind,
+    // Pass that produced each version, version 0 is the initial content.
+    writers: Vec<Option<u32>>,
+    export: Option<VEGraphUsage>,
+}
+
+impl VEGraphResource {
+    fn is_imported(&self) -> bool {
+        !matches!(self.kind, VEGraphResourceKind::TransientImage(_))
+    }
+
+    fn aspect(&self) -> Option<vk::ImageAspectFlags> {
+        match &self.kind {
+            VEGraphResourceKind::ImportedImage { aspect, .. } => Some(*aspect),
+            VEGraphResourceKind::TransientImage(desc) => {
+                Some(get_format_info(desc.format).aspect_flags())
+            }
+            VEGraphResourceKind::ImportedBuffer => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct VEGraphAccess {
+    resource: u32,
+    version: u32,
+    usage: VEGraphUsage,
+    write: bool,
+}
+
+#[derive(Clone, Debug)]
+struct VEGraphPassDesc {
+    name: String,
+    accesses: Vec<VEGraphAccess>,
+    side_effects: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VERenderGraph {
+    resources: Vec<VEGraphResource>,
+    passes: Vec<VEGraphPassDesc>,
+}
+
+pub struct VEGraphPassBuilder<'a> {
+    graph: &'a mut VERenderGraph,
+    pass: u32,
+}
+
+impl VEGraphPassBuilder<'_> {
+    pub fn id(&self) -> VEGraphPass {
+        VEGraphPass(self.pass)
+    }
+
+    pub fn read_image(
+        &mut self,
+        image: VEGraphImage,
+        usage: VEGraphUsage,
+    ) -> Result<&mut Self, VERenderGraphError> {
+        self.graph
+            .add_access(self.pass, image.id, image.version, usage, false)?;
+        Ok(self)
+    }
+
+    pub fn write_image(
+        &mut self,
+        image: VEGraphImage,
+        usage: VEGraphUsage,
+    ) -> Result<VEGraphImage, VERenderGraphError> {
+        let version = self
+            .graph
+            .add_access(self.pass, image.id, image.version, usage, true)?;
+        Ok(VEGraphImage {
+            id: image.id,
+            version,
+        })
+    }
+
+    pub fn read_buffer(
+        &mut self,
+        buffer: VEGraphBuffer,
+        usage: VEGraphUsage,
+    ) -> Result<&mut Self, VERenderGraphError> {
+        self.graph
+            .add_access(self.pass, buffer.id, buffer.version, usage, false)?;
+        Ok(self)
+    }
+
+    pub fn write_buffer(
+        &mut self,
+        buffer: VEGraphBuffer,
+        usage: VEGraphUsage,
+    ) -> Result<VEGraphBuffer, VERenderGraphError> {
+        let version = self
+            .graph
+            .add_access(self.pass, buffer.id, buffer.version, usage, true)?;
+        Ok(VEGraphBuffer {
+            id: buffer.id,
+            version,
+        })
+    }
+
+    // Passes with side effects are never culled, even when nothing reads what they write.
+    pub fn with_side_effects(&mut self) -> &mut Self {
+        self.graph.passes[self.pass as usize].side_effects = true;
+        self
+    }
+}
+
+impl VERenderGraph {
+    pub fn new() -> VERenderGraph {
+        VERenderGraph::default()
+    }
+
+    fn add_resource(&mut self, name: &str, kind: VEGraphResourceKind) -> u32 {
+        self.resources.push(VEGraphResource {
+            name: name.to_string(),
+            kind,
+            writers: vec![None],
+            export: None,
+        });
+        (self.resources.len() - 1) as u32
+    }
+
+    // The image is expected in `layout` when the graph starts executing.
+    pub fn import_image(
+        &mut self,
+        name: &str,
+        format: vk::Format,
+        layout: vk::ImageLayout,
+    ) -> VEGraphImage {
+        let aspect = get_aspect_from_vk(format);
+        let id = self.add_resource(name, VEGraphResourceKind::ImportedImage { aspect, layout });
+        VEGraphImage { id, version: 0 }
+    }
+
+    pub fn import_buffer(&mut self, name: &str) -> VEGraphBuffer {
+        let id = self.add_resource(name, VEGraphResourceKind::ImportedBuffer);
+        VEGraphBuffer { id, version: 0 }
+    }
+
+    // Transient images only live for the duration of the graph and may share memory.
+    pub fn create_image(&mut self, name: &str, desc: VETransientImageDesc) -> VEGraphImage {
+        let id = self.add_resource(name, VEGraphResourceKind::TransientImage(desc));
+        VEGraphImage { id, version: 0 }
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> VEGraphPassBuilder<'_> {
+        self.passes.push(VEGraphPassDesc {
+            name: name.to_string(),
+            accesses: vec![],
+            side_effects: false,
+        });
+        let pass = (self.passes.len() - 1) as u32;
+        VEGraphPassBuilder { graph: self, pass }
+    }
+
+    // Leaves the image in the layout of `usage` once the graph is done.
+    pub fn export_image(
+        &mut self,
+        image: VEGraphImage,
+        usage: VEGraphUsage,
+    ) -> Result<(), VERenderGraphError> {
+        let resource = self
+            .resources
+            .get_mut(image.id as usize)
+            .ok_or(VERenderGraphError::UnknownResource(image.id))?;
+        if !resource.is_imported() {
+            return Err(VERenderGraphError::ExportingTransientResource(
+                resource.name.clone(),
+            ));
+        }
+        resource.export = Some(usage);
+        Ok(())
+    }
+
+    pub fn pass_name(&self, pass: VEGraphPass) -> Option<&str> {
+        self.passes.get(pass.0 as usize).map(|p| p.name.as_str())
+    }
+
+    pub(crate) fn transient_desc(&self, id: u32) -> Option<&VETransientImageDesc> {
+        match self.resources.get(id as usize).map(|r| &r.kind) {
+            Some(VEGraphResourceKind::TransientImage(desc)) => Some(desc),
+            _ => None,
+        }
+    }
+
+    // Image usages of a resource over all of its passes, used to create transient images.
+    pub(crate) fn image_usages(&self, id: u32) -> Vec<VEImageUsage> {
+        let mut usages: Vec<VEImageUsage> = vec![];
+        for access in self.passes.iter().flat_map(|p| p.accesses.iter()) {
+            if access.resource != id {
+                continue;
+            }
+            if let Some(usage) = get_image_usage(access.usage) {
+                if !usages.contains(&usage) {
+                    usages.push(usage);
+                }
+            }
+        }
+        usages
+    }
+
+    fn add_access(
+        &mut self,
+        pass: u32,
+        resource: u32,
+        version: u32,
+        usage: VEGraphUsage,
+        write: bool,
+    ) -> Result<u32, VERenderGraphError> {
+        let entry = self
+            .resources
+            .get_mut(resource as usize)
+            .ok_or(VERenderGraphError::UnknownResource(resource))?;
+        let latest = (entry.writers.len() - 1) as u32;
+        if version > latest {
+            return Err(VERenderGraphError::UnknownResource(resource));
+        }
+        let produced = if write {
+            if version != latest {
+                return Err(VERenderGraphError::OutdatedResourceVersion(
+                    entry.name.clone(),
+                ));
+            }
+            entry.writers.push(Some(pass));
+            latest + 1
+        } else {
+            version
+        };
+        self.passes[pass as usize].accesses.push(VEGraphAccess {
+            resource,
+            version,
+            usage,
+            write,
+        });
+        Ok(produced)
+    }
+
+    // Passes that wrote the versions `pass` reads or overwrites.
+    fn producers(&self, pass: u32) -> Vec<u32> {
+        let mut producers: Vec<u32> = self.passes[pass as usize]
+            .accesses
+            .iter()
+            .filter_map(|a| self.resources[a.resource as usize].writers[a.version as usize])
+            .filter(|p| *p != pass)
+            .collect();
+        producers.sort_unstable();
+        producers.dedup();
+        producers
+    }
+
+    // Passes that have to run before `pass`: its producers, and the readers of the versions it
+    // overwrites.
+    fn dependencies(&self, pass: u32) -> Vec<u32> {
+        let mut dependencies = self.producers(pass);
+        for access in self.passes[pass as usize]
+            .accesses
+            .iter()
+            .filter(|a| a.write)
+        {
+            for (index, other) in self.passes.iter().enumerate() {
+                let reads_overwritten = other.accesses.iter().any(|a| {
+                    a.resource == access.resource && a.version == access.version && !a.write
+                });
+                if reads_overwritten && index as u32 != pass {
+                    dependencies.push(index as u32);
+                }
+            }
+        }
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        dependencies
+    }
+
+    fn find_live_passes(&self) -> Vec<bool> {
+        let mut live = vec![false; self.passes.len()];
+        let mut stack: Vec<u32> = vec![];
+        for (index, pass) in self.passes.iter().enumerate() {
+            let has_output = pass.side_effects
+                || pass
+                    .accesses
+                    .iter()
+                    .any(|a| a.write && self.resources[a.resource as usize].is_imported());
+            if has_output {
+                live[index] = true;
+                stack.push(index as u32);
+            }
+        }
+        while let Some(pass) = stack.pop() {
+            for producer in self.producers(pass) {
+                if !live[producer as usize] {
+                    live[producer as usize] = true;
+                    stack.push(producer);
+                }
+            }
+        }
+        live
+    }
+
+    // Kahn's algorithm, ties are broken by declaration order.
+    fn sort_passes(
+        &self,
+        dependencies: &[Vec<u32>],
+        live: &[bool],
+    ) -> Result<Vec<u32>, VERenderGraphError> {
+        let mut remaining: Vec<usize> = dependencies
+            .iter()
+            .map(|d| d.iter().filter(|p| live[**p as usize]).count())
+            .collect();
+        let mut dependents: Vec<Vec<u32>> = vec![vec![]; self.passes.len()];
+        for (pass, pass_dependencies) in dependencies.iter().enumerate() {
+            for dependency in pass_dependencies {
+                dependents[*dependency as usize].push(pass as u32);
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<u32>> = (0..self.passes.len() as u32)
+            .filter(|p| live[*p as usize] && remaining[*p as usize] == 0)
+            .map(Reverse)
+            .collect();
+        let mut order = vec![];
+        while let Some(Reverse(pass)) = ready.pop() {
+            order.push(pass);
+            for dependent in &dependents[pass as usize] {
+                if !live[*dependent as usize] {
+                    continue;
+                }
+                remaining[*dependent as usize] -= 1;
+                if remaining[*dependent as usize] == 0 {
+                    ready.push(Reverse(*dependent));
+                }
+            }
+        }
+
+        if order.len() != live.iter().filter(|l| **l).count() {
+            return Err(VERenderGraphError::DependencyCycle);
+        }
+        Ok(order)
+    }
+
+    // Merges the accesses of a pass per resource.
+    fn pass_usages(&self, pass: u32) -> Result<Vec<(u32, VEAccessInfo)>, VERenderGraphError> {
+        let mut usages: Vec<(u32, VEAccessInfo)> = vec![];
+        for access in &self.passes[pass as usize].accesses {
+            let info = get_usage_info(access.usage, access.write);
+            match usages.iter_mut().find(|u| u.0 == access.resource) {
+                Some(existing) => {
+                    let resource = &self.resources[access.resource as usize];
+                    if resource.aspect().is_some() && existing.1.layout != info.layout {
+                        return Err(VERenderGraphError::ConflictingImageLayouts(
+                            resource.name.clone(),
+                        ));
+                    }
+                    existing.1.stages |= info.stages;
+                    existing.1.access |= info.access;
+                    existing.1.write |= info.write;
+                }
+                None => usages.push((access.resource, info)),
+            }
+        }
+        Ok(usages)
+    }
+
+    pub fn compile(&self) -> Result<VECompiledGraph, VERenderGraphError> {
+        let dependencies: Vec<Vec<u32>> = (0..self.passes.len() as u32)
+            .map(|p| self.dependencies(p))
+            .collect();
+        let live = self.find_live_passes();
+        let order = self.sort_passes(&dependencies, &live)?;
+
+        let mut states: Vec<VEResourceState> = self
+            .resources
+            .iter()
+            .map(|r| match r.kind {
+                // Whatever happened to imported resources before the graph is unknown.
+                VEGraphResourceKind::ImportedImage { layout, .. } => {
+                    VEResourceState::unknown(layout)
+                }
+                VEGraphResourceKind::ImportedBuffer => {
+                    VEResourceState::unknown(vk::ImageLayout::UNDEFINED)
+                }
+                VEGraphResourceKind::TransientImage(_) => VEResourceState::default(),
+            })
+            .collect();
+
+        let mut steps = vec![];
+        let mut lifetimes: Vec<VETransientLifetime> = vec![];
+        for (step, pass) in order.iter().enumerate() {
+            let mut barriers = VEGraphBarrierBatch::default();
+            for (resource, info) in self.pass_usages(*pass)? {
+                let aspect = self.resources[resource as usize].aspect();
+                barriers.access(&mut states[resource as usize], resource, aspect, info);
+
+                if self.transient_desc(resource).is_some() {
+                    match lifetimes.iter_mut().find(|l| l.image == resource) {
+                        Some(lifetime) => {
+                            lifetime.last_step = step;
+                            lifetime.last_stages = info.stages;
+                            lifetime.last_access = info.access & WRITE_ACCESS;
+                        }
+                        None => lifetimes.push(VETransientLifetime {
+                            image: resource,
+                            first_step: step,
+                            last_step: step,
+                            last_stages: info.stages,
+                            last_access: info.access & WRITE_ACCESS,
+                        }),
+                    }
+                }
+            }
+            steps.push(VEGraphStep {
+                pass: VEGraphPass(*pass),
+                barriers,
+            });
+        }
+
+        let mut final_barriers = VEGraphBarrierBatch::default();
+        for (index, resource) in self.resources.iter().enumerate() {
+            if let Some(usage) = resource.export {
+                let info = get_usage_info(usage, false);
+                final_barriers.access(&mut states[index], index as u32, resource.aspect(), info);
+            }
+        }
+
+        Ok(VECompiledGraph {
+            steps,
+            final_barriers,
+            culled: (0..self.passes.len() as u32)
+                .filter(|p| !live[*p as usize])
+                .map(VEGraphPass)
+                .collect(),
+            lifetimes,
+            final_layouts: states.iter().map(|s| s.layout).collect(),
+            names: self.resources.iter().map(|r| r.name.clone()).collect(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEGraphImageBarrier {
+    pub(crate) image: u32,
+    pub aspect: vk::ImageAspectFlags,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+    pub src_stages: vk::PipelineStageFlags2,
+    pub src_access: vk::AccessFlags2,
+    pub dst_stages: vk::PipelineStageFlags2,
+    pub dst_access: vk::AccessFlags2,
+}
+
+// Everything that has to happen before a pass, recorded as a single pipeline barrier. Buffers and
+// images that keep their layout are covered by the global memory barrier.
+#[derive(Default)]
+pub struct VEGraphBarrierBatch {
+    pub memory: Option<VEMemoryBarrier2>,
+    pub images: Vec<VEGraphImageBarrier>,
+}
+
+impl VEGraphBarrierBatch {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.images.is_empty()
+    }
+
+    // Moves the state of `resource` to `info` and merges the barrier that needs into the batch.
+    // Images without a layout change and buffers share the global memory barrier, write after
+    // read leaves its accesses empty since it only needs an execution dependency.
+    fn access(
+        &mut self,
+        state: &mut VEResourceState,
+        resource: u32,
+        aspect: Option<vk::ImageAspectFlags>,
+        info: VEAccessInfo,
+    ) {
+        let Some(barrier) = state.require(info, aspect.is_some()) else {
+            return;
+        };
+        match aspect.filter(|_| barrier.old_layout != barrier.new_layout) {
+            Some(aspect) => self.images.push(VEGraphImageBarrier {
+                image: resource,
+                aspect,
+                old_layout: barrier.old_layout,
+                new_layout: barrier.new_layout,
+                src_stages: barrier.src_stages,
+                src_access: barrier.src_access,
+                dst_stages: barrier.dst_stages,
+                dst_access: barrier.dst_access,
+            }),
+            None => {
+                let memory = self.memory.get_or_insert(VEMemoryBarrier2 {
+                    src_stages: vk::PipelineStageFlags2::NONE,
+                    src_access: vk::AccessFlags2::NONE,
+                    dst_stages: vk::PipelineStageFlags2::NONE,
+                    dst_access: vk::AccessFlags2::NONE,
+                });
+                memory.src_stages |= barrier.src_stages;
+                memory.src_access |= barrier.src_access;
+                memory.dst_stages |= barrier.dst_stages;
+                memory.dst_access |= barrier.dst_access;
+            }
+        }
+    }
+}
+
+pub struct VEGraphStep {
+    pub pass: VEGraphPass,
+    pub barriers: VEGraphBarrierBatch,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VETransientLifetime {
+    pub(crate) image: u32,
+    pub first_step: usize,
+    pub last_step: usize,
+    pub last_stages: vk::PipelineStageFlags2,
+    pub last_access: vk::AccessFlags2,
+}
+
+pub struct VECompiledGraph {
+    pub steps: Vec<VEGraphStep>,
+    // Transitions of exported resources after the last pass.
+    pub final_barriers: VEGraphBarrierBatch,
+    pub culled: Vec<VEGraphPass>,
+    pub lifetimes: Vec<VETransientLifetime>,
+    final_layouts: Vec<vk::ImageLayout>,
+    pub(crate) names: Vec<String>,
+}
+
+impl VECompiledGraph {
+    // Layout the image is left in, imported images should update their `current_layout` with it.
+    pub fn final_layout(&self, image: VEGraphImage) -> Option<vk::ImageLayout> {
+        self.final_layouts.get(image.id as usize).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transient_desc() -> VETransientImageDesc {
+        VETransientImageDesc {
+            width: 64,
+            height: 64,
+            format: VEImageFormat::RGBA8unorm,
+            samples: VESampleCount::Count1,
+        }
+    }
+
+    fn pass_order(compiled: &VECompiledGraph) -> Vec<VEGraphPass> {
+        compiled.steps.iter().map(|s| s.pass).collect()
+    }
+
+    #[test]
+    fn compile_orders_passes_after_their_producers() -> Result<(), VERenderGraphError> {
+        let mut graph = VERenderGraph::new();
+        let history = graph.import_buffer("history");
+        let output = graph.import_buffer("output");
+        let compute_read = VEGraphUsage::StorageRead(VEDescriptorSetFieldStage::Compute);
+        let compute_write = VEGraphUsage::StorageWrite(VEDescriptorSetFieldStage::Compute);
+
+        let mut update = graph.add_pass("update");
+        let update_pass = update.id();
+        let updated_history = update.write_buffer(history, compute_write)?;
+
+        // Reads the history from before the update, so it has to run before it.
+        let mut reproject = graph.add_pass("reproject");
+        let reproject_pass = reproject.id();
+        reproject.read_buffer(history, compute_read)?;
+        let output = reproject.write_buffer(output, compute_write)?;
+
+        let mut shade = graph.add_pass("shade");
+        let shade_pass = shade.id();
+        shade.read_buffer(updated_history, compute_read)?;
+        shade.write_buffer(output, compute_write)?;
+
+        let compiled = graph.compile()?;
+        assert_eq!(
+            pass_order(&compiled),
+            vec![reproject_pass, update_pass, shade_pass]
+        );
+        assert!(compiled.culled.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn compile_culls_passes_nobody_reads() -> Result<(), VERenderGraphError> {
+        let mut graph = VERenderGraph::new();
+        let target = graph.import_image(
+            "target",
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let used = graph.create_image("used", transient_desc());
+        let unused = graph.create_image("unused", transient_desc());
+
+        let mut unused_pass = graph.add_pass("unused");
+        let unused_id = unused_pass.id();
+        unused_pass.write_image(unused, VEGraphUsage::ColorAttachment)?;
+
+        let mut producer = graph.add_pass("producer");
+        let producer_id = producer.id();
+        let used = producer.write_image(used, VEGraphUsage::ColorAttachment)?;
+
+        let mut consumer = graph.add_pass("consumer");
+        let consumer_id = consumer.id();
+        consumer.read_image(
+            used,
+            VEGraphUsage::Sampled(VEDescriptorSetFieldStage::Fragment),
+        )?;
+        consumer.write_image(target, VEGraphUsage::ColorAttachment)?;
+
+        let compiled = graph.compile()?;
+        assert_eq!(pass_order(&compiled), vec![producer_id, consumer_id]);
+        assert_eq!(compiled.culled, vec![unused_id]);
+        assert!(compiled.lifetimes.iter().all(|l| l.image != unused.id));
+        Ok(())
+    }
+
+    #[test]
+    fn compile_keeps_passes_with_side_effects() -> Result<(), VERenderGraphError> {
+        let mut graph = VERenderGraph::new();
+        let image = graph.create_image("image", transient_desc());
+        let mut pass = graph.add_pass("debug");
+        pass.with_side_effects();
+        pass.write_image(image, VEGraphUsage::ColorAttachment)?;
+
+        let compiled = graph.compile()?;
+        assert_eq!(compiled.steps.len(), 1);
+        assert!(compiled.culled.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn compile_detects_dependency_cycles() -> Result<(), VERenderGraphError> {
+        let mut graph = VERenderGraph::new();
+        let i = graph.create_image("i", transient_desc());
+        let j = graph.create_image("j", transient_desc());
+        let target = graph.import_buffer("target");
+
+        // The writer of j has to run first, but it also overwrites the version of i the
+        // reader still needs.
+        let mut writer = graph.add_pass("writer");
+        writer.write_image(i, VEGraphUsage::ColorAttachment)?;
+        let j = writer.write_image(j, VEGraphUsage::ColorAttachment)?;
+
+        let mut reader = graph.add_pass("reader");
+        reader
+            .read_image(
+                i,
+                VEGraphUsage::Sampled(VEDescriptorSetFieldStage::Fragment),
+            )?
+            .read_image(
+                j,
+                VEGraphUsage::Sampled(VEDescriptorSetFieldStage::Fragment),
+            )?;
+        reader.write_buffer(
+            target,
+            VEGraphUsage::StorageWrite(VEDescriptorSetFieldStage::Fragment),
+        )?;
+
+        assert!(matches!(
+            graph.compile(),
+            Err(VERenderGraphError::DependencyCycle)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn compile_transitions_images_read_after_write() -> Result<(), VERenderGraphError> {
+        let mut graph = VERenderGraph::new();
+        let target = graph.import_image(
+            "target",
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let image = graph.create_image("image", transient_desc());
+
+        let mut producer = graph.add_pass("producer");
+        let image = producer.write_image(image, VEGraphUsage::ColorAttachment)?;
+        let mut consumer = graph.add_pass("consumer");
+        consumer.read_image(
+            image,
+            VEGraphUsage::Sampled(VEDescriptorSetFieldStage::Fragment),
+        )?;
+        let target = consumer.write_image(target, VEGraphUsage::ColorAttachment)?;
+        graph.export_image(target, VEGraphUsage::Present)?;
+
+        let compiled = graph.compile()?;
+        let barriers = &compiled.steps[1].barriers;
+        let barrier = barriers.images.iter().find(|b| b.image == image.id);
+        assert!(barrier.is_some_and(|b| {
+            b.old_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                && b.new_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                && b.src_stages == vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                && b.src_access == vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                && b.dst_stages == vk::PipelineStageFlags2::FRAGMENT_SHADER
+                && b.dst_access == vk::AccessFlags2::SHADER_SAMPLED_READ
+        }));
+
+        assert!(compiled
+            .final_barriers
+            .images
+            .iter()
+            .any(|b| { b.image == target.id && b.new_layout == vk::ImageLayout::PRESENT_SRC_KHR }));
+        assert_eq!(
+            compiled.final_layout(target),
+            Some(vk::ImageLayout::PRESENT_SRC_KHR)
+        );
+        Ok(())
+    }
+
+    fn compute_info(write: bool) -> VEAccessInfo {
+        let usage = if write {
+            VEGraphUsage::StorageWrite(VEDescriptorSetFieldStage::Compute)
+        } else {
+            VEGraphUsage::StorageRead(VEDescriptorSetFieldStage::Compute)
+        };
+        get_usage_info(usage, write)
+    }
+
+    fn written_state() -> VEResourceState {
+        let mut state = VEResourceState::default();
+        let mut barriers = VEGraphBarrierBatch::default();
+        barriers.access(&mut state, 0, None, compute_info(true));
+        state
+    }
+
+    #[test]
+    fn access_makes_writes_visible_to_reads() {
+        let mut state = written_state();
+        let mut barriers = VEGraphBarrierBatch::default();
+        barriers.access(&mut state, 0, None, compute_info(false));
+
+        assert!(barriers.memory.is_some_and(|m| {
+            m.src_stages == vk::PipelineStageFlags2::COMPUTE_SHADER
+                && m.src_access == vk::AccessFlags2::SHADER_STORAGE_WRITE
+                && m.dst_stages == vk::PipelineStageFlags2::COMPUTE_SHADER
+                && m.dst_access == vk::AccessFlags2::SHADER_STORAGE_READ
+        }));
+
+        // The write is already visible to a second read.
+        let mut barriers = VEGraphBarrierBatch::default();
+        barriers.access(&mut state, 0, None, compute_info(false));
+        assert!(barriers.is_empty());
+    }
+
+    #[test]
+    fn access_orders_writes_after_reads_without_memory_dependency() {
+        let mut state = VEResourceState::default();
+        let mut barriers = VEGraphBarrierBatch::default();
+        barriers.access(&mut state, 0, None, compute_info(false));
+        let vertex_info = get_usage_info(VEGraphUsage::VertexBuffer, false);
+        barriers.access(&mut state, 0, None, vertex_info);
+        assert!(barriers.is_empty());
+
+        let write_info = get_usage_info(VEGraphUsage::TransferDestination, true);
+        barriers.access(&mut state, 0, None, write_info);
+        assert!(barriers.memory.is_some_and(|m| {
+            m.src_stages
+                == vk::PipelineStageFlags2::COMPUTE_SHADER
+                    | vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT
+                && m.src_access.is_empty()
+                && m.dst_stages == vk::PipelineStageFlags2::TRANSFER
+                && m.dst_access.is_empty()
+        }));
+    }
+
+    #[test]
+    fn access_orders_writes_after_writes() {
+        let mut state = written_state();
+        let mut barriers = VEGraphBarrierBatch::default();
+        let write_info = get_usage_info(VEGraphUsage::TransferDestination, true);
+        barriers.access(&mut state, 0, None, write_info);
+
+        assert!(barriers.memory.is_some_and(|m| {
+            m.src_stages == vk::PipelineStageFlags2::COMPUTE_SHADER
+                && m.src_access == vk::AccessFlags2::SHADER_STORAGE_WRITE
+                && m.dst_stages == vk::PipelineStageFlags2::TRANSFER
+                && m.dst_access == vk::AccessFlags2::TRANSFER_WRITE
+        }));
+    }
+
+    #[test]
+    fn access_transitions_image_layouts() {
+        let mut state = VEResourceState::default();
+        let mut barriers = VEGraphBarrierBatch::default();
+        let aspect = Some(vk::ImageAspectFlags::COLOR);
+        let write_info = get_usage_info(VEGraphUsage::TransferDestination, true);
+        barriers.access(&mut state, 3, aspect, write_info);
+        assert_eq!(barriers.images.len(), 1);
+        assert_eq!(barriers.images[0].old_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(
+            barriers.images[0].new_layout,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL
+        );
+
+        // Write after write in the same layout only needs a memory barrier.
+        let mut barriers = VEGraphBarrierBatch::default();
+        barriers.access(&mut state, 3, aspect, write_info);
+        assert!(barriers.images.is_empty());
+        assert!(barriers.memory.is_some());
+    }
+
+    #[test]
+    fn usages_keep_synchronization2_stages_and_accesses() {
+        let index = get_usage_info(VEGraphUsage::IndexBuffer, false);
+        assert_eq!(index.stages, vk::PipelineStageFlags2::INDEX_INPUT);
+        let sampled = get_usage_info(
+            VEGraphUsage::Sampled(VEDescriptorSetFieldStage::Fragment),
+            false,
+        );
+        assert_eq!(sampled.access, vk::AccessFlags2::SHADER_SAMPLED_READ);
+        let storage = get_usage_info(
+            VEGraphUsage::StorageWrite(VEDescriptorSetFieldStage::Compute),
+            true,
+        );
+        assert!(storage.write);
+        assert_eq!(
+            storage.access & WRITE_ACCESS,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE
+        );
+    }
+}
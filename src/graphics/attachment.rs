@@ -1,4 +1,5 @@
-use crate::image::image::VEImage;
+use crate::core::resource_state::VESubresourceRange;
+use crate::image::image::{VEImage, VEImageError, VEImageViewAspect, VEImageViewCreateInfo};
 use ash::vk;
 use thiserror::Error;
 
@@ -6,22 +7,259 @@ use thiserror::Error;
 pub enum VEAttachmentError {
     #[error("image view not found")]
     ImageViewNotFound,
+
+    #[error("only multisampled attachments can be resolved")]
+    AttachmentNotMultisampled,
+
+    #[error("resolve target must have a single sample")]
+    ResolveTargetMultisampled,
+
+    #[error(
+        "resolve target format {target:?} does not match the attachment format {attachment:?}"
+    )]
+    ResolveFormatMismatch {
+        attachment: vk::Format,
+        target: vk::Format,
+    },
+
+    #[error("resolve target size does not match the attachment size")]
+    ResolveSizeMismatch,
+
+    #[error("image error")]
+    ImageError(#[from] VEImageError),
+
+    #[error("layer {0} is out of bounds")]
+    LayerOutOfBounds(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEBlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    ConstantColor,
+    OneMinusConstantColor,
+    ConstantAlpha,
+    OneMinusConstantAlpha,
+    SrcAlphaSaturate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEBlendOp {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VELogicOp {
+    Clear,
+    And,
+    AndReverse,
+    Copy,
+    AndInverted,
+    NoOp,
+    Xor,
+    Or,
+    Nor,
+    Equivalent,
+    Invert,
+    OrReverse,
+    CopyInverted,
+    OrInverted,
+    Nand,
+    Set,
+}
+
+pub(crate) fn get_blend_factor(factor: VEBlendFactor) -> vk::BlendFactor {
+    match factor {
+        VEBlendFactor::Zero => vk::BlendFactor::ZERO,
+        VEBlendFactor::One => vk::BlendFactor::ONE,
+        VEBlendFactor::SrcColor => vk::BlendFactor::SRC_COLOR,
+        VEBlendFactor::OneMinusSrcColor => vk::BlendFactor::ONE_MINUS_SRC_COLOR,
+        VEBlendFactor::DstColor => vk::BlendFactor::DST_COLOR,
+        VEBlendFactor::OneMinusDstColor => vk::BlendFactor::ONE_MINUS_DST_COLOR,
+        VEBlendFactor::SrcAlpha => vk::BlendFactor::SRC_ALPHA,
+        VEBlendFactor::OneMinusSrcAlpha => vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        VEBlendFactor::DstAlpha => vk::BlendFactor::DST_ALPHA,
+        VEBlendFactor::OneMinusDstAlpha => vk::BlendFactor::ONE_MINUS_DST_ALPHA,
+        VEBlendFactor::ConstantColor => vk::BlendFactor::CONSTANT_COLOR,
+        VEBlendFactor::OneMinusConstantColor => vk::BlendFactor::ONE_MINUS_CONSTANT_COLOR,
+        VEBlendFactor::ConstantAlpha => vk::BlendFactor::CONSTANT_ALPHA,
+        VEBlendFactor::OneMinusConstantAlpha => vk::BlendFactor::ONE_MINUS_CONSTANT_ALPHA,
+        VEBlendFactor::SrcAlphaSaturate => vk::BlendFactor::SRC_ALPHA_SATURATE,
+    }
+}
+
+pub(crate) fn get_blend_op(op: VEBlendOp) -> vk::BlendOp {
+    match op {
+        VEBlendOp::Add => vk::BlendOp::ADD,
+        VEBlendOp::Subtract => vk::BlendOp::SUBTRACT,
+        VEBlendOp::ReverseSubtract => vk::BlendOp::REVERSE_SUBTRACT,
+        VEBlendOp::Min => vk::BlendOp::MIN,
+        VEBlendOp::Max => vk::BlendOp::MAX,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VELoadOp {
+    Load,
+    Clear,
+    DontCare,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEStoreOp {
+    Store,
+    DontCare,
 }
 
+pub(crate) fn get_load_op(op: VELoadOp) -> vk::AttachmentLoadOp {
+    match op {
+        VELoadOp::Load => vk::AttachmentLoadOp::LOAD,
+        VELoadOp::Clear => vk::AttachmentLoadOp::CLEAR,
+        VELoadOp::DontCare => vk::AttachmentLoadOp::DONT_CARE,
+    }
+}
+
+pub(crate) fn get_store_op(op: VEStoreOp) -> vk::AttachmentStoreOp {
+    match op {
+        VEStoreOp::Store => vk::AttachmentStoreOp::STORE,
+        VEStoreOp::DontCare => vk::AttachmentStoreOp::DONT_CARE,
+    }
+}
+
+pub(crate) fn get_logic_op(op: VELogicOp) -> vk::LogicOp {
+    match op {
+        VELogicOp::Clear => vk::LogicOp::CLEAR,
+        VELogicOp::And => vk::LogicOp::AND,
+        VELogicOp::AndReverse => vk::LogicOp::AND_REVERSE,
+        VELogicOp::Copy => vk::LogicOp::COPY,
+        VELogicOp::AndInverted => vk::LogicOp::AND_INVERTED,
+        VELogicOp::NoOp => vk::LogicOp::NO_OP,
+        VELogicOp::Xor => vk::LogicOp::XOR,
+        VELogicOp::Or => vk::LogicOp::OR,
+        VELogicOp::Nor => vk::LogicOp::NOR,
+        VELogicOp::Equivalent => vk::LogicOp::EQUIVALENT,
+        VELogicOp::Invert => vk::LogicOp::INVERT,
+        VELogicOp::OrReverse => vk::LogicOp::OR_REVERSE,
+        VELogicOp::CopyInverted => vk::LogicOp::COPY_INVERTED,
+        VELogicOp::OrInverted => vk::LogicOp::OR_INVERTED,
+        VELogicOp::Nand => vk::LogicOp::NAND,
+        VELogicOp::Set => vk::LogicOp::SET,
+    }
+}
+
+// result = src * src_factor <op> dst * dst_factor, Min and Max ignore the factors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEBlendEquation {
+    pub src_factor: VEBlendFactor,
+    pub dst_factor: VEBlendFactor,
+    pub op: VEBlendOp,
+}
+
+impl VEBlendEquation {
+    pub fn new(src_factor: VEBlendFactor, dst_factor: VEBlendFactor, op: VEBlendOp) -> Self {
+        VEBlendEquation {
+            src_factor,
+            dst_factor,
+            op,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEBlendState {
+    pub color: VEBlendEquation,
+    pub alpha: VEBlendEquation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AttachmentBlending {
     Additive,
     Alpha,
+    PremultipliedAlpha,
+    Min,
+    Max,
+    Custom(VEBlendState),
+}
+
+impl AttachmentBlending {
+    pub fn blend_state(&self) -> VEBlendState {
+        let same = |equation: VEBlendEquation| VEBlendState {
+            color: equation,
+            alpha: equation,
+        };
+        match self {
+            AttachmentBlending::Additive => same(VEBlendEquation::new(
+                VEBlendFactor::One,
+                VEBlendFactor::One,
+                VEBlendOp::Add,
+            )),
+            AttachmentBlending::Alpha => same(VEBlendEquation::new(
+                VEBlendFactor::SrcAlpha,
+                VEBlendFactor::OneMinusSrcAlpha,
+                VEBlendOp::Add,
+            )),
+            AttachmentBlending::PremultipliedAlpha => same(VEBlendEquation::new(
+                VEBlendFactor::One,
+                VEBlendFactor::OneMinusSrcAlpha,
+                VEBlendOp::Add,
+            )),
+            AttachmentBlending::Min => same(VEBlendEquation::new(
+                VEBlendFactor::One,
+                VEBlendFactor::One,
+                VEBlendOp::Min,
+            )),
+            AttachmentBlending::Max => same(VEBlendEquation::new(
+                VEBlendFactor::One,
+                VEBlendFactor::One,
+                VEBlendOp::Max,
+            )),
+            AttachmentBlending::Custom(state) => *state,
+        }
+    }
+}
+
+pub struct VEResolveAttachment {
+    pub image_view: vk::ImageView,
+    pub description: vk::AttachmentDescription,
+    pub image_layout: vk::ImageLayout,
+    pub(crate) image: vk::Image,
 }
 
 pub struct VEAttachment {
     pub image_view: vk::ImageView,
+    pub width: u32,
+    pub height: u32,
     pub is_depth: bool,
     pub description: vk::AttachmentDescription,
     pub blending: Option<AttachmentBlending>,
     pub clear: Option<vk::ClearValue>,
+    pub color_write_mask: vk::ColorComponentFlags,
+    // Blend constants and the logic op are shared by all color attachments of a render stage, so
+    // every attachment that sets them has to agree.
+    pub blend_constants: Option<[f32; 4]>,
+    pub logic_op: Option<VELogicOp>,
+    pub resolve: Option<VEResolveAttachment>,
+    // Layout of the image when the attachment was created, used as the initial layout on load.
+    pub(crate) image_layout: vk::ImageLayout,
+    // Image and subresources rendered to, dynamic rendering transitions them before rendering.
+    pub(crate) image: vk::Image,
+    pub(crate) range: VESubresourceRange,
 }
 
 impl VEAttachment {
+    // Views of combined depth/stencil images need `VEImageViewAspect::All`.
     pub fn from_image(
         image: &VEImage,
         view: vk::ImageView,
@@ -30,7 +268,7 @@ impl VEAttachment {
     ) -> Result<VEAttachment, VEAttachmentError> {
         let description = vk::AttachmentDescription::default()
             .format(image.format)
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .samples(image.samples)
             .load_op(if clear.is_some() {
                 vk::AttachmentLoadOp::CLEAR
             } else {
@@ -39,19 +277,156 @@ impl VEAttachment {
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(image.current_layout)
-            .final_layout(if image.is_depth() {
-                vk::ImageLayout::GENERAL // TODO verify, its the final layout
+            .initial_layout(if clear.is_some() {
+                vk::ImageLayout::UNDEFINED
             } else {
-                vk::ImageLayout::GENERAL
-            });
+                image.current_layout
+            })
+            .final_layout(vk::ImageLayout::GENERAL);
 
         Ok(VEAttachment {
             image_view: view,
+            width: image.width,
+            height: image.height,
             is_depth: image.is_depth(),
             description,
             blending,
             clear,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+            blend_constants: None,
+            logic_op: None,
+            resolve: None,
+            image_layout: image.current_layout,
+            image: image.handle,
+            range: VESubresourceRange::new(0, 1, 0, image.array_layers),
         })
     }
+
+    // Renders into one layer of an array or cube image, like a single shadow cascade.
+    pub fn from_image_layer(
+        image: &mut VEImage,
+        layer: u32,
+        blending: Option<AttachmentBlending>,
+        clear: Option<vk::ClearValue>,
+    ) -> Result<VEAttachment, VEAttachmentError> {
+        let layout = image
+            .layout_at(0, layer)
+            .ok_or(VEAttachmentError::LayerOutOfBounds(layer))?;
+        let view = image
+            .get_view(VEImageViewCreateInfo::layer_2d(layer).with_aspect(VEImageViewAspect::All))?;
+        let mut attachment = VEAttachment::from_image(image, view, blending, clear)?;
+        if attachment.clear.is_none() {
+            attachment.description.initial_layout = layout;
+        }
+        attachment.image_layout = layout;
+        attachment.range = VESubresourceRange::new(0, 1, layer, 1);
+        Ok(attachment)
+    }
+
+    // Only a loaded attachment keeps its contents, otherwise the image starts out undefined. A
+    // clear without a clear value clears to zero.
+    pub fn with_load_op(mut self, op: VELoadOp) -> Self {
+        self.description.load_op = get_load_op(op);
+        self.description.initial_layout = match op {
+            VELoadOp::Load => self.image_layout,
+            VELoadOp::Clear | VELoadOp::DontCare => vk::ImageLayout::UNDEFINED,
+        };
+        if op == VELoadOp::Clear && self.clear.is_none() {
+            self.clear = Some(vk::ClearValue::default());
+        }
+        self
+    }
+
+    pub fn with_store_op(mut self, op: VEStoreOp) -> Self {
+        self.description.store_op = get_store_op(op);
+        self
+    }
+
+    pub fn with_stencil_ops(mut self, load: VELoadOp, store: VEStoreOp) -> Self {
+        self.description.stencil_load_op = get_load_op(load);
+        self.description.stencil_store_op = get_store_op(store);
+        self
+    }
+
+    // Also applies to the resolve target, if there is one.
+    pub fn with_final_layout(mut self, layout: vk::ImageLayout) -> Self {
+        self.description.final_layout = layout;
+        if let Some(resolve) = &mut self.resolve {
+            resolve.description.final_layout = layout;
+        }
+        self
+    }
+
+    // Resolves the multisampled attachment into `image` at the end of the render pass.
+    pub fn with_resolve(
+        mut self,
+        image: &VEImage,
+        view: vk::ImageView,
+    ) -> Result<VEAttachment, VEAttachmentError> {
+        if self.description.samples == vk::SampleCountFlags::TYPE_1 {
+            return Err(VEAttachmentError::AttachmentNotMultisampled);
+        }
+        if image.samples != vk::SampleCountFlags::TYPE_1 {
+            return Err(VEAttachmentError::ResolveTargetMultisampled);
+        }
+        if image.format != self.description.format {
+            return Err(VEAttachmentError::ResolveFormatMismatch {
+                attachment: self.description.format,
+                target: image.format,
+            });
+        }
+        if image.width != self.width || image.height != self.height {
+            return Err(VEAttachmentError::ResolveSizeMismatch);
+        }
+
+        let description = vk::AttachmentDescription::default()
+            .format(image.format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(self.description.final_layout);
+
+        self.resolve = Some(VEResolveAttachment {
+            image_view: view,
+            description,
+            image_layout: image.current_layout,
+            image: image.handle,
+        });
+        Ok(self)
+    }
+
+    pub fn with_color_write_mask(mut self, mask: vk::ColorComponentFlags) -> Self {
+        self.color_write_mask = mask;
+        self
+    }
+
+    pub fn with_blend_constants(mut self, constants: [f32; 4]) -> Self {
+        self.blend_constants = Some(constants);
+        self
+    }
+
+    // For integer targets, replaces blending on every color attachment.
+    pub fn with_logic_op(mut self, op: VELogicOp) -> Self {
+        self.logic_op = Some(op);
+        self
+    }
+
+    pub(crate) fn build_blend_attachment_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        let state = self.blending.map_or_else(
+            || AttachmentBlending::Additive.blend_state(),
+            |blending| blending.blend_state(),
+        );
+        vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(self.color_write_mask)
+            .blend_enable(self.blending.is_some())
+            .color_blend_op(get_blend_op(state.color.op))
+            .src_color_blend_factor(get_blend_factor(state.color.src_factor))
+            .dst_color_blend_factor(get_blend_factor(state.color.dst_factor))
+            .alpha_blend_op(get_blend_op(state.alpha.op))
+            .src_alpha_blend_factor(get_blend_factor(state.alpha.src_factor))
+            .dst_alpha_blend_factor(get_blend_factor(state.alpha.dst_factor))
+    }
 }
@@ -1,5 +1,5 @@
 use crate::core::device::VEDevice;
-use crate::graphics::attachment::VEAttachment;
+use crate::graphics::render_pass_desc::VERenderPassDesc;
 use crate::graphics::renderpass::VERenderPass;
 use ash::vk;
 use std::sync::Arc;
@@ -12,6 +12,7 @@ pub enum VEFrameBufferError {
 }
 
 pub struct VEFrameBuffer {
+    device: Arc<VEDevice>,
     pub handle: vk::Framebuffer,
 }
 
@@ -21,9 +22,9 @@ impl VEFrameBuffer {
         width: u32,
         height: u32,
         render_pass: &VERenderPass,
-        attachments: &[&VEAttachment],
+        desc: &VERenderPassDesc,
     ) -> Result<VEFrameBuffer, VEFrameBufferError> {
-        let image_views: Vec<vk::ImageView> = attachments.iter().map(|a| a.image_view).collect();
+        let image_views = desc.framebuffer_views();
 
         let create_info = vk::FramebufferCreateInfo::default()
             .attachments(&image_views)
@@ -34,6 +35,12 @@ impl VEFrameBuffer {
 
         let handle = unsafe { device.device.create_framebuffer(&create_info, None)? };
 
-        Ok(VEFrameBuffer { handle })
+        Ok(VEFrameBuffer { device, handle })
+    }
+}
+
+impl Drop for VEFrameBuffer {
+    fn drop(&mut self) {
+        unsafe { self.device.device.destroy_framebuffer(self.handle, None) }
     }
 }
@@ -1,13 +1,13 @@
-use crate::core::descriptor_set_layout::VEDescriptorSetLayout;
 use crate::core::device::VEDevice;
-use crate::core::shader_module::VEShaderModule;
-use crate::graphics::attachment::{AttachmentBlending, VEAttachment};
+use crate::graphics::attachment::{get_logic_op, VEAttachment};
+use crate::graphics::pipeline_state::VEPolygonMode;
+use crate::graphics::render_stage::{get_cull_flags, get_primitive_topology, VEPipelineDesc};
 use crate::graphics::renderpass::VERenderPass;
 use crate::graphics::vertex_attributes::{
-    create_vertex_input_state_descriptions, VEVertexAttributesError, VertexAttribFormat,
+    create_vertex_input_state_descriptions, VEVertexAttributesError,
 };
+use crate::image::image_format::get_format_info_from_vk;
 use ash::vk;
-use ash::vk::ColorComponentFlags;
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -21,6 +21,15 @@ pub enum VEGraphicsPipelineError {
 
     #[error("vertex attributes error")]
     VertexAttributesError(#[from] VEVertexAttributesError),
+
+    #[error("{0} is not supported by the device")]
+    FeatureNotSupported(&'static str),
+
+    #[error("color attachments declare different blend constants")]
+    ConflictingBlendConstants,
+
+    #[error("color attachments declare different logic ops")]
+    ConflictingLogicOps,
 }
 
 pub struct VEGraphicsPipeline {
@@ -31,154 +40,148 @@ pub struct VEGraphicsPipeline {
 impl VEGraphicsPipeline {
     pub fn new(
         device: Arc<VEDevice>,
-        viewport_width: u32,
-        viewport_height: u32,
-        set_layouts: &[&VEDescriptorSetLayout],
-        vertex_shader: &VEShaderModule,
-        fragment_shader: &VEShaderModule,
-        render_pass: &VERenderPass,
+        desc: &VEPipelineDesc,
+        // Without a render pass the pipeline is created for dynamic rendering into the attachments
+        render_pass: Option<&VERenderPass>,
+        subpass: u32,
         attachments: &[&VEAttachment],
-        vertex_attributes: &[VertexAttribFormat],
-        primitive_topology: vk::PrimitiveTopology,
-        cull_flags: vk::CullModeFlags,
     ) -> Result<VEGraphicsPipeline, VEGraphicsPipelineError> {
+        let pipeline_state = desc.pipeline_state;
+        let features = &device.features;
+        if pipeline_state.polygon_mode != VEPolygonMode::Fill && !features.fill_mode_non_solid {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported(
+                "non solid polygon mode",
+            ));
+        }
+        if pipeline_state.depth_bounds.is_some() && !features.depth_bounds {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported(
+                "depth bounds test",
+            ));
+        }
+        let has_bias_clamp = pipeline_state
+            .depth_bias
+            .is_some_and(|bias| bias.clamp != 0.0);
+        if has_bias_clamp && !features.depth_bias_clamp {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported(
+                "depth bias clamp",
+            ));
+        }
+        if pipeline_state.line_width != 1.0 && !features.wide_lines {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported("wide lines"));
+        }
+        if pipeline_state.sample_shading.is_some() && !features.sample_rate_shading {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported(
+                "sample rate shading",
+            ));
+        }
+
         let vertex_shader_stage_info = vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::VERTEX)
-            .module(vertex_shader.handle)
+            .module(desc.vertex_shader.handle)
             .name(c"main");
 
         let fragment_shader_stage_info = vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(fragment_shader.handle)
+            .module(desc.fragment_shader.handle)
             .name(c"main");
 
         let shader_stage_infos = [vertex_shader_stage_info, fragment_shader_stage_info];
 
-        let layouts: Vec<vk::DescriptorSetLayout> = set_layouts.iter().map(|x| x.layout).collect();
+        let layouts: Vec<vk::DescriptorSetLayout> =
+            desc.set_layouts.iter().map(|x| x.layout).collect();
 
-        let vertex_attrib_descriptions = create_vertex_input_state_descriptions(vertex_attributes)?;
-        let tmp_binds = [vertex_attrib_descriptions.0];
+        let (binding_descriptions, attribute_descriptions) =
+            create_vertex_input_state_descriptions(
+                desc.vertex_bindings,
+                device.properties.limits.max_vertex_input_bindings,
+            )?;
         let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
-            .vertex_binding_descriptions(&tmp_binds)
-            .vertex_attribute_descriptions(&vertex_attrib_descriptions.1);
+            .vertex_binding_descriptions(&binding_descriptions)
+            .vertex_attribute_descriptions(&attribute_descriptions);
 
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(primitive_topology)
+            .topology(get_primitive_topology(desc.primitive_topology))
             .primitive_restart_enable(false);
 
-        let viewport = vk::Viewport::default()
-            .x(0.0)
-            .y(viewport_height as f32)
-            .width(viewport_width as f32)
-            .height(-(viewport_height as f32))
-            .min_depth(0.0)
-            .max_depth(1.0);
-
-        // let viewport = vk::Viewport::default()
-        //     .x(0.0)
-        //     .y(0.0)
-        //     .width(viewport_width as f32)
-        //     .height(viewport_height as f32)
-        //     .min_depth(0.0)
-        //     .max_depth(1.0);
-
-        let scissor = vk::Rect2D::default()
-            .offset(vk::Offset2D::default())
-            .extent(
-                vk::Extent2D::default()
-                    .width(viewport_width)
-                    .height(viewport_height),
-            );
-
-        let tmp_viewports = [viewport];
-        let tmp_scissors = [scissor];
         let viewport_state = vk::PipelineViewportStateCreateInfo::default()
-            .viewports(&tmp_viewports)
-            .scissors(&tmp_scissors);
-
-        let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
-            .depth_clamp_enable(true)
-            .rasterizer_discard_enable(false)
-            .polygon_mode(vk::PolygonMode::FILL)
-            .line_width(1.0)
-            .cull_mode(cull_flags)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-            .depth_bias_enable(false);
-
-        let multisampling = vk::PipelineMultisampleStateCreateInfo::default()
-            .sample_shading_enable(false)
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-        let mut enable_depth = false;
-
-        let mut attachment_blend_states: Vec<vk::PipelineColorBlendAttachmentState> = vec![];
-
-        //for att in render_pass.attachments {
-        for i in 0..attachments.len() {
-            let att = &attachments[i];
-            if !att.is_depth {
-                // not a depth buffer
-                let mut blend_state = vk::PipelineColorBlendAttachmentState::default()
-                    .color_write_mask(ColorComponentFlags::RGBA);
-                match &att.blending {
-                    None => {
-                        blend_state = blend_state
-                            .color_blend_op(vk::BlendOp::ADD)
-                            .src_color_blend_factor(vk::BlendFactor::ONE)
-                            .dst_color_blend_factor(vk::BlendFactor::ONE)
-                            .alpha_blend_op(vk::BlendOp::ADD)
-                            .src_alpha_blend_factor(vk::BlendFactor::ONE)
-                            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
-                            .blend_enable(false);
-                    }
-                    Some(blending) => match blending {
-                        AttachmentBlending::Alpha => {
-                            blend_state = blend_state
-                                .color_blend_op(vk::BlendOp::ADD)
-                                .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-                                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-                                .alpha_blend_op(vk::BlendOp::ADD)
-                                .src_alpha_blend_factor(vk::BlendFactor::SRC_ALPHA)
-                                .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-                                .blend_enable(true);
-                        }
-                        AttachmentBlending::Additive => {
-                            blend_state = blend_state
-                                .color_blend_op(vk::BlendOp::ADD)
-                                .src_color_blend_factor(vk::BlendFactor::ONE)
-                                .dst_color_blend_factor(vk::BlendFactor::ONE)
-                                .alpha_blend_op(vk::BlendOp::ADD)
-                                .src_alpha_blend_factor(vk::BlendFactor::ONE)
-                                .dst_alpha_blend_factor(vk::BlendFactor::ONE)
-                                .blend_enable(true);
-                        }
-                    },
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state =
+            vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+        let rasterizer = pipeline_state.build_rasterization_state(get_cull_flags(desc.cull_mode));
+
+        let samples = match render_pass {
+            Some(render_pass) => render_pass.samples,
+            None => attachments
+                .first()
+                .map_or(vk::SampleCountFlags::TYPE_1, |a| a.description.samples),
+        };
+        let multisampling = pipeline_state.build_multisample_state(samples);
+
+        let enable_depth = attachments.iter().any(|att| att.is_depth);
+        let color_attachments: Vec<&&VEAttachment> =
+            attachments.iter().filter(|att| !att.is_depth).collect();
+
+        let attachment_blend_states: Vec<vk::PipelineColorBlendAttachmentState> = color_attachments
+            .iter()
+            .map(|att| att.build_blend_attachment_state())
+            .collect();
+        let is_blend_independent = attachment_blend_states.windows(2).any(|pair| {
+            pair[0].blend_enable != pair[1].blend_enable
+                || pair[0].color_write_mask != pair[1].color_write_mask
+                || pair[0].src_color_blend_factor != pair[1].src_color_blend_factor
+                || pair[0].dst_color_blend_factor != pair[1].dst_color_blend_factor
+                || pair[0].color_blend_op != pair[1].color_blend_op
+                || pair[0].src_alpha_blend_factor != pair[1].src_alpha_blend_factor
+                || pair[0].dst_alpha_blend_factor != pair[1].dst_alpha_blend_factor
+                || pair[0].alpha_blend_op != pair[1].alpha_blend_op
+        });
+        if is_blend_independent && !features.independent_blend {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported(
+                "independent blending per attachment",
+            ));
+        }
+
+        let mut blend_constants = None;
+        let mut logic_op = None;
+        for att in &color_attachments {
+            if att.blend_constants.is_some() {
+                if blend_constants.is_some_and(|constants| Some(constants) != att.blend_constants) {
+                    return Err(VEGraphicsPipelineError::ConflictingBlendConstants);
                 }
-                attachment_blend_states.push(blend_state);
-            } else {
-                // is a depth buffer, enable depth
-                enable_depth = true;
+                blend_constants = att.blend_constants;
             }
+            if att.logic_op.is_some() {
+                if logic_op.is_some_and(|op| Some(op) != att.logic_op) {
+                    return Err(VEGraphicsPipelineError::ConflictingLogicOps);
+                }
+                logic_op = att.logic_op;
+            }
+        }
+        if logic_op.is_some() && !features.logic_op {
+            return Err(VEGraphicsPipelineError::FeatureNotSupported("logic ops"));
         }
 
-        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
-            .depth_test_enable(enable_depth)
-            .depth_write_enable(enable_depth)
-            .depth_compare_op(if enable_depth {
-                vk::CompareOp::LESS
-            } else {
-                vk::CompareOp::ALWAYS
-            })
-            .min_depth_bounds(0.0)
-            .max_depth_bounds(1.0);
+        let depth_stencil = pipeline_state.build_depth_stencil_state(enable_depth);
 
         let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
-            .logic_op_enable(false)
-            .logic_op(vk::LogicOp::COPY)
+            .logic_op_enable(logic_op.is_some())
+            .logic_op(logic_op.map_or(vk::LogicOp::COPY, get_logic_op))
             .attachments(&attachment_blend_states)
-            .blend_constants([1.0, 1.0, 1.0, 1.0]);
+            .blend_constants(blend_constants.unwrap_or([1.0, 1.0, 1.0, 1.0]));
 
-        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+        let push_constant_ranges: Vec<vk::PushConstantRange> = desc
+            .push_constant_ranges
+            .iter()
+            .map(|x| x.build())
+            .collect();
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&layouts)
+            .push_constant_ranges(&push_constant_ranges);
         let pipeline_layout = unsafe {
             device
                 .device
@@ -186,7 +189,26 @@ impl VEGraphicsPipeline {
                 .map_err(VEGraphicsPipelineError::LayoutCreationFailed)?
         };
 
-        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        let color_formats: Vec<vk::Format> = color_attachments
+            .iter()
+            .map(|att| att.description.format)
+            .collect();
+        let depth_info = attachments
+            .iter()
+            .find(|att| att.is_depth)
+            .and_then(|att| get_format_info_from_vk(att.description.format));
+        let depth_format = depth_info
+            .filter(|info| info.has_depth())
+            .map_or(vk::Format::UNDEFINED, |info| info.vk_format);
+        let stencil_format = depth_info
+            .filter(|info| info.has_stencil())
+            .map_or(vk::Format::UNDEFINED, |info| info.vk_format);
+        let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&color_formats)
+            .depth_attachment_format(depth_format)
+            .stencil_attachment_format(stencil_format);
+
+        let mut pipeline_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&shader_stage_infos)
             .vertex_input_state(&vertex_input_state)
             .input_assembly_state(&input_assembly)
@@ -195,9 +217,14 @@ impl VEGraphicsPipeline {
             .multisample_state(&multisampling)
             .depth_stencil_state(&depth_stencil)
             .color_blend_state(&color_blending)
-            .layout(pipeline_layout)
-            .render_pass(render_pass.handle)
-            .subpass(0);
+            .dynamic_state(&dynamic_state)
+            .layout(pipeline_layout);
+        pipeline_info = match render_pass {
+            Some(render_pass) => pipeline_info
+                .render_pass(render_pass.handle)
+                .subpass(subpass),
+            None => pipeline_info.push_next(&mut rendering_info),
+        };
 
         let pipeline = unsafe {
             device
@@ -0,0 +1,200 @@
+use crate::buffer::buffer::{VEBuffer, VEBufferError, VEBufferUsage};
+use crate::core::command_buffer::VECommandBuffer;
+use crate::core::command_pool::VECommandPool;
+use crate::core::device::VEDevice;
+use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::memory_properties::VEMemoryProperties;
+use crate::memory::memory_manager::VEMemoryManager;
+use ash::vk;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VEIndexBufferError {
+    #[error("opening file failed")]
+    OpeningFileFailed(#[source] io::Error),
+
+    #[error("getting file metadata failed")]
+    GettingFileMetadataFailed(#[source] io::Error),
+
+    #[error("reading file failed")]
+    ReadingFileFailed(#[source] io::Error),
+
+    #[error("index size mismatch in the data")]
+    IndexSizeMismatch,
+
+    #[error("{0} bytes of index data do not fit in a u32 index count")]
+    DataTooLarge(u64),
+
+    #[error("{index_count} indices do not fit in a buffer of {size} bytes")]
+    BufferTooSmall { index_count: u32, size: u64 },
+
+    #[error("buffer error")]
+    BufferError(#[from] VEBufferError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VEIndexType {
+    U16,
+    U32,
+}
+
+pub(crate) fn get_index_type(typ: VEIndexType) -> vk::IndexType {
+    match typ {
+        VEIndexType::U16 => vk::IndexType::UINT16,
+        VEIndexType::U32 => vk::IndexType::UINT32,
+    }
+}
+
+pub fn get_index_type_byte_size(typ: VEIndexType) -> u32 {
+    match typ {
+        VEIndexType::U16 => 2,
+        VEIndexType::U32 => 4,
+    }
+}
+
+pub struct VEIndexBuffer {
+    device: Arc<VEDevice>,
+    pub buffer: VEBuffer,
+    pub index_type: VEIndexType,
+    pub index_count: u32,
+}
+
+impl VEIndexBuffer {
+    pub fn new(
+        device: Arc<VEDevice>,
+        buffer: VEBuffer,
+        index_type: VEIndexType,
+        index_count: u32,
+    ) -> Result<VEIndexBuffer, VEIndexBufferError> {
+        let index_size = get_index_type_byte_size(index_type) as u64;
+        if !buffer.size.is_multiple_of(index_size) {
+            return Err(VEIndexBufferError::IndexSizeMismatch);
+        }
+        if index_count as u64 * index_size > buffer.size {
+            return Err(VEIndexBufferError::BufferTooSmall {
+                index_count,
+                size: buffer.size,
+            });
+        }
+        Ok(VEIndexBuffer {
+            device,
+            buffer,
+            index_type,
+            index_count,
+        })
+    }
+
+    pub fn from_data(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        data: Vec<u8>,
+        index_type: VEIndexType,
+    ) -> Result<VEIndexBuffer, VEIndexBufferError> {
+        let input_size = data.len() as u64;
+        let index_count = Self::get_index_count(input_size, index_type)?;
+
+        let mut staging_buffer = VEBuffer::new(
+            device.clone(),
+            queue.clone(),
+            command_pool.clone(),
+            memory_manager.clone(),
+            &[VEBufferUsage::TransferSource],
+            input_size,
+            Some(VEMemoryProperties::HostCoherent),
+        )?;
+
+        let final_buffer = VEBuffer::new(
+            device.clone(),
+            queue.clone(),
+            command_pool.clone(),
+            memory_manager.clone(),
+            &[VEBufferUsage::Index, VEBufferUsage::TransferDestination],
+            input_size,
+            Some(VEMemoryProperties::DeviceLocal),
+        )?;
+
+        unsafe {
+            let mem = staging_buffer.map()? as *mut u8;
+            let slice = std::slice::from_raw_parts_mut(mem, data.len());
+            slice.copy_from_slice(&data);
+        }
+
+        staging_buffer.copy_to(&final_buffer, 0, 0, staging_buffer.size)?;
+
+        VEIndexBuffer::new(device, final_buffer, index_type, index_count)
+    }
+
+    pub fn from_file(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        path: &str,
+        index_type: VEIndexType,
+    ) -> Result<VEIndexBuffer, VEIndexBufferError> {
+        let mut file = File::open(path).map_err(VEIndexBufferError::OpeningFileFailed)?;
+        let metadata = file
+            .metadata()
+            .map_err(VEIndexBufferError::GettingFileMetadataFailed)?;
+        let file_size = metadata.len();
+        let index_count = Self::get_index_count(file_size, index_type)?;
+        let file_len =
+            usize::try_from(file_size).map_err(|_| VEIndexBufferError::DataTooLarge(file_size))?;
+
+        let mut staging_buffer = VEBuffer::new(
+            device.clone(),
+            queue.clone(),
+            command_pool.clone(),
+            memory_manager.clone(),
+            &[VEBufferUsage::TransferSource],
+            file_size,
+            Some(VEMemoryProperties::HostCoherent),
+        )?;
+
+        let final_buffer = VEBuffer::new(
+            device.clone(),
+            queue.clone(),
+            command_pool.clone(),
+            memory_manager.clone(),
+            &[VEBufferUsage::Index, VEBufferUsage::TransferDestination],
+            file_size,
+            Some(VEMemoryProperties::DeviceLocal),
+        )?;
+
+        unsafe {
+            let mem = staging_buffer.map()? as *mut u8;
+            let slice = std::slice::from_raw_parts_mut(mem, file_len);
+            file.read_exact(slice)
+                .map_err(VEIndexBufferError::ReadingFileFailed)?;
+        }
+
+        staging_buffer.copy_to(&final_buffer, 0, 0, staging_buffer.size)?;
+
+        VEIndexBuffer::new(device, final_buffer, index_type, index_count)
+    }
+
+    fn get_index_count(size: u64, index_type: VEIndexType) -> Result<u32, VEIndexBufferError> {
+        let index_size = get_index_type_byte_size(index_type) as u64;
+        if !size.is_multiple_of(index_size) {
+            return Err(VEIndexBufferError::IndexSizeMismatch);
+        }
+        u32::try_from(size / index_size).map_err(|_| VEIndexBufferError::DataTooLarge(size))
+    }
+
+    pub fn bind(&self, command_buffer: &VECommandBuffer) {
+        unsafe {
+            self.device.device.cmd_bind_index_buffer(
+                command_buffer.handle,
+                self.buffer.buffer,
+                0,
+                get_index_type(self.index_type),
+            );
+        }
+    }
+}
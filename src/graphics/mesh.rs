@@ -0,0 +1,65 @@
+use crate::core::command_buffer::VECommandBuffer;
+use crate::core::device::VEDevice;
+use crate::graphics::index_buffer::VEIndexBuffer;
+use crate::graphics::vertex_buffer::VEVertexBuffer;
+use std::sync::Arc;
+
+pub struct VEMesh {
+    device: Arc<VEDevice>,
+    pub vertex_buffer: VEVertexBuffer,
+    pub index_buffer: VEIndexBuffer,
+}
+
+impl VEMesh {
+    pub fn new(
+        device: Arc<VEDevice>,
+        vertex_buffer: VEVertexBuffer,
+        index_buffer: VEIndexBuffer,
+    ) -> VEMesh {
+        VEMesh {
+            device,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    pub fn bind(&self, command_buffer: &VECommandBuffer) {
+        self.vertex_buffer.bind(command_buffer);
+        self.index_buffer.bind(command_buffer);
+    }
+
+    pub fn draw_instanced(&self, command_buffer: &VECommandBuffer, instances: u32) {
+        self.draw_indexed_instanced(
+            command_buffer,
+            self.index_buffer.index_count,
+            instances,
+            0,
+            0,
+            0,
+        );
+    }
+
+    // `base_vertex` is added to every index before fetching the vertex, which allows several meshes
+    // to share one vertex and index buffer.
+    pub fn draw_indexed_instanced(
+        &self,
+        command_buffer: &VECommandBuffer,
+        index_count: u32,
+        instances: u32,
+        first_index: u32,
+        base_vertex: i32,
+        first_instance: u32,
+    ) {
+        self.bind(command_buffer);
+        unsafe {
+            self.device.device.cmd_draw_indexed(
+                command_buffer.handle,
+                index_count,
+                instances,
+                first_index,
+                base_vertex,
+                first_instance,
+            );
+        }
+    }
+}
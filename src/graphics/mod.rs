@@ -1,8 +1,12 @@
 pub mod attachment;
+pub mod index_buffer;
+pub mod mesh;
+pub mod pipeline_state;
+pub mod render_pass_desc;
 pub mod render_stage;
 pub mod vertex_attributes;
 
 mod framebuffer;
 mod graphics_pipeline;
-mod renderpass;
+pub mod renderpass;
 pub mod vertex_buffer;
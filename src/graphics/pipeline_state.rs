@@ -0,0 +1,287 @@
+use ash::vk;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEPolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEFrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VECompareOp {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEStencilOp {
+    Keep,
+    Zero,
+    Replace,
+    IncrementAndClamp,
+    DecrementAndClamp,
+    Invert,
+    IncrementAndWrap,
+    DecrementAndWrap,
+}
+
+pub(crate) fn get_polygon_mode(mode: VEPolygonMode) -> vk::PolygonMode {
+    match mode {
+        VEPolygonMode::Fill => vk::PolygonMode::FILL,
+        VEPolygonMode::Line => vk::PolygonMode::LINE,
+        VEPolygonMode::Point => vk::PolygonMode::POINT,
+    }
+}
+
+pub(crate) fn get_front_face(face: VEFrontFace) -> vk::FrontFace {
+    match face {
+        VEFrontFace::CounterClockwise => vk::FrontFace::COUNTER_CLOCKWISE,
+        VEFrontFace::Clockwise => vk::FrontFace::CLOCKWISE,
+    }
+}
+
+pub(crate) fn get_compare_op(op: VECompareOp) -> vk::CompareOp {
+    match op {
+        VECompareOp::Never => vk::CompareOp::NEVER,
+        VECompareOp::Less => vk::CompareOp::LESS,
+        VECompareOp::Equal => vk::CompareOp::EQUAL,
+        VECompareOp::LessOrEqual => vk::CompareOp::LESS_OR_EQUAL,
+        VECompareOp::Greater => vk::CompareOp::GREATER,
+        VECompareOp::NotEqual => vk::CompareOp::NOT_EQUAL,
+        VECompareOp::GreaterOrEqual => vk::CompareOp::GREATER_OR_EQUAL,
+        VECompareOp::Always => vk::CompareOp::ALWAYS,
+    }
+}
+
+pub(crate) fn get_stencil_op(op: VEStencilOp) -> vk::StencilOp {
+    match op {
+        VEStencilOp::Keep => vk::StencilOp::KEEP,
+        VEStencilOp::Zero => vk::StencilOp::ZERO,
+        VEStencilOp::Replace => vk::StencilOp::REPLACE,
+        VEStencilOp::IncrementAndClamp => vk::StencilOp::INCREMENT_AND_CLAMP,
+        VEStencilOp::DecrementAndClamp => vk::StencilOp::DECREMENT_AND_CLAMP,
+        VEStencilOp::Invert => vk::StencilOp::INVERT,
+        VEStencilOp::IncrementAndWrap => vk::StencilOp::INCREMENT_AND_WRAP,
+        VEStencilOp::DecrementAndWrap => vk::StencilOp::DECREMENT_AND_WRAP,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEStencilOpState {
+    pub fail_op: VEStencilOp,
+    pub pass_op: VEStencilOp,
+    pub depth_fail_op: VEStencilOp,
+    pub compare_op: VECompareOp,
+    pub compare_mask: u32,
+    pub write_mask: u32,
+    pub reference: u32,
+}
+
+impl Default for VEStencilOpState {
+    fn default() -> Self {
+        VEStencilOpState {
+            fail_op: VEStencilOp::Keep,
+            pass_op: VEStencilOp::Keep,
+            depth_fail_op: VEStencilOp::Keep,
+            compare_op: VECompareOp::Always,
+            compare_mask: 0xff,
+            write_mask: 0xff,
+            reference: 0,
+        }
+    }
+}
+
+impl VEStencilOpState {
+    pub fn build(&self) -> vk::StencilOpState {
+        vk::StencilOpState::default()
+            .fail_op(get_stencil_op(self.fail_op))
+            .pass_op(get_stencil_op(self.pass_op))
+            .depth_fail_op(get_stencil_op(self.depth_fail_op))
+            .compare_op(get_compare_op(self.compare_op))
+            .compare_mask(self.compare_mask)
+            .write_mask(self.write_mask)
+            .reference(self.reference)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VEDepthBias {
+    pub constant_factor: f32,
+    pub clamp: f32,
+    pub slope_factor: f32,
+}
+
+// Depth test and write only take effect when the render stage has a depth attachment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VEPipelineState {
+    pub polygon_mode: VEPolygonMode,
+    pub front_face: VEFrontFace,
+    pub line_width: f32,
+    pub depth_clamp: bool,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare_op: VECompareOp,
+    pub depth_bias: Option<VEDepthBias>,
+    pub depth_bounds: Option<(f32, f32)>,
+    pub stencil: Option<(VEStencilOpState, VEStencilOpState)>,
+    // Uses a negative viewport height so that +Y points up in clip space.
+    pub flip_viewport_y: bool,
+    // Minimum fraction of samples shaded per pixel, only relevant for multisampled attachments.
+    pub sample_shading: Option<f32>,
+    pub alpha_to_coverage: bool,
+}
+
+impl Default for VEPipelineState {
+    fn default() -> Self {
+        VEPipelineState {
+            polygon_mode: VEPolygonMode::Fill,
+            front_face: VEFrontFace::CounterClockwise,
+            line_width: 1.0,
+            depth_clamp: true,
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: VECompareOp::Less,
+            depth_bias: None,
+            depth_bounds: None,
+            stencil: None,
+            flip_viewport_y: true,
+            sample_shading: None,
+            alpha_to_coverage: false,
+        }
+    }
+}
+
+impl VEPipelineState {
+    pub fn polygon_mode(mut self, polygon_mode: VEPolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: VEFrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn depth_clamp(mut self, depth_clamp: bool) -> Self {
+        self.depth_clamp = depth_clamp;
+        self
+    }
+
+    pub fn depth_test(mut self, depth_test: bool) -> Self {
+        self.depth_test = depth_test;
+        self
+    }
+
+    pub fn depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write;
+        self
+    }
+
+    pub fn depth_compare_op(mut self, depth_compare_op: VECompareOp) -> Self {
+        self.depth_compare_op = depth_compare_op;
+        self
+    }
+
+    pub fn depth_bias(mut self, depth_bias: VEDepthBias) -> Self {
+        self.depth_bias = Some(depth_bias);
+        self
+    }
+
+    pub fn depth_bounds(mut self, min: f32, max: f32) -> Self {
+        self.depth_bounds = Some((min, max));
+        self
+    }
+
+    pub fn stencil(mut self, front: VEStencilOpState, back: VEStencilOpState) -> Self {
+        self.stencil = Some((front, back));
+        self
+    }
+
+    pub fn flip_viewport_y(mut self, flip_viewport_y: bool) -> Self {
+        self.flip_viewport_y = flip_viewport_y;
+        self
+    }
+
+    pub fn sample_shading(mut self, min_sample_shading: f32) -> Self {
+        self.sample_shading = Some(min_sample_shading);
+        self
+    }
+
+    pub fn alpha_to_coverage(mut self, alpha_to_coverage: bool) -> Self {
+        self.alpha_to_coverage = alpha_to_coverage;
+        self
+    }
+
+    pub(crate) fn build_multisample_state(
+        &self,
+        samples: vk::SampleCountFlags,
+    ) -> vk::PipelineMultisampleStateCreateInfo<'static> {
+        vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(samples)
+            .sample_shading_enable(self.sample_shading.is_some())
+            .min_sample_shading(self.sample_shading.unwrap_or(0.0))
+            .alpha_to_coverage_enable(self.alpha_to_coverage)
+    }
+
+    pub(crate) fn build_rasterization_state(
+        &self,
+        cull_flags: vk::CullModeFlags,
+    ) -> vk::PipelineRasterizationStateCreateInfo<'static> {
+        let bias = self.depth_bias.unwrap_or(VEDepthBias {
+            constant_factor: 0.0,
+            clamp: 0.0,
+            slope_factor: 0.0,
+        });
+        vk::PipelineRasterizationStateCreateInfo::default()
+            .depth_clamp_enable(self.depth_clamp)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(get_polygon_mode(self.polygon_mode))
+            .line_width(self.line_width)
+            .cull_mode(cull_flags)
+            .front_face(get_front_face(self.front_face))
+            .depth_bias_enable(self.depth_bias.is_some())
+            .depth_bias_constant_factor(bias.constant_factor)
+            .depth_bias_clamp(bias.clamp)
+            .depth_bias_slope_factor(bias.slope_factor)
+    }
+
+    pub(crate) fn build_depth_stencil_state(
+        &self,
+        has_depth_attachment: bool,
+    ) -> vk::PipelineDepthStencilStateCreateInfo<'static> {
+        let depth_test = has_depth_attachment && self.depth_test;
+        let (min_depth_bounds, max_depth_bounds) = self.depth_bounds.unwrap_or((0.0, 1.0));
+        let (front, back) = self.stencil.unwrap_or_default();
+        vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(depth_test)
+            .depth_write_enable(depth_test && self.depth_write)
+            .depth_compare_op(if depth_test {
+                get_compare_op(self.depth_compare_op)
+            } else {
+                vk::CompareOp::ALWAYS
+            })
+            .depth_bounds_test_enable(has_depth_attachment && self.depth_bounds.is_some())
+            .min_depth_bounds(min_depth_bounds)
+            .max_depth_bounds(max_depth_bounds)
+            .stencil_test_enable(has_depth_attachment && self.stencil.is_some())
+            .front(front.build())
+            .back(back.build())
+    }
+}
@@ -0,0 +1,177 @@
+use crate::graphics::attachment::VEAttachment;
+use crate::graphics::renderpass::VERenderPassError;
+use ash::vk;
+
+// Format, sample count and resolve format of an attachment, which render pass compatibility
+// depends on.
+pub(crate) type VEAttachmentFormat = (vk::Format, vk::SampleCountFlags, Option<vk::Format>);
+
+// Attachments are referenced by their index in `VERenderPassDesc::attachments`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VESubpassDesc {
+    pub color_attachments: Vec<u32>,
+    pub input_attachments: Vec<u32>,
+    pub depth_attachment: Option<u32>,
+    // Resolves the multisampled color and depth attachments of this subpass into their targets.
+    pub resolve: bool,
+}
+
+impl VESubpassDesc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(mut self, attachment: u32) -> Self {
+        self.color_attachments.push(attachment);
+        self
+    }
+
+    pub fn with_input(mut self, attachment: u32) -> Self {
+        self.input_attachments.push(attachment);
+        self
+    }
+
+    pub fn with_depth(mut self, attachment: u32) -> Self {
+        self.depth_attachment = Some(attachment);
+        self
+    }
+
+    pub fn with_resolve(mut self) -> Self {
+        self.resolve = true;
+        self
+    }
+
+    pub(crate) fn uses(&self, attachment: u32) -> bool {
+        self.color_attachments.contains(&attachment)
+            || self.input_attachments.contains(&attachment)
+            || self.depth_attachment == Some(attachment)
+    }
+}
+
+// Extra dependency on top of the ones derived from attachment usage, use vk::SUBPASS_EXTERNAL
+// for commands outside the render pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VESubpassDependency {
+    pub src_subpass: u32,
+    pub dst_subpass: u32,
+    pub src_stages: vk::PipelineStageFlags,
+    pub src_access: vk::AccessFlags,
+    pub dst_stages: vk::PipelineStageFlags,
+    pub dst_access: vk::AccessFlags,
+    pub by_region: bool,
+}
+
+impl VESubpassDependency {
+    pub fn build(&self) -> vk::SubpassDependency {
+        vk::SubpassDependency::default()
+            .src_subpass(self.src_subpass)
+            .dst_subpass(self.dst_subpass)
+            .src_stage_mask(self.src_stages)
+            .src_access_mask(self.src_access)
+            .dst_stage_mask(self.dst_stages)
+            .dst_access_mask(self.dst_access)
+            .dependency_flags(if self.by_region {
+                vk::DependencyFlags::BY_REGION
+            } else {
+                vk::DependencyFlags::empty()
+            })
+    }
+}
+
+pub struct VERenderPassDesc<'a> {
+    pub attachments: Vec<&'a VEAttachment>,
+    pub subpasses: Vec<VESubpassDesc>,
+    pub dependencies: Vec<VESubpassDependency>,
+}
+
+impl<'a> VERenderPassDesc<'a> {
+    pub fn new(attachments: &[&'a VEAttachment]) -> Self {
+        VERenderPassDesc {
+            attachments: attachments.to_vec(),
+            subpasses: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    // A single subpass writing all color attachments in order, followed by the depth attachment.
+    pub fn single_subpass(attachments: &[&'a VEAttachment]) -> Result<Self, VERenderPassError> {
+        let depth_count = attachments.iter().filter(|a| a.is_depth).count();
+        if depth_count > 1 {
+            return Err(VERenderPassError::MultipleDepthAttachments(depth_count));
+        }
+        let mut ordered: Vec<&VEAttachment> = attachments
+            .iter()
+            .filter(|a| !a.is_depth)
+            .copied()
+            .collect();
+        ordered.extend(attachments.iter().find(|a| a.is_depth).copied());
+
+        let mut subpass = VESubpassDesc::new().with_resolve();
+        for (index, attachment) in ordered.iter().enumerate() {
+            subpass = if attachment.is_depth {
+                subpass.with_depth(index as u32)
+            } else {
+                subpass.with_color(index as u32)
+            };
+        }
+        Ok(VERenderPassDesc::new(&ordered).with_subpass(subpass))
+    }
+
+    pub fn with_subpass(mut self, subpass: VESubpassDesc) -> Self {
+        self.subpasses.push(subpass);
+        self
+    }
+
+    pub fn with_dependency(mut self, dependency: VESubpassDependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    // Color attachments of the subpass in reference order, followed by its depth attachment.
+    pub(crate) fn subpass_attachments(&self, subpass: &VESubpassDesc) -> Vec<&'a VEAttachment> {
+        subpass
+            .color_attachments
+            .iter()
+            .chain(subpass.depth_attachment.iter())
+            .filter_map(|index| self.attachments.get(*index as usize).copied())
+            .collect()
+    }
+
+    pub(crate) fn attachment_formats(&self) -> Vec<VEAttachmentFormat> {
+        self.attachments
+            .iter()
+            .map(|a| {
+                (
+                    a.description.format,
+                    a.description.samples,
+                    a.resolve.as_ref().map(|r| r.description.format),
+                )
+            })
+            .collect()
+    }
+
+    // Resolve targets follow the attachments, in the same order.
+    pub(crate) fn framebuffer_views(&self) -> Vec<vk::ImageView> {
+        self.attachments
+            .iter()
+            .map(|a| a.image_view)
+            .chain(
+                self.attachments
+                    .iter()
+                    .filter_map(|a| a.resolve.as_ref().map(|r| r.image_view)),
+            )
+            .collect()
+    }
+
+    pub(crate) fn clear_values(&self) -> Vec<vk::ClearValue> {
+        self.attachments
+            .iter()
+            .map(|a| a.clear.unwrap_or_default())
+            .chain(
+                self.attachments
+                    .iter()
+                    .filter_map(|a| a.resolve.as_ref().map(|_| vk::ClearValue::default())),
+            )
+            .collect()
+    }
+}
@@ -1,14 +1,35 @@
-use crate::core::command_buffer::VECommandBuffer;
+use crate::buffer::buffer::VEBuffer;
+use crate::buffer::indirect_command::{
+    validate_indirect_buffer, VEDrawIndexedIndirectCommand, VEDrawIndirectCommand,
+    VEIndirectCommandError,
+};
+use crate::buffer::typed_buffer::VETypedBuffer;
+use crate::core::command_buffer::{VECommandBuffer, VECommandBufferError};
 use crate::core::descriptor_set::VEDescriptorSet;
 use crate::core::descriptor_set_layout::VEDescriptorSetLayout;
-use crate::core::device::VEDevice;
+use crate::core::descriptor_update_template::{
+    VEDescriptorUpdateTemplate, VEDescriptorUpdateTemplateEntry,
+};
+use crate::core::descriptor_write::VEDescriptorWrite;
+use crate::core::device::{VEDevice, VEDeviceError};
+use crate::core::push_constants::{
+    resolve_push_constant_ranges, validate_push_constants_update, VEPushConstantRange,
+    VEPushConstantsError,
+};
+use crate::core::push_descriptor::{VEPushDescriptorError, VEPushDescriptorSets};
+use crate::core::resource_state::{VEImageAccess, VESubresourceRange};
 use crate::core::shader_module::VEShaderModule;
 use crate::graphics::attachment::VEAttachment;
 use crate::graphics::framebuffer::{VEFrameBuffer, VEFrameBufferError};
 use crate::graphics::graphics_pipeline::{VEGraphicsPipeline, VEGraphicsPipelineError};
+use crate::graphics::pipeline_state::VEPipelineState;
+use crate::graphics::render_pass_desc::{VEAttachmentFormat, VERenderPassDesc};
 use crate::graphics::renderpass::{VERenderPass, VERenderPassError};
-use crate::graphics::vertex_attributes::VertexAttribFormat;
+use crate::graphics::vertex_attributes::VEVertexBindingLayout;
+use crate::image::image::VEImage;
+use crate::image::image_format::get_format_info_from_vk;
 use ash::vk;
+use bytemuck::Pod;
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -22,28 +43,73 @@ pub enum VERenderStageError {
 
     #[error("framebuffer error")]
     FrameBufferError(#[from] VEFrameBufferError),
+
+    #[error("push constants error")]
+    PushConstantsError(#[from] VEPushConstantsError),
+
+    #[error("push descriptor error")]
+    PushDescriptorError(#[from] VEPushDescriptorError),
+
+    #[error("indirect command error")]
+    IndirectCommandError(#[from] VEIndirectCommandError),
+
+    #[error("device error")]
+    DeviceError(#[from] VEDeviceError),
+
+    #[error("command buffer error")]
+    CommandBufferError(#[from] VECommandBufferError),
+
+    #[error("attachments are not compatible with the render pass")]
+    IncompatibleAttachments,
+
+    #[error("attachments have different sizes")]
+    AttachmentSizeMismatch,
+
+    #[error("subpass {0} is not part of the render pass")]
+    SubpassOutOfRange(u32),
+
+    #[error("dynamic rendering is not supported by the device")]
+    DynamicRenderingNotSupported,
+
+    #[error("render stage does not use dynamic rendering")]
+    NotADynamicRenderingStage,
+
+    #[error("render stage uses dynamic rendering")]
+    NotARenderPassStage,
 }
 
 static BIND_POINT: vk::PipelineBindPoint = vk::PipelineBindPoint::GRAPHICS;
 
+// An attachment of a dynamic rendering stage with the images it renders and resolves into.
+pub struct VERenderingTarget<'a> {
+    pub attachment: &'a VEAttachment,
+    pub image: &'a mut VEImage,
+    pub resolve_image: Option<&'a mut VEImage>,
+}
+
 pub struct VERenderStage {
     device: Arc<VEDevice>,
     pipeline: Arc<VEGraphicsPipeline>,
-    render_pass: VERenderPass,
-    framebuffer: VEFrameBuffer,
+    // None for dynamic rendering stages
+    render_pass: Option<Arc<VERenderPass>>,
+    framebuffer: Option<VEFrameBuffer>,
     viewport_width: u32,
     viewport_height: u32,
+    flip_viewport_y: bool,
+    attachment_formats: Vec<VEAttachmentFormat>,
     clear_values: Vec<vk::ClearValue>,
+    push_constant_ranges: Vec<VEPushConstantRange>,
+    push_descriptor_sets: VEPushDescriptorSets,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub enum VECullMode {
     None,
     Front,
     Back,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub enum VEPrimitiveTopology {
     Points,
     LineList,
@@ -53,7 +119,7 @@ pub enum VEPrimitiveTopology {
     TriangleFan,
 }
 
-fn get_primitive_topology(topo: VEPrimitiveTopology) -> vk::PrimitiveTopology {
+pub(crate) fn get_primitive_topology(topo: VEPrimitiveTopology) -> vk::PrimitiveTopology {
     match topo {
         VEPrimitiveTopology::Points => vk::PrimitiveTopology::POINT_LIST,
         VEPrimitiveTopology::LineList => vk::PrimitiveTopology::LINE_LIST,
@@ -64,7 +130,7 @@ fn get_primitive_topology(topo: VEPrimitiveTopology) -> vk::PrimitiveTopology {
     }
 }
 
-fn get_cull_flags(mode: VECullMode) -> vk::CullModeFlags {
+pub(crate) fn get_cull_flags(mode: VECullMode) -> vk::CullModeFlags {
     match mode {
         VECullMode::None => vk::CullModeFlags::NONE,
         VECullMode::Front => vk::CullModeFlags::FRONT,
@@ -72,48 +138,160 @@ fn get_cull_flags(mode: VECullMode) -> vk::CullModeFlags {
     }
 }
 
+// Shaders, resources and fixed function state of a render stage's pipeline.
+#[derive(Clone, Copy)]
+pub struct VEPipelineDesc<'a> {
+    pub set_layouts: &'a [&'a VEDescriptorSetLayout],
+    pub push_constant_ranges: &'a [VEPushConstantRange],
+    pub vertex_shader: &'a VEShaderModule,
+    pub fragment_shader: &'a VEShaderModule,
+    pub vertex_bindings: &'a [VEVertexBindingLayout],
+    pub primitive_topology: VEPrimitiveTopology,
+    pub cull_mode: VECullMode,
+    pub pipeline_state: &'a VEPipelineState,
+}
+
+fn get_attachments_size(attachments: &[&VEAttachment]) -> Result<(u32, u32), VERenderStageError> {
+    let (width, height) = match attachments.first() {
+        Some(first) => (first.width, first.height),
+        None => return Err(VERenderStageError::IncompatibleAttachments),
+    };
+    if attachments
+        .iter()
+        .any(|a| a.width != width || a.height != height)
+    {
+        return Err(VERenderStageError::AttachmentSizeMismatch);
+    }
+    Ok((width, height))
+}
+
+// Integer formats cannot be averaged.
+fn get_resolve_mode(attachment: &VEAttachment) -> vk::ResolveModeFlags {
+    let is_integer = get_format_info_from_vk(attachment.description.format)
+        .is_some_and(|info| info.is_integer());
+    if attachment.is_depth || is_integer {
+        vk::ResolveModeFlags::SAMPLE_ZERO
+    } else {
+        vk::ResolveModeFlags::AVERAGE
+    }
+}
+
 impl VERenderStage {
     pub fn new(
         device: Arc<VEDevice>,
         viewport_width: u32,
         viewport_height: u32,
         attachments: &[&VEAttachment],
-        set_layouts: &[&VEDescriptorSetLayout],
-        vertex_shader: &VEShaderModule,
-        fragment_shader: &VEShaderModule,
-        vertex_attributes: &[VertexAttribFormat],
-        primitive_topology: VEPrimitiveTopology,
-        cull_mode: VECullMode,
+        pipeline_desc: &VEPipelineDesc,
     ) -> Result<VERenderStage, VERenderStageError> {
-        let render_pass = VERenderPass::new(device.clone(), attachments)?;
-
-        let framebuffer = VEFrameBuffer::new(
-            device.clone(),
+        let desc = VERenderPassDesc::single_subpass(attachments)?;
+        let render_pass = Arc::new(VERenderPass::from_desc(device.clone(), &desc)?);
+        Self::create(
+            device,
             viewport_width,
             viewport_height,
-            &render_pass,
-            attachments,
+            Some(render_pass),
+            &desc,
+            0,
+            pipeline_desc,
+        )
+    }
+
+    // Stages sharing a render pass are bound in subpass order. The one for subpass 0 owns the
+    // framebuffer and begins the render pass in `bind`, `next_subpass` has to be called before
+    // binding the stage of each later subpass. `desc` must be the one the render pass was created
+    // from.
+    pub fn from_render_pass(
+        device: Arc<VEDevice>,
+        render_pass: Arc<VERenderPass>,
+        desc: &VERenderPassDesc,
+        subpass: u32,
+        pipeline_desc: &VEPipelineDesc,
+    ) -> Result<VERenderStage, VERenderStageError> {
+        if subpass >= render_pass.subpass_count {
+            return Err(VERenderStageError::SubpassOutOfRange(subpass));
+        }
+        if !render_pass.is_compatible(desc) {
+            return Err(VERenderStageError::IncompatibleAttachments);
+        }
+        let (width, height) = get_attachments_size(&desc.attachments)?;
+        Self::create(
+            device,
+            width,
+            height,
+            Some(render_pass),
+            desc,
+            subpass,
+            pipeline_desc,
+        )
+    }
+
+    // Renders with vkCmdBeginRendering into attachments given to `begin_rendering`, which only need
+    // to match the formats and sample counts of `attachments`.
+    pub fn new_dynamic(
+        device: Arc<VEDevice>,
+        attachments: &[&VEAttachment],
+        pipeline_desc: &VEPipelineDesc,
+    ) -> Result<VERenderStage, VERenderStageError> {
+        if !device.features.dynamic_rendering {
+            return Err(VERenderStageError::DynamicRenderingNotSupported);
+        }
+        let desc = VERenderPassDesc::single_subpass(attachments)?;
+        if desc
+            .attachments
+            .windows(2)
+            .any(|pair| pair[0].description.samples != pair[1].description.samples)
+        {
+            return Err(VERenderStageError::IncompatibleAttachments);
+        }
+        let (width, height) = get_attachments_size(&desc.attachments)?;
+        Self::create(device, width, height, None, &desc, 0, pipeline_desc)
+    }
+
+    fn create(
+        device: Arc<VEDevice>,
+        viewport_width: u32,
+        viewport_height: u32,
+        render_pass: Option<Arc<VERenderPass>>,
+        desc: &VERenderPassDesc,
+        subpass: u32,
+        pipeline_desc: &VEPipelineDesc,
+    ) -> Result<VERenderStage, VERenderStageError> {
+        let push_constant_ranges = resolve_push_constant_ranges(
+            &device,
+            pipeline_desc.push_constant_ranges,
+            &[pipeline_desc.vertex_shader, pipeline_desc.fragment_shader],
         )?;
 
+        let push_descriptor_sets =
+            VEPushDescriptorSets::new(device.clone(), BIND_POINT, pipeline_desc.set_layouts);
+
+        let framebuffer = match &render_pass {
+            Some(render_pass) if subpass == 0 => Some(VEFrameBuffer::new(
+                device.clone(),
+                viewport_width,
+                viewport_height,
+                render_pass,
+                desc,
+            )?),
+            _ => None,
+        };
+
+        let subpass_attachments = match desc.subpasses.get(subpass as usize) {
+            Some(subpass_desc) => desc.subpass_attachments(subpass_desc),
+            None => return Err(VERenderStageError::SubpassOutOfRange(subpass)),
+        };
         let pipeline = VEGraphicsPipeline::new(
             device.clone(),
-            viewport_width,
-            viewport_height,
-            set_layouts,
-            vertex_shader,
-            fragment_shader,
-            &render_pass,
-            attachments,
-            vertex_attributes,
-            get_primitive_topology(primitive_topology),
-            get_cull_flags(cull_mode),
+            &VEPipelineDesc {
+                push_constant_ranges: &push_constant_ranges,
+                ..*pipeline_desc
+            },
+            render_pass.as_deref(),
+            subpass,
+            &subpass_attachments,
         )?;
 
-        let clear_values = attachments
-            .iter()
-            .map(|a| a.clear.unwrap_or_else(|| vk::ClearValue::default()))
-            .collect();
-
         Ok(VERenderStage {
             device: device.clone(),
             pipeline: Arc::new(pipeline),
@@ -121,15 +299,117 @@ impl VERenderStage {
             framebuffer,
             viewport_width,
             viewport_height,
-            clear_values,
+            flip_viewport_y: pipeline_desc.pipeline_state.flip_viewport_y,
+            attachment_formats: desc.attachment_formats(),
+            clear_values: desc.clear_values(),
+            push_constant_ranges,
+            push_descriptor_sets,
         })
     }
 
+    // Recreates only the framebuffer, the pipeline and render pass are kept since viewport and
+    // scissor are dynamic state. The attachments must match the ones the stage was created with.
+    // Waits for the device to go idle before the old framebuffer is destroyed.
+    pub fn resize(&mut self, attachments: &[&VEAttachment]) -> Result<(), VERenderStageError> {
+        self.resize_render_pass(&VERenderPassDesc::single_subpass(attachments)?)
+    }
+
+    // Same as `resize` for stages created with `from_render_pass`, every stage sharing the render
+    // pass has to be resized.
+    pub fn resize_render_pass(
+        &mut self,
+        desc: &VERenderPassDesc,
+    ) -> Result<(), VERenderStageError> {
+        let compatible = match &self.render_pass {
+            Some(render_pass) => render_pass.is_compatible(desc),
+            None => desc.attachment_formats() == self.attachment_formats,
+        };
+        if !compatible {
+            return Err(VERenderStageError::IncompatibleAttachments);
+        }
+        let (width, height) = get_attachments_size(&desc.attachments)?;
+
+        if let (Some(render_pass), Some(_)) = (&self.render_pass, &self.framebuffer) {
+            self.device.wait_idle()?;
+            self.framebuffer = Some(VEFrameBuffer::new(
+                self.device.clone(),
+                width,
+                height,
+                render_pass,
+                desc,
+            )?);
+        }
+        self.viewport_width = width;
+        self.viewport_height = height;
+        self.clear_values = desc.clear_values();
+        Ok(())
+    }
+
+    // Coordinates are in framebuffer pixels with the origin at the top left, the Y flip of the
+    // pipeline state is applied here.
+    pub fn set_viewport(
+        &self,
+        command_buffer: &VECommandBuffer,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) {
+        let viewport = if self.flip_viewport_y {
+            vk::Viewport::default()
+                .x(x)
+                .y(y + height)
+                .width(width)
+                .height(-height)
+        } else {
+            vk::Viewport::default()
+                .x(x)
+                .y(y)
+                .width(width)
+                .height(height)
+        };
+        unsafe {
+            self.device.device.cmd_set_viewport(
+                command_buffer.handle,
+                0,
+                &[viewport.min_depth(0.0).max_depth(1.0)],
+            );
+        }
+    }
+
+    pub fn set_scissor(
+        &self,
+        command_buffer: &VECommandBuffer,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) {
+        let scissor = vk::Rect2D::default()
+            .offset(vk::Offset2D { x, y })
+            .extent(vk::Extent2D { width, height });
+        unsafe {
+            self.device
+                .device
+                .cmd_set_scissor(command_buffer.handle, 0, &[scissor]);
+        }
+    }
+
     pub fn set_descriptor_set(
         &self,
         command_buffer: &VECommandBuffer,
         index: u32,
         set: &VEDescriptorSet,
+    ) {
+        self.set_descriptor_set_with_dynamic_offsets(command_buffer, index, set, &[]);
+    }
+
+    pub fn set_descriptor_set_with_dynamic_offsets(
+        &self,
+        command_buffer: &VECommandBuffer,
+        index: u32,
+        set: &VEDescriptorSet,
+        dynamic_offsets: &[u32],
     ) {
         unsafe {
             self.device.device.cmd_bind_descriptor_sets(
@@ -138,46 +418,431 @@ impl VERenderStage {
                 self.pipeline.layout,
                 index,
                 &[set.set],
-                &[],
+                dynamic_offsets,
             );
         }
     }
 
-    pub fn bind(&self, command_buffer: &VECommandBuffer) {
-        let rect = vk::Rect2D::default()
-            .offset(vk::Offset2D::default())
-            .extent(
-                vk::Extent2D::default()
-                    .width(self.viewport_width)
-                    .height(self.viewport_height),
-            );
+    pub fn push_descriptor(
+        &self,
+        command_buffer: &VECommandBuffer,
+        set: u32,
+        writes: &[VEDescriptorWrite],
+    ) -> Result<(), VERenderStageError> {
+        self.push_descriptor_sets
+            .push(command_buffer, self.pipeline.layout, set, writes)?;
+        Ok(())
+    }
 
-        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
-            .framebuffer(self.framebuffer.handle)
-            .render_pass(self.render_pass.handle)
-            .clear_values(&self.clear_values)
-            .render_area(rect);
+    pub fn push_descriptor_with_template<T: Copy>(
+        &self,
+        command_buffer: &VECommandBuffer,
+        set: u32,
+        template: &VEDescriptorUpdateTemplate<T>,
+        data: &T,
+    ) -> Result<(), VERenderStageError> {
+        self.push_descriptor_sets.push_with_template(
+            command_buffer,
+            self.pipeline.layout,
+            set,
+            template,
+            data,
+        )?;
+        Ok(())
+    }
+
+    pub fn create_push_descriptor_template<T: Copy>(
+        &self,
+        set: u32,
+        entries: &[VEDescriptorUpdateTemplateEntry],
+    ) -> Result<VEDescriptorUpdateTemplate<T>, VERenderStageError> {
+        Ok(self
+            .push_descriptor_sets
+            .create_template(self.pipeline.layout, set, entries)?)
+    }
+
+    // Recycles the descriptor sets allocated when push descriptors are not supported, call it once
+    // per frame after the previous frame's command buffers have completed.
+    pub fn reset_push_descriptors(&self) -> Result<(), VERenderStageError> {
+        self.push_descriptor_sets.reset()?;
+        Ok(())
+    }
 
+    pub fn push_constants<T: Pod>(
+        &self,
+        command_buffer: &VECommandBuffer,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        value: &T,
+    ) -> Result<(), VERenderStageError> {
+        let bytes = bytemuck::bytes_of(value);
+        validate_push_constants_update(
+            &self.push_constant_ranges,
+            stage_flags,
+            offset,
+            bytes.len() as u32,
+        )?;
         unsafe {
-            self.device.device.cmd_begin_render_pass(
+            self.device.device.cmd_push_constants(
                 command_buffer.handle,
-                &render_pass_begin_info,
-                vk::SubpassContents::INLINE,
+                self.pipeline.layout,
+                stage_flags,
+                offset,
+                bytes,
             );
+        }
+        Ok(())
+    }
+
+    // Dynamic rendering stages and stages of later subpasses only bind the pipeline here, the
+    // rendering has to be begun with `begin_rendering` or the subpass 0 stage respectively.
+    pub fn bind(&self, command_buffer: &VECommandBuffer) {
+        if let (Some(render_pass), Some(framebuffer)) = (&self.render_pass, &self.framebuffer) {
+            let rect = vk::Rect2D::default()
+                .offset(vk::Offset2D::default())
+                .extent(
+                    vk::Extent2D::default()
+                        .width(self.viewport_width)
+                        .height(self.viewport_height),
+                );
+
+            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                .framebuffer(framebuffer.handle)
+                .render_pass(render_pass.handle)
+                .clear_values(&self.clear_values)
+                .render_area(rect);
+
+            unsafe {
+                self.device.device.cmd_begin_render_pass(
+                    command_buffer.handle,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+            }
+        }
+
+        self.bind_pipeline(command_buffer, self.viewport_width, self.viewport_height);
+    }
+
+    // Advances the render pass begun by the subpass 0 stage to the next subpass.
+    pub fn next_subpass(&self, command_buffer: &VECommandBuffer) -> Result<(), VERenderStageError> {
+        if self.render_pass.is_none() {
+            return Err(VERenderStageError::NotARenderPassStage);
+        }
+        unsafe {
+            self.device
+                .device
+                .cmd_next_subpass(command_buffer.handle, vk::SubpassContents::INLINE)
+        };
+        Ok(())
+    }
+
+    // Transitions the target images to attachment layouts and begins rendering into them. The
+    // attachments only need to match the formats and sample counts of the ones the stage was
+    // created with.
+    pub fn begin_rendering(
+        &self,
+        command_buffer: &VECommandBuffer,
+        targets: &mut [VERenderingTarget],
+    ) -> Result<(), VERenderStageError> {
+        if self.render_pass.is_some() {
+            return Err(VERenderStageError::NotADynamicRenderingStage);
+        }
+        let attachments: Vec<&VEAttachment> =
+            targets.iter().map(|target| target.attachment).collect();
+        let desc = VERenderPassDesc::single_subpass(&attachments)?;
+        if desc.attachment_formats() != self.attachment_formats {
+            return Err(VERenderStageError::IncompatibleAttachments);
+        }
+        let (width, height) = get_attachments_size(&desc.attachments)?;
+
+        for target in targets.iter_mut() {
+            let attachment = target.attachment;
+            let access = if attachment.is_depth {
+                VEImageAccess::DepthAttachment
+            } else {
+                VEImageAccess::ColorAttachment
+            };
+            if target.image.handle != attachment.image {
+                return Err(VERenderStageError::IncompatibleAttachments);
+            }
+            command_buffer.require_range(target.image, access, attachment.range)?;
+            match (&attachment.resolve, &mut target.resolve_image) {
+                (Some(resolve), Some(image)) if image.handle == resolve.image => {
+                    let range = VESubresourceRange::new(0, 1, 0, image.array_layers);
+                    command_buffer.require_range(image, access, range)?;
+                }
+                (None, None) => {}
+                _ => return Err(VERenderStageError::IncompatibleAttachments),
+            }
+        }
+
+        let rendering_attachment = |attachment: &VEAttachment, stencil: bool| {
+            let layout = if attachment.is_depth {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            };
+            let description = &attachment.description;
+            let (load_op, store_op) = if stencil {
+                (description.stencil_load_op, description.stencil_store_op)
+            } else {
+                (description.load_op, description.store_op)
+            };
+            let mut info = vk::RenderingAttachmentInfo::default()
+                .image_view(attachment.image_view)
+                .image_layout(layout)
+                .load_op(load_op)
+                .store_op(store_op)
+                .clear_value(attachment.clear.unwrap_or_default());
+            if let Some(resolve) = &attachment.resolve {
+                info = info
+                    .resolve_mode(get_resolve_mode(attachment))
+                    .resolve_image_view(resolve.image_view)
+                    .resolve_image_layout(layout);
+            }
+            info
+        };
+        let color_attachments: Vec<vk::RenderingAttachmentInfo> = desc
+            .attachments
+            .iter()
+            .filter(|a| !a.is_depth)
+            .map(|a| rendering_attachment(a, false))
+            .collect();
+        let depth = desc.attachments.iter().find(|a| a.is_depth);
+        let depth_info = depth.and_then(|a| get_format_info_from_vk(a.description.format));
+        let depth_attachment = depth
+            .filter(|_| depth_info.is_some_and(|info| info.has_depth()))
+            .map(|a| rendering_attachment(a, false));
+        let stencil_attachment = depth
+            .filter(|_| depth_info.is_some_and(|info| info.has_stencil()))
+            .map(|a| rendering_attachment(a, true));
+
+        let mut rendering_info = vk::RenderingInfo::default()
+            .render_area(
+                vk::Rect2D::default()
+                    .offset(vk::Offset2D::default())
+                    .extent(vk::Extent2D { width, height }),
+            )
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+        if let Some(depth_attachment) = &depth_attachment {
+            rendering_info = rendering_info.depth_attachment(depth_attachment);
+        }
+        if let Some(stencil_attachment) = &stencil_attachment {
+            rendering_info = rendering_info.stencil_attachment(stencil_attachment);
+        }
+
+        unsafe {
+            self.device
+                .device
+                .cmd_begin_rendering(command_buffer.handle, &rendering_info);
+        }
 
+        self.bind_pipeline(command_buffer, width, height);
+        Ok(())
+    }
+
+    fn bind_pipeline(&self, command_buffer: &VECommandBuffer, width: u32, height: u32) {
+        unsafe {
             self.device.device.cmd_bind_pipeline(
                 command_buffer.handle,
                 BIND_POINT,
                 self.pipeline.pipeline,
             );
         }
+
+        self.set_viewport(command_buffer, 0.0, 0.0, width as f32, height as f32);
+        self.set_scissor(command_buffer, 0, 0, width, height);
+    }
+
+    // Binds `(buffer, offset)` pairs to consecutive vertex bindings starting at `first_binding`.
+    pub fn bind_vertex_buffers(
+        &self,
+        command_buffer: &VECommandBuffer,
+        first_binding: u32,
+        buffers: &[(&VEBuffer, u64)],
+    ) {
+        let handles: Vec<vk::Buffer> = buffers.iter().map(|(buffer, _)| buffer.buffer).collect();
+        let offsets: Vec<vk::DeviceSize> = buffers.iter().map(|(_, offset)| *offset).collect();
+        unsafe {
+            self.device.device.cmd_bind_vertex_buffers(
+                command_buffer.handle,
+                first_binding,
+                &handles,
+                &offsets,
+            );
+        }
+    }
+
+    pub fn draw(
+        &self,
+        command_buffer: &VECommandBuffer,
+        vertex_count: u32,
+        instances: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.device.device.cmd_draw(
+                command_buffer.handle,
+                vertex_count,
+                instances,
+                first_vertex,
+                first_instance,
+            );
+        }
+    }
+
+    pub fn draw_indirect(
+        &self,
+        command_buffer: &VECommandBuffer,
+        commands: &VETypedBuffer<VEDrawIndirectCommand>,
+        first_command: u32,
+        draw_count: u32,
+    ) -> Result<(), VERenderStageError> {
+        validate_indirect_buffer(&self.device, commands, first_command, draw_count)?;
+        unsafe {
+            self.device.device.cmd_draw_indirect(
+                command_buffer.handle,
+                commands.buffer.buffer,
+                VETypedBuffer::<VEDrawIndirectCommand>::offset_of(first_command),
+                draw_count,
+                VETypedBuffer::<VEDrawIndirectCommand>::element_size() as u32,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn draw_indexed_indirect(
+        &self,
+        command_buffer: &VECommandBuffer,
+        commands: &VETypedBuffer<VEDrawIndexedIndirectCommand>,
+        first_command: u32,
+        draw_count: u32,
+    ) -> Result<(), VERenderStageError> {
+        validate_indirect_buffer(&self.device, commands, first_command, draw_count)?;
+        unsafe {
+            self.device.device.cmd_draw_indexed_indirect(
+                command_buffer.handle,
+                commands.buffer.buffer,
+                VETypedBuffer::<VEDrawIndexedIndirectCommand>::offset_of(first_command),
+                draw_count,
+                VETypedBuffer::<VEDrawIndexedIndirectCommand>::element_size() as u32,
+            );
+        }
+        Ok(())
+    }
+
+    // The number of draws is read on the GPU from `count` at `count_index`, clamped to
+    // `max_draw_count`.
+    pub fn draw_indirect_count(
+        &self,
+        command_buffer: &VECommandBuffer,
+        commands: &VETypedBuffer<VEDrawIndirectCommand>,
+        first_command: u32,
+        count: &VETypedBuffer<u32>,
+        count_index: u32,
+        max_draw_count: u32,
+    ) -> Result<(), VERenderStageError> {
+        self.validate_indirect_count(commands, first_command, count, count_index, max_draw_count)?;
+        unsafe {
+            self.device.device.cmd_draw_indirect_count(
+                command_buffer.handle,
+                commands.buffer.buffer,
+                VETypedBuffer::<VEDrawIndirectCommand>::offset_of(first_command),
+                count.buffer.buffer,
+                VETypedBuffer::<u32>::offset_of(count_index),
+                max_draw_count,
+                VETypedBuffer::<VEDrawIndirectCommand>::element_size() as u32,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn draw_indexed_indirect_count(
+        &self,
+        command_buffer: &VECommandBuffer,
+        commands: &VETypedBuffer<VEDrawIndexedIndirectCommand>,
+        first_command: u32,
+        count: &VETypedBuffer<u32>,
+        count_index: u32,
+        max_draw_count: u32,
+    ) -> Result<(), VERenderStageError> {
+        self.validate_indirect_count(commands, first_command, count, count_index, max_draw_count)?;
+        unsafe {
+            self.device.device.cmd_draw_indexed_indirect_count(
+                command_buffer.handle,
+                commands.buffer.buffer,
+                VETypedBuffer::<VEDrawIndexedIndirectCommand>::offset_of(first_command),
+                count.buffer.buffer,
+                VETypedBuffer::<u32>::offset_of(count_index),
+                max_draw_count,
+                VETypedBuffer::<VEDrawIndexedIndirectCommand>::element_size() as u32,
+            );
+        }
+        Ok(())
+    }
+
+    fn validate_indirect_count<T: Pod>(
+        &self,
+        commands: &VETypedBuffer<T>,
+        first_command: u32,
+        count: &VETypedBuffer<u32>,
+        count_index: u32,
+        max_draw_count: u32,
+    ) -> Result<(), VEIndirectCommandError> {
+        if !self.device.features.draw_indirect_count {
+            return Err(VEIndirectCommandError::DrawIndirectCountNotSupported);
+        }
+        validate_indirect_buffer(&self.device, commands, first_command, max_draw_count)?;
+        validate_indirect_buffer(&self.device, count, count_index, 1)
+    }
+
+    // Ends the rendering and records the final layouts the render pass left the target images in.
+    // Dynamic rendering keeps them in the layouts `begin_rendering` recorded.
+    pub fn end_render_pass_with_targets(
+        &self,
+        command_buffer: &VECommandBuffer,
+        targets: &mut [VERenderingTarget],
+    ) -> Result<(), VERenderStageError> {
+        for target in targets.iter() {
+            let resolve_matches = match (&target.attachment.resolve, &target.resolve_image) {
+                (Some(resolve), Some(image)) => image.handle == resolve.image,
+                (None, None) => true,
+                _ => false,
+            };
+            if target.image.handle != target.attachment.image || !resolve_matches {
+                return Err(VERenderStageError::IncompatibleAttachments);
+            }
+        }
+        self.end_render_pass(command_buffer);
+        if self.render_pass.is_none() {
+            return Ok(());
+        }
+
+        for target in targets.iter_mut() {
+            let attachment = target.attachment;
+            target
+                .image
+                .set_range_layout(attachment.range, attachment.description.final_layout);
+            if let (Some(resolve), Some(image)) = (&attachment.resolve, &mut target.resolve_image) {
+                let range = VESubresourceRange::new(0, 1, 0, image.array_layers);
+                image.set_range_layout(range, resolve.description.final_layout);
+            }
+        }
+        Ok(())
     }
 
     pub fn end_render_pass(&self, command_buffer: &VECommandBuffer) {
         unsafe {
-            self.device
-                .device
-                .cmd_end_render_pass(command_buffer.handle);
+            match self.render_pass {
+                Some(_) => self
+                    .device
+                    .device
+                    .cmd_end_render_pass(command_buffer.handle),
+                None => self.device.device.cmd_end_rendering(command_buffer.handle),
+            }
         }
     }
 }
@@ -1,5 +1,7 @@
 use crate::core::device::VEDevice;
 use crate::graphics::attachment::VEAttachment;
+use crate::graphics::render_pass_desc::{VEAttachmentFormat, VERenderPassDesc, VESubpassDesc};
+use crate::image::image_format::get_aspect_from_vk;
 use ash::vk;
 use std::sync::Arc;
 use thiserror::Error;
@@ -8,10 +10,367 @@ use thiserror::Error;
 pub enum VERenderPassError {
     #[error("creation failed")]
     CreationFailed(#[from] vk::Result),
+
+    #[error("attachments have different sample counts")]
+    MismatchedSampleCounts,
+
+    #[error("depth resolve is not supported by the device")]
+    DepthResolveNotSupported,
+
+    #[error("render pass has no subpasses")]
+    NoSubpasses,
+
+    #[error("attachment index {0} is out of range")]
+    AttachmentIndexOutOfRange(u32),
+
+    #[error("attachment {0} is resolved by more than one subpass")]
+    AttachmentResolvedTwice(u32),
+
+    #[error("a single subpass can only have one depth attachment, got {0}")]
+    MultipleDepthAttachments(usize),
 }
 
 pub struct VERenderPass {
+    device: Arc<VEDevice>,
     pub handle: vk::RenderPass,
+    pub samples: vk::SampleCountFlags,
+    pub subpass_count: u32,
+    attachment_formats: Vec<VEAttachmentFormat>,
+    subpasses: Vec<VESubpassDesc>,
+}
+
+// Stages and accesses of one attachment within one subpass.
+#[derive(Clone, Copy)]
+struct VEAttachmentUse {
+    stages: vk::PipelineStageFlags,
+    read: vk::AccessFlags,
+    write: vk::AccessFlags,
+    resolves: bool,
+}
+
+fn get_attachment_stages(attachment: &VEAttachment) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    if attachment.is_depth {
+        (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+    } else {
+        (
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        )
+    }
+}
+
+fn get_attachment_use(
+    desc: &VERenderPassDesc,
+    subpass: &VESubpassDesc,
+    index: u32,
+) -> Option<VEAttachmentUse> {
+    if !subpass.uses(index) {
+        return None;
+    }
+    let mut result = VEAttachmentUse {
+        stages: vk::PipelineStageFlags::empty(),
+        read: vk::AccessFlags::empty(),
+        write: vk::AccessFlags::empty(),
+        resolves: false,
+    };
+    let is_output =
+        subpass.color_attachments.contains(&index) || subpass.depth_attachment == Some(index);
+    if subpass.color_attachments.contains(&index) {
+        result.stages |= vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        result.read |= vk::AccessFlags::COLOR_ATTACHMENT_READ;
+        result.write |= vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
+    }
+    if subpass.depth_attachment == Some(index) {
+        result.stages |= vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        result.read |= vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ;
+        result.write |= vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    }
+    if subpass.input_attachments.contains(&index) {
+        result.stages |= vk::PipelineStageFlags::FRAGMENT_SHADER;
+        result.read |= vk::AccessFlags::INPUT_ATTACHMENT_READ;
+    }
+    let has_resolve = desc.attachments[index as usize].resolve.is_some();
+    if subpass.resolve && is_output && has_resolve {
+        // Resolves happen in the color attachment output stage, for depth as well
+        result.stages |= vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        result.resolves = true;
+    }
+    Some(result)
+}
+
+// Where the contents are consumed after the render pass, judging by the final layout.
+fn get_final_layout_stage_access(
+    layout: vk::ImageLayout,
+) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    match layout {
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => (
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        ),
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ,
+        ),
+        // Presentation is synchronized by the semaphore, the dependency only orders the transition
+        vk::ImageLayout::PRESENT_SRC_KHR => (
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::AccessFlags::empty(),
+        ),
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ),
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        _ => (
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+        ),
+    }
+}
+
+// Merges into an existing dependency between the same pair of subpasses.
+fn add_dependency(
+    dependencies: &mut Vec<vk::SubpassDependency>,
+    dependency: vk::SubpassDependency,
+) {
+    let existing = dependencies.iter_mut().find(|d| {
+        d.src_subpass == dependency.src_subpass && d.dst_subpass == dependency.dst_subpass
+    });
+    match existing {
+        None => dependencies.push(dependency),
+        Some(existing) => {
+            existing.src_stage_mask |= dependency.src_stage_mask;
+            existing.src_access_mask |= dependency.src_access_mask;
+            existing.dst_stage_mask |= dependency.dst_stage_mask;
+            existing.dst_access_mask |= dependency.dst_access_mask;
+            existing.dependency_flags &= dependency.dependency_flags;
+        }
+    }
+}
+
+// Each attachment gets a dependency into its first subpass, between consecutive subpasses using
+// it and out of its last subpass. The incoming one always waits on earlier attachment writes since
+// clears and DONT_CARE loads are writes too, the outgoing one only exists when it is stored or
+// resolved. Writes from other stages, like transfers or compute, have to be synchronized by the
+// caller.
+fn create_dependencies(desc: &VERenderPassDesc) -> Vec<vk::SubpassDependency> {
+    let mut dependencies = vec![];
+    for (index, attachment) in desc.attachments.iter().enumerate() {
+        let uses: Vec<(u32, VEAttachmentUse)> = desc
+            .subpasses
+            .iter()
+            .enumerate()
+            .filter_map(|(subpass_index, subpass)| {
+                get_attachment_use(desc, subpass, index as u32)
+                    .map(|attachment_use| (subpass_index as u32, attachment_use))
+            })
+            .collect();
+        let (Some((first, first_use)), Some((last, last_use))) = (uses.first(), uses.last()) else {
+            continue;
+        };
+
+        let description = &attachment.description;
+        let (stages, write) = get_attachment_stages(attachment);
+        add_dependency(
+            &mut dependencies,
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(*first)
+                .src_stage_mask(stages)
+                .src_access_mask(write)
+                .dst_stage_mask(first_use.stages)
+                .dst_access_mask(first_use.read | first_use.write),
+        );
+
+        for pair in uses.windows(2) {
+            let ((src, src_use), (dst, dst_use)) = (pair[0], pair[1]);
+            add_dependency(
+                &mut dependencies,
+                vk::SubpassDependency::default()
+                    .src_subpass(src)
+                    .dst_subpass(dst)
+                    .src_stage_mask(src_use.stages)
+                    .src_access_mask(src_use.write)
+                    .dst_stage_mask(dst_use.stages)
+                    .dst_access_mask(dst_use.read | dst_use.write)
+                    .dependency_flags(vk::DependencyFlags::BY_REGION),
+            );
+        }
+
+        let stores = description.store_op == vk::AttachmentStoreOp::STORE
+            || description.stencil_store_op == vk::AttachmentStoreOp::STORE;
+        if stores {
+            let (dst_stages, dst_access) = get_final_layout_stage_access(description.final_layout);
+            add_dependency(
+                &mut dependencies,
+                vk::SubpassDependency::default()
+                    .src_subpass(*last)
+                    .dst_subpass(vk::SUBPASS_EXTERNAL)
+                    .src_stage_mask(last_use.stages)
+                    .src_access_mask(last_use.write)
+                    .dst_stage_mask(dst_stages)
+                    .dst_access_mask(dst_access),
+            );
+        }
+
+        let resolving = uses
+            .iter()
+            .find(|(_, attachment_use)| attachment_use.resolves);
+        if let (Some((subpass, _)), Some(resolve)) = (resolving, &attachment.resolve) {
+            let (dst_stages, dst_access) =
+                get_final_layout_stage_access(resolve.description.final_layout);
+            add_dependency(
+                &mut dependencies,
+                vk::SubpassDependency::default()
+                    .src_subpass(*subpass)
+                    .dst_subpass(vk::SUBPASS_EXTERNAL)
+                    .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                    .dst_stage_mask(dst_stages)
+                    .dst_access_mask(dst_access),
+            );
+        }
+    }
+    dependencies.extend(desc.dependencies.iter().map(|d| d.build()));
+    dependencies
+}
+
+fn get_reference_layout(subpass: &VESubpassDesc, index: u32, is_depth: bool) -> vk::ImageLayout {
+    let is_input = subpass.input_attachments.contains(&index);
+    let is_output =
+        subpass.color_attachments.contains(&index) || subpass.depth_attachment == Some(index);
+    match (is_output, is_input, is_depth) {
+        (true, true, _) => vk::ImageLayout::GENERAL,
+        (true, false, false) => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        (true, false, true) => vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        (false, _, false) => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        (false, _, true) => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+    }
+}
+
+struct VESubpassReferences {
+    colors: Vec<vk::AttachmentReference>,
+    inputs: Vec<vk::AttachmentReference>,
+    resolves: Vec<vk::AttachmentReference>,
+    depth: Option<vk::AttachmentReference>,
+    depth_resolve: Option<vk::AttachmentReference>,
+    preserve: Vec<u32>,
+}
+
+// Descriptions of the attachments followed by their resolve targets, and the index of the resolve
+// target of every attachment.
+fn create_attachment_descriptions(
+    desc: &VERenderPassDesc,
+) -> (Vec<vk::AttachmentDescription>, Vec<Option<u32>>) {
+    let mut atta_descs: Vec<vk::AttachmentDescription> =
+        desc.attachments.iter().map(|a| a.description).collect();
+    let mut resolve_indices = vec![];
+    for attachment in &desc.attachments {
+        resolve_indices.push(attachment.resolve.as_ref().map(|resolve| {
+            atta_descs.push(resolve.description);
+            atta_descs.len() as u32 - 1
+        }));
+    }
+    (atta_descs, resolve_indices)
+}
+
+fn create_references(
+    desc: &VERenderPassDesc,
+    resolve_indices: &[Option<u32>],
+) -> Result<Vec<VESubpassReferences>, VERenderPassError> {
+    let mut resolved = vec![false; desc.attachments.len()];
+    let mut references = vec![];
+    for (subpass_index, subpass) in desc.subpasses.iter().enumerate() {
+        let reference = |index: u32| {
+            let is_depth = desc.attachments[index as usize].is_depth;
+            vk::AttachmentReference::default()
+                .attachment(index)
+                .layout(get_reference_layout(subpass, index, is_depth))
+        };
+        let resolve_reference = |index: u32, layout: vk::ImageLayout| {
+            vk::AttachmentReference::default()
+                .attachment(resolve_indices[index as usize].unwrap_or(vk::ATTACHMENT_UNUSED))
+                .layout(layout)
+        };
+
+        let mut resolves = vec![];
+        let mut depth_resolve = None;
+        if subpass.resolve {
+            for index in subpass
+                .color_attachments
+                .iter()
+                .chain(subpass.depth_attachment.iter())
+            {
+                if resolve_indices[*index as usize].is_some() {
+                    if resolved[*index as usize] {
+                        return Err(VERenderPassError::AttachmentResolvedTwice(*index));
+                    }
+                    resolved[*index as usize] = true;
+                }
+            }
+            if subpass
+                .color_attachments
+                .iter()
+                .any(|index| resolve_indices[*index as usize].is_some())
+            {
+                resolves = subpass
+                    .color_attachments
+                    .iter()
+                    .map(|index| {
+                        resolve_reference(*index, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    })
+                    .collect();
+            }
+            depth_resolve = subpass
+                .depth_attachment
+                .filter(|index| resolve_indices[*index as usize].is_some())
+                .map(|index| {
+                    resolve_reference(index, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                });
+        }
+
+        // Keep the contents of attachments used before and after this subpass
+        let preserve = (0..desc.attachments.len() as u32)
+            .filter(|index| {
+                !subpass.uses(*index)
+                    && desc.subpasses[..subpass_index]
+                        .iter()
+                        .any(|s| s.uses(*index))
+                    && desc.subpasses[subpass_index + 1..]
+                        .iter()
+                        .any(|s| s.uses(*index))
+            })
+            .collect();
+
+        references.push(VESubpassReferences {
+            colors: subpass
+                .color_attachments
+                .iter()
+                .map(|i| reference(*i))
+                .collect(),
+            inputs: subpass
+                .input_attachments
+                .iter()
+                .map(|i| reference(*i))
+                .collect(),
+            resolves,
+            depth: subpass.depth_attachment.map(reference),
+            depth_resolve,
+            preserve,
+        });
+    }
+    Ok(references)
 }
 
 impl VERenderPass {
@@ -19,55 +378,456 @@ impl VERenderPass {
         device: Arc<VEDevice>,
         attachments: &[&VEAttachment],
     ) -> Result<VERenderPass, VERenderPassError> {
-        let color_attas: Vec<&&VEAttachment> = attachments.iter().filter(|x| !x.is_depth).collect();
-        let depth_atta = attachments.iter().filter(|x| x.is_depth).last();
+        Self::from_desc(device, &VERenderPassDesc::single_subpass(attachments)?)
+    }
 
-        let color_references: Vec<vk::AttachmentReference> = (0..color_attas.len())
-            .map(|i| Self::create_subpass_attachment_reference(i as i32, false))
-            .collect();
+    pub fn from_desc(
+        device: Arc<VEDevice>,
+        desc: &VERenderPassDesc,
+    ) -> Result<VERenderPass, VERenderPassError> {
+        if desc.subpasses.is_empty() {
+            return Err(VERenderPassError::NoSubpasses);
+        }
+        for subpass in &desc.subpasses {
+            let out_of_range = subpass
+                .color_attachments
+                .iter()
+                .chain(subpass.input_attachments.iter())
+                .chain(subpass.depth_attachment.iter())
+                .find(|index| **index as usize >= desc.attachments.len());
+            if let Some(index) = out_of_range {
+                return Err(VERenderPassError::AttachmentIndexOutOfRange(*index));
+            }
+        }
 
-        let depth_reference_maybe =
-            Self::create_subpass_attachment_reference(color_attas.len() as i32, true);
-        let depth_reference = match depth_atta {
-            None => None,
-            Some(_) => Some(&depth_reference_maybe), // depth last
-        };
+        let samples = desc
+            .attachments
+            .first()
+            .map_or(vk::SampleCountFlags::TYPE_1, |a| a.description.samples);
+        if desc
+            .attachments
+            .iter()
+            .any(|a| a.description.samples != samples)
+        {
+            return Err(VERenderPassError::MismatchedSampleCounts);
+        }
 
-        let subpass = Self::create_subpass(&color_references, depth_reference);
-        let subpasses = [subpass];
+        let (atta_descs, resolve_indices) = create_attachment_descriptions(desc);
+        let references = create_references(desc, &resolve_indices)?;
+        if references.iter().any(|r| r.depth_resolve.is_some())
+            && !device.features.depth_stencil_resolve
+        {
+            return Err(VERenderPassError::DepthResolveNotSupported);
+        }
+
+        let dependencies = create_dependencies(desc);
 
-        let atta_descs: Vec<vk::AttachmentDescription> =
-            attachments.iter().map(|a| a.description).collect();
+        let handle = if references.iter().all(|r| r.depth_resolve.is_none()) {
+            let subpasses: Vec<vk::SubpassDescription> =
+                references.iter().map(Self::create_subpass).collect();
 
-        let create_info = vk::RenderPassCreateInfo::default()
-            .attachments(&atta_descs)
-            .subpasses(&subpasses);
+            let create_info = vk::RenderPassCreateInfo::default()
+                .attachments(&atta_descs)
+                .subpasses(&subpasses)
+                .dependencies(&dependencies);
 
-        let handle = unsafe { device.device.create_render_pass(&create_info, None)? };
+            unsafe { device.device.create_render_pass(&create_info, None)? }
+        } else {
+            // Depth resolve is only expressible through render pass 2
+            Self::create_render_pass2(&device, &atta_descs, &references, &dependencies)?
+        };
 
-        Ok(VERenderPass { handle })
+        Ok(VERenderPass {
+            device,
+            handle,
+            samples,
+            subpass_count: desc.subpasses.len() as u32,
+            attachment_formats: desc.attachment_formats(),
+            subpasses: desc.subpasses.clone(),
+        })
     }
 
-    fn create_subpass<'a>(
-        color_references: &'a [vk::AttachmentReference],
-        depth_reference: Option<&'a vk::AttachmentReference>,
-    ) -> vk::SubpassDescription<'a> {
-        let mut description =
-            vk::SubpassDescription::default().color_attachments(&color_references);
-        match depth_reference {
+    // Whether `desc` describes the same attachments and subpasses this render pass was created
+    // from, so that it can be used for its framebuffer and pipelines.
+    pub fn is_compatible(&self, desc: &VERenderPassDesc) -> bool {
+        desc.attachment_formats() == self.attachment_formats && desc.subpasses == self.subpasses
+    }
+
+    fn create_subpass(references: &VESubpassReferences) -> vk::SubpassDescription<'_> {
+        let mut description = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&references.colors)
+            .input_attachments(&references.inputs)
+            .preserve_attachments(&references.preserve);
+        if !references.resolves.is_empty() {
+            description = description.resolve_attachments(&references.resolves);
+        }
+        match &references.depth {
             None => (),
             Some(reference) => description = description.depth_stencil_attachment(reference),
         }
         description
     }
 
-    fn create_subpass_attachment_reference(index: i32, depth: bool) -> vk::AttachmentReference {
-        vk::AttachmentReference::default()
-            .attachment(index as u32)
-            .layout(if depth {
-                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
-            } else {
-                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+    fn create_render_pass2(
+        device: &VEDevice,
+        atta_descs: &[vk::AttachmentDescription],
+        references: &[VESubpassReferences],
+        dependencies: &[vk::SubpassDependency],
+    ) -> Result<vk::RenderPass, VERenderPassError> {
+        let atta_descs2: Vec<vk::AttachmentDescription2> =
+            atta_descs.iter().map(Self::to_description2).collect();
+        let to_references2 = |references: &[vk::AttachmentReference]| {
+            references
+                .iter()
+                .map(|r| Self::to_reference2(r, atta_descs))
+                .collect::<Vec<vk::AttachmentReference2>>()
+        };
+        let references2: Vec<[Vec<vk::AttachmentReference2>; 5]> = references
+            .iter()
+            .map(|r| {
+                [
+                    to_references2(&r.colors),
+                    to_references2(&r.inputs),
+                    to_references2(&r.resolves),
+                    to_references2(r.depth.as_slice()),
+                    to_references2(r.depth_resolve.as_slice()),
+                ]
             })
+            .collect();
+
+        let mut depth_resolves: Vec<Option<vk::SubpassDescriptionDepthStencilResolve>> =
+            references2
+                .iter()
+                .map(|[_, _, _, _, depth_resolve]| {
+                    depth_resolve.first().map(|reference| {
+                        vk::SubpassDescriptionDepthStencilResolve::default()
+                            .depth_resolve_mode(vk::ResolveModeFlags::SAMPLE_ZERO)
+                            .stencil_resolve_mode(vk::ResolveModeFlags::SAMPLE_ZERO)
+                            .depth_stencil_resolve_attachment(reference)
+                    })
+                })
+                .collect();
+
+        let subpasses: Vec<vk::SubpassDescription2> = references2
+            .iter()
+            .zip(references)
+            .zip(depth_resolves.iter_mut())
+            .map(
+                |(([colors, inputs, resolves, depth, _], r), depth_resolve)| {
+                    let mut subpass = vk::SubpassDescription2::default()
+                        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                        .color_attachments(colors)
+                        .input_attachments(inputs)
+                        .preserve_attachments(&r.preserve);
+                    if !resolves.is_empty() {
+                        subpass = subpass.resolve_attachments(resolves);
+                    }
+                    if let Some(depth) = depth.first() {
+                        subpass = subpass.depth_stencil_attachment(depth);
+                    }
+                    if let Some(depth_resolve) = depth_resolve {
+                        subpass = subpass.push_next(depth_resolve);
+                    }
+                    subpass
+                },
+            )
+            .collect();
+
+        let dependencies2: Vec<vk::SubpassDependency2> =
+            dependencies.iter().map(Self::to_dependency2).collect();
+
+        let create_info = vk::RenderPassCreateInfo2::default()
+            .attachments(&atta_descs2)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies2);
+
+        Ok(unsafe { device.device.create_render_pass2(&create_info, None)? })
+    }
+
+    fn to_description2(
+        description: &vk::AttachmentDescription,
+    ) -> vk::AttachmentDescription2<'static> {
+        vk::AttachmentDescription2::default()
+            .flags(description.flags)
+            .format(description.format)
+            .samples(description.samples)
+            .load_op(description.load_op)
+            .store_op(description.store_op)
+            .stencil_load_op(description.stencil_load_op)
+            .stencil_store_op(description.stencil_store_op)
+            .initial_layout(description.initial_layout)
+            .final_layout(description.final_layout)
+    }
+
+    fn to_dependency2(dependency: &vk::SubpassDependency) -> vk::SubpassDependency2<'static> {
+        vk::SubpassDependency2::default()
+            .src_subpass(dependency.src_subpass)
+            .dst_subpass(dependency.dst_subpass)
+            .src_stage_mask(dependency.src_stage_mask)
+            .dst_stage_mask(dependency.dst_stage_mask)
+            .src_access_mask(dependency.src_access_mask)
+            .dst_access_mask(dependency.dst_access_mask)
+            .dependency_flags(dependency.dependency_flags)
+    }
+
+    // Render pass 2 wants the aspect of every reference, input attachments in particular.
+    fn to_reference2(
+        reference: &vk::AttachmentReference,
+        atta_descs: &[vk::AttachmentDescription],
+    ) -> vk::AttachmentReference2<'static> {
+        let aspect = atta_descs
+            .get(reference.attachment as usize)
+            .map_or(vk::ImageAspectFlags::empty(), |d| {
+                get_aspect_from_vk(d.format)
+            });
+        vk::AttachmentReference2::default()
+            .attachment(reference.attachment)
+            .layout(reference.layout)
+            .aspect_mask(aspect)
+    }
+}
+
+impl Drop for VERenderPass {
+    fn drop(&mut self) {
+        unsafe { self.device.device.destroy_render_pass(self.handle, None) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::resource_state::VESubresourceRange;
+    use crate::graphics::attachment::VEResolveAttachment;
+
+    fn description(
+        format: vk::Format,
+        store_op: vk::AttachmentStoreOp,
+        final_layout: vk::ImageLayout,
+    ) -> vk::AttachmentDescription {
+        vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(store_op)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .final_layout(final_layout)
+    }
+
+    fn attachment(is_depth: bool, description: vk::AttachmentDescription) -> VEAttachment {
+        VEAttachment {
+            image_view: vk::ImageView::null(),
+            width: 64,
+            height: 64,
+            is_depth,
+            description,
+            blending: None,
+            clear: None,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+            blend_constants: None,
+            logic_op: None,
+            resolve: None,
+            image_layout: vk::ImageLayout::UNDEFINED,
+            image: vk::Image::null(),
+            range: VESubresourceRange::new(0, 1, 0, 1),
+        }
+    }
+
+    fn color(store_op: vk::AttachmentStoreOp, final_layout: vk::ImageLayout) -> VEAttachment {
+        attachment(
+            false,
+            description(vk::Format::R8G8B8A8_UNORM, store_op, final_layout),
+        )
+    }
+
+    fn depth(store_op: vk::AttachmentStoreOp) -> VEAttachment {
+        attachment(
+            true,
+            description(
+                vk::Format::D32_SFLOAT,
+                store_op,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+        )
+    }
+
+    fn find_dependency(
+        dependencies: &[vk::SubpassDependency],
+        src: u32,
+        dst: u32,
+    ) -> Option<&vk::SubpassDependency> {
+        dependencies
+            .iter()
+            .find(|d| d.src_subpass == src && d.dst_subpass == dst)
+    }
+
+    #[test]
+    fn stored_attachments_get_an_outgoing_dependency() {
+        let target = color(
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        let desc =
+            VERenderPassDesc::new(&[&target]).with_subpass(VESubpassDesc::new().with_color(0));
+        let dependencies = create_dependencies(&desc);
+
+        let Some(outgoing) = find_dependency(&dependencies, 0, vk::SUBPASS_EXTERNAL) else {
+            panic!("no outgoing dependency");
+        };
+        assert_eq!(
+            outgoing.src_stage_mask,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        );
+        assert_eq!(
+            outgoing.src_access_mask,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        );
+        assert_eq!(
+            outgoing.dst_stage_mask,
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER
+        );
+        assert_eq!(outgoing.dst_access_mask, vk::AccessFlags::SHADER_READ);
+        assert!(find_dependency(&dependencies, vk::SUBPASS_EXTERNAL, 0).is_some());
+    }
+
+    #[test]
+    fn discarded_attachments_get_no_outgoing_dependency() {
+        let target = color(
+            vk::AttachmentStoreOp::DONT_CARE,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        let depth_target = depth(vk::AttachmentStoreOp::DONT_CARE);
+        let desc = VERenderPassDesc::new(&[&target, &depth_target])
+            .with_subpass(VESubpassDesc::new().with_color(0).with_depth(1));
+        let dependencies = create_dependencies(&desc);
+
+        assert!(find_dependency(&dependencies, 0, vk::SUBPASS_EXTERNAL).is_none());
+        assert!(find_dependency(&dependencies, vk::SUBPASS_EXTERNAL, 0).is_some());
+    }
+
+    #[test]
+    fn resolve_targets_get_a_color_output_dependency() {
+        let mut target = color(
+            vk::AttachmentStoreOp::DONT_CARE,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        target.description.samples = vk::SampleCountFlags::TYPE_4;
+        target.resolve = Some(VEResolveAttachment {
+            image_view: vk::ImageView::null(),
+            description: description(
+                vk::Format::R8G8B8A8_UNORM,
+                vk::AttachmentStoreOp::STORE,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ),
+            image_layout: vk::ImageLayout::UNDEFINED,
+            image: vk::Image::null(),
+        });
+        let desc = VERenderPassDesc::new(&[&target])
+            .with_subpass(VESubpassDesc::new().with_color(0).with_resolve());
+        let dependencies = create_dependencies(&desc);
+
+        let Some(outgoing) = find_dependency(&dependencies, 0, vk::SUBPASS_EXTERNAL) else {
+            panic!("no dependency for the resolve target");
+        };
+        assert_eq!(
+            outgoing.src_stage_mask,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        );
+        assert_eq!(
+            outgoing.src_access_mask,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        );
+        assert_eq!(outgoing.dst_stage_mask, vk::PipelineStageFlags::TRANSFER);
+        assert_eq!(outgoing.dst_access_mask, vk::AccessFlags::TRANSFER_READ);
+
+        let (atta_descs, resolve_indices) = create_attachment_descriptions(&desc);
+        assert_eq!(atta_descs.len(), 2);
+        assert_eq!(resolve_indices, vec![Some(1)]);
+        let references = create_references(&desc, &resolve_indices);
+        assert!(references.is_ok_and(|r| r[0].resolves.len() == 1
+            && r[0].resolves[0].attachment == 1
+            && r[0].resolves[0].layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL));
+    }
+
+    #[test]
+    fn gbuffer_inputs_are_synchronized_by_region() {
+        // The albedo is written by the first subpass and only read back by the last one, the depth
+        // is tested against and read as an input by the second subpass.
+        let albedo = color(
+            vk::AttachmentStoreOp::DONT_CARE,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        let depth_target = depth(vk::AttachmentStoreOp::DONT_CARE);
+        let lighting = color(
+            vk::AttachmentStoreOp::DONT_CARE,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        let output = color(
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        );
+        let desc = VERenderPassDesc::new(&[&albedo, &depth_target, &lighting, &output])
+            .with_subpass(VESubpassDesc::new().with_color(0).with_depth(1))
+            .with_subpass(
+                VESubpassDesc::new()
+                    .with_input(1)
+                    .with_depth(1)
+                    .with_color(2),
+            )
+            .with_subpass(
+                VESubpassDesc::new()
+                    .with_input(0)
+                    .with_input(2)
+                    .with_color(3),
+            );
+
+        let dependencies = create_dependencies(&desc);
+        for (src, dst) in [(0, 1), (0, 2), (1, 2)] {
+            let Some(dependency) = find_dependency(&dependencies, src, dst) else {
+                panic!("no dependency from subpass {src} to {dst}");
+            };
+            assert_eq!(dependency.dependency_flags, vk::DependencyFlags::BY_REGION);
+        }
+        let Some(into_depth_input) = find_dependency(&dependencies, 0, 1) else {
+            panic!("no dependency into the depth input");
+        };
+        assert!(into_depth_input
+            .dst_access_mask
+            .contains(vk::AccessFlags::INPUT_ATTACHMENT_READ));
+        assert!(into_depth_input
+            .dst_stage_mask
+            .contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+        let Some(into_lighting) = find_dependency(&dependencies, 1, 2) else {
+            panic!("no dependency into the lighting subpass");
+        };
+        assert_eq!(
+            into_lighting.src_access_mask,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        );
+        assert_eq!(
+            into_lighting.dst_access_mask,
+            vk::AccessFlags::INPUT_ATTACHMENT_READ
+        );
+
+        let (_, resolve_indices) = create_attachment_descriptions(&desc);
+        let Ok(references) = create_references(&desc, &resolve_indices) else {
+            panic!("references failed");
+        };
+        assert!(references[1]
+            .depth
+            .is_some_and(|r| r.layout == vk::ImageLayout::GENERAL));
+        assert_eq!(references[1].inputs[0].layout, vk::ImageLayout::GENERAL);
+        assert_eq!(
+            references[1].colors[0].layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert!(references[2]
+            .inputs
+            .iter()
+            .all(|r| r.layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL));
+        assert!(references[0].preserve.is_empty());
+        assert_eq!(references[1].preserve, vec![0]);
+        assert!(references[2].preserve.is_empty());
     }
 }
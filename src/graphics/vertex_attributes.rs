@@ -90,10 +90,62 @@ pub(crate) fn get_vertex_attribute_type_byte_size(attrib: &VertexAttribFormat) -
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEVertexInputRate {
+    Vertex,
+    Instance,
+}
+
+fn get_vertex_input_rate(rate: VEVertexInputRate) -> vk::VertexInputRate {
+    match rate {
+        VEVertexInputRate::Vertex => vk::VertexInputRate::VERTEX,
+        VEVertexInputRate::Instance => vk::VertexInputRate::INSTANCE,
+    }
+}
+
+// One vertex buffer binding, attribute locations continue across bindings in the order given.
+#[derive(Clone, Debug)]
+pub struct VEVertexBindingLayout {
+    pub binding: u32,
+    pub input_rate: VEVertexInputRate,
+    pub attributes: Vec<VertexAttribFormat>,
+}
+
+impl VEVertexBindingLayout {
+    pub fn per_vertex(binding: u32, attributes: &[VertexAttribFormat]) -> VEVertexBindingLayout {
+        VEVertexBindingLayout {
+            binding,
+            input_rate: VEVertexInputRate::Vertex,
+            attributes: attributes.to_vec(),
+        }
+    }
+
+    pub fn per_instance(binding: u32, attributes: &[VertexAttribFormat]) -> VEVertexBindingLayout {
+        VEVertexBindingLayout {
+            binding,
+            input_rate: VEVertexInputRate::Instance,
+            attributes: attributes.to_vec(),
+        }
+    }
+
+    pub fn stride(&self) -> u32 {
+        self.attributes
+            .iter()
+            .map(get_vertex_attribute_type_byte_size)
+            .sum()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum VEVertexAttributesError {
     #[error("invalid format")]
     InvalidFormat,
+
+    #[error("vertex binding {0} is declared more than once")]
+    DuplicateBinding(u32),
+
+    #[error("vertex binding {binding} exceeds the device limit of {limit} bindings")]
+    BindingExceedsDeviceLimit { binding: u32, limit: u32 },
 }
 
 fn resolve_vertex_attribute_format(
@@ -147,43 +199,53 @@ fn is_offset(attrib: &VertexAttribFormat) -> bool {
 }
 
 pub fn create_vertex_input_state_descriptions(
-    attribs: &[VertexAttribFormat],
+    bindings: &[VEVertexBindingLayout],
+    max_bindings: u32,
 ) -> Result<
     (
-        vk::VertexInputBindingDescription,
+        Vec<vk::VertexInputBindingDescription>,
         Vec<vk::VertexInputAttributeDescription>,
     ),
     VEVertexAttributesError,
 > {
-    let stride: u32 = attribs
-        .iter()
-        .map(|a| get_vertex_attribute_type_byte_size(a))
-        .sum();
-
-    // if (stride % 4 != 0) {
-    //     panic!("Stride not dividable by 4");
-    // }
-
-    let binding_desc = vk::VertexInputBindingDescription::default()
-        .stride(stride)
-        .binding(0)
-        .input_rate(vk::VertexInputRate::VERTEX);
-
-    let mut offset = 0;
-    let mut location = 0;
+    let mut binding_descriptions: Vec<vk::VertexInputBindingDescription> = vec![];
     let mut descriptions: Vec<vk::VertexInputAttributeDescription> = vec![];
+    let mut location = 0;
+
+    for binding in bindings {
+        if binding.binding >= max_bindings {
+            return Err(VEVertexAttributesError::BindingExceedsDeviceLimit {
+                binding: binding.binding,
+                limit: max_bindings,
+            });
+        }
+        if binding_descriptions
+            .iter()
+            .any(|desc| desc.binding == binding.binding)
+        {
+            return Err(VEVertexAttributesError::DuplicateBinding(binding.binding));
+        }
 
-    for attrib in attribs {
-        if !is_offset(attrib) {
-            let desc = vk::VertexInputAttributeDescription::default()
-                .binding(0)
-                .location(location)
-                .format(resolve_vertex_attribute_format(attrib)?)
-                .offset(offset);
-            descriptions.push(desc);
-            location += 1;
+        binding_descriptions.push(
+            vk::VertexInputBindingDescription::default()
+                .stride(binding.stride())
+                .binding(binding.binding)
+                .input_rate(get_vertex_input_rate(binding.input_rate)),
+        );
+
+        let mut offset = 0;
+        for attrib in &binding.attributes {
+            if !is_offset(attrib) {
+                let desc = vk::VertexInputAttributeDescription::default()
+                    .binding(binding.binding)
+                    .location(location)
+                    .format(resolve_vertex_attribute_format(attrib)?)
+                    .offset(offset);
+                descriptions.push(desc);
+                location += 1;
+            }
+            offset += get_vertex_attribute_type_byte_size(attrib);
         }
-        offset += get_vertex_attribute_type_byte_size(attrib);
     }
-    Ok((binding_desc, descriptions))
+    Ok((binding_descriptions, descriptions))
 }
@@ -6,7 +6,6 @@ use crate::core::main_device_queue::VEMainDeviceQueue;
 use crate::core::memory_properties::VEMemoryProperties;
 use crate::graphics::vertex_attributes::{get_vertex_attribute_type_byte_size, VertexAttribFormat};
 use crate::memory::memory_manager::VEMemoryManager;
-use ash::vk;
 use std::fs::File;
 use std::io;
 use std::io::Read;
@@ -27,6 +26,9 @@ pub enum VEVertexBufferError {
     #[error("vertex size mismatch in the file")]
     VertexSizeMismatch,
 
+    #[error("{0} bytes of vertex data do not fit in a u32 vertex count")]
+    DataTooLarge(u64),
+
     #[error("buffer error")]
     BufferError(#[from] VEBufferError),
 }
@@ -59,13 +61,8 @@ impl VEVertexBuffer {
             .map(|a| get_vertex_attribute_type_byte_size(a))
             .sum();
 
-        let input_size = data.len() as u32;
-
-        if input_size % vertex_size_bytes != 0 {
-            return Err(VEVertexBufferError::VertexSizeMismatch);
-        }
-
-        let vertex_count = input_size / vertex_size_bytes;
+        let input_size = data.len() as u64;
+        let vertex_count = Self::get_vertex_count(input_size, vertex_size_bytes)?;
 
         let mut staging_buffer = VEBuffer::new(
             device.clone(),
@@ -73,7 +70,7 @@ impl VEVertexBuffer {
             command_pool.clone(),
             memory_manager.clone(),
             &[VEBufferUsage::TransferSource],
-            input_size as vk::DeviceSize,
+            input_size,
             Some(VEMemoryProperties::HostCoherent),
         )?;
 
@@ -83,13 +80,13 @@ impl VEVertexBuffer {
             command_pool.clone(),
             memory_manager.clone(),
             &[VEBufferUsage::Vertex, VEBufferUsage::TransferDestination],
-            input_size as vk::DeviceSize,
+            input_size,
             Some(VEMemoryProperties::DeviceLocal),
         )?;
 
         unsafe {
             let mem = staging_buffer.map()? as *mut u8;
-            let slice = std::slice::from_raw_parts_mut(mem, input_size as usize);
+            let slice = std::slice::from_raw_parts_mut(mem, data.len());
             slice.copy_from_slice(&data);
             // staging_buffer.unmap()?;
         }
@@ -116,13 +113,10 @@ impl VEVertexBuffer {
         let metadata = file
             .metadata()
             .map_err(VEVertexBufferError::GettingFileMetadataFailed)?;
-        let file_size = metadata.len() as u32;
-
-        if file_size % vertex_size_bytes != 0 {
-            return Err(VEVertexBufferError::VertexSizeMismatch);
-        }
-
-        let vertex_count = file_size / vertex_size_bytes;
+        let file_size = metadata.len();
+        let vertex_count = Self::get_vertex_count(file_size, vertex_size_bytes)?;
+        let file_len =
+            usize::try_from(file_size).map_err(|_| VEVertexBufferError::DataTooLarge(file_size))?;
 
         let mut staging_buffer = VEBuffer::new(
             device.clone(),
@@ -130,7 +124,7 @@ impl VEVertexBuffer {
             command_pool.clone(),
             memory_manager.clone(),
             &[VEBufferUsage::TransferSource],
-            file_size as vk::DeviceSize,
+            file_size,
             Some(VEMemoryProperties::HostCoherent),
         )?;
 
@@ -140,13 +134,13 @@ impl VEVertexBuffer {
             command_pool.clone(),
             memory_manager.clone(),
             &[VEBufferUsage::Vertex, VEBufferUsage::TransferDestination],
-            file_size as vk::DeviceSize,
+            file_size,
             Some(VEMemoryProperties::DeviceLocal),
         )?;
 
         unsafe {
             let mem = staging_buffer.map()? as *mut u8;
-            let mut slice = std::slice::from_raw_parts_mut(mem, file_size as usize);
+            let mut slice = std::slice::from_raw_parts_mut(mem, file_len);
             file.read_exact(&mut slice)
                 .map_err(VEVertexBufferError::ReadingFileFailed)?;
             // staging_buffer.unmap()?;
@@ -157,7 +151,15 @@ impl VEVertexBuffer {
         Ok(VEVertexBuffer::new(device, final_buffer, vertex_count))
     }
 
-    pub fn draw_instanced(&self, command_buffer: &VECommandBuffer, instances: u32) {
+    fn get_vertex_count(size: u64, vertex_size: u32) -> Result<u32, VEVertexBufferError> {
+        if vertex_size == 0 || !size.is_multiple_of(vertex_size as u64) {
+            return Err(VEVertexBufferError::VertexSizeMismatch);
+        }
+        u32::try_from(size / vertex_size as u64)
+            .map_err(|_| VEVertexBufferError::DataTooLarge(size))
+    }
+
+    pub fn bind(&self, command_buffer: &VECommandBuffer) {
         unsafe {
             self.device.device.cmd_bind_vertex_buffers(
                 command_buffer.handle,
@@ -165,6 +167,12 @@ impl VEVertexBuffer {
                 &[self.buffer.buffer],
                 &[0],
             );
+        }
+    }
+
+    pub fn draw_instanced(&self, command_buffer: &VECommandBuffer, instances: u32) {
+        self.bind(command_buffer);
+        unsafe {
             self.device
                 .device
                 .cmd_draw(command_buffer.handle, self.vertex_count, instances, 0, 0);
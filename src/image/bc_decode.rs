@@ -0,0 +1,690 @@
+use crate::image::image_format::{get_format_info, VEImageFormat};
+
+// CPU decoders for the BC formats, used when the device cannot sample them. BC6H is not
+// covered, devices without BC support get an error for it.
+
+type VEBlockTexels = [[u8; 4]; 16];
+
+pub(crate) fn get_decoded_format(format: VEImageFormat) -> Option<VEImageFormat> {
+    match format {
+        VEImageFormat::BC1unorm
+        | VEImageFormat::BC2unorm
+        | VEImageFormat::BC3unorm
+        | VEImageFormat::BC7unorm => Some(VEImageFormat::RGBA8unorm),
+        VEImageFormat::BC1srgb
+        | VEImageFormat::BC2srgb
+        | VEImageFormat::BC3srgb
+        | VEImageFormat::BC7srgb => Some(VEImageFormat::RGBA8srgb),
+        VEImageFormat::BC4unorm => Some(VEImageFormat::R8unorm),
+        VEImageFormat::BC4inorm => Some(VEImageFormat::R8inorm),
+        VEImageFormat::BC5unorm => Some(VEImageFormat::RG8unorm),
+        VEImageFormat::BC5inorm => Some(VEImageFormat::RG8inorm),
+        _ => None,
+    }
+}
+
+// Decodes `slices` images of `width` x `height` stored back to back, into the format
+// `get_decoded_format` returns for `format`.
+pub(crate) fn decode_bc(
+    format: VEImageFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    slices: u32,
+) -> Option<Vec<u8>> {
+    let channels = match get_decoded_format(format)? {
+        VEImageFormat::R8unorm | VEImageFormat::R8inorm => 1,
+        VEImageFormat::RG8unorm | VEImageFormat::RG8inorm => 2,
+        _ => 4,
+    };
+    let block_size = get_format_info(format).block_size as usize;
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    let slice_size = blocks_x * blocks_y * block_size;
+    if data.len() < slice_size * slices as usize {
+        return None;
+    }
+
+    let mut result = vec![0u8; width * height * channels * slices as usize];
+    for (slice, (source, target)) in data
+        .chunks_exact(slice_size)
+        .zip(result.chunks_exact_mut(width * height * channels))
+        .enumerate()
+    {
+        if slice >= slices as usize {
+            break;
+        }
+        for (index, block) in source.chunks_exact(block_size).enumerate() {
+            let texels = decode_block(format, block);
+            let (block_x, block_y) = (index % blocks_x * 4, index / blocks_x * 4);
+            for (texel, rgba) in texels.iter().enumerate() {
+                let (x, y) = (block_x + texel % 4, block_y + texel / 4);
+                if x < width && y < height {
+                    let offset = (y * width + x) * channels;
+                    target[offset..offset + channels].copy_from_slice(&rgba[..channels]);
+                }
+            }
+        }
+    }
+    Some(result)
+}
+
+fn decode_block(format: VEImageFormat, block: &[u8]) -> VEBlockTexels {
+    match format {
+        VEImageFormat::BC1unorm | VEImageFormat::BC1srgb => decode_color_block(block, true),
+        VEImageFormat::BC2unorm | VEImageFormat::BC2srgb => {
+            let mut texels = decode_color_block(&block[8..16], false);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let nibble = (block[i / 2] >> (4 * (i % 2))) & 0xf;
+                texel[3] = nibble * 17;
+            }
+            texels
+        }
+        VEImageFormat::BC3unorm | VEImageFormat::BC3srgb => {
+            let mut texels = decode_color_block(&block[8..16], false);
+            let alpha = decode_channel_block(&block[0..8], false);
+            for (texel, alpha) in texels.iter_mut().zip(alpha) {
+                texel[3] = alpha;
+            }
+            texels
+        }
+        VEImageFormat::BC4unorm | VEImageFormat::BC4inorm => {
+            let red = decode_channel_block(block, format == VEImageFormat::BC4inorm);
+            red.map(|r| [r, 0, 0, 255])
+        }
+        VEImageFormat::BC5unorm | VEImageFormat::BC5inorm => {
+            let signed = format == VEImageFormat::BC5inorm;
+            let red = decode_channel_block(&block[0..8], signed);
+            let green = decode_channel_block(&block[8..16], signed);
+            let mut texels = [[0, 0, 0, 255]; 16];
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[0] = red[i];
+                texel[1] = green[i];
+            }
+            texels
+        }
+        VEImageFormat::BC7unorm | VEImageFormat::BC7srgb => decode_bc7_block(block),
+        _ => [[0; 4]; 16],
+    }
+}
+
+fn expand_565(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+        255,
+    ]
+}
+
+fn mix(a: [u8; 4], b: [u8; 4], weight_a: u32, weight_b: u32) -> [u8; 4] {
+    let total = weight_a + weight_b;
+    let mut result = [0; 4];
+    for c in 0..4 {
+        result[c] = ((a[c] as u32 * weight_a + b[c] as u32 * weight_b + total / 2) / total) as u8;
+    }
+    result
+}
+
+// The color half of BC1 to BC3, only BC1 has the three color mode with transparent black.
+fn decode_color_block(block: &[u8], bc1: bool) -> VEBlockTexels {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+    let palette = if c0 > c1 || !bc1 {
+        [e0, e1, mix(e0, e1, 2, 1), mix(e0, e1, 1, 2)]
+    } else {
+        [e0, e1, mix(e0, e1, 1, 1), [0, 0, 0, 0]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+    texels
+}
+
+// BC3 alpha and BC4/BC5 channels. Signed values are returned as their two's complement bytes.
+fn decode_channel_block(block: &[u8], signed: bool) -> [u8; 16] {
+    let (e0, e1) = if signed {
+        (
+            (block[0] as i8).max(-127) as f32,
+            (block[1] as i8).max(-127) as f32,
+        )
+    } else {
+        (block[0] as f32, block[1] as f32)
+    };
+    let (min, max) = if signed {
+        (-127.0, 127.0)
+    } else {
+        (0.0, 255.0)
+    };
+    let mut palette = [e0, e1, 0.0, 0.0, 0.0, 0.0, min, max];
+    if e0 > e1 {
+        for (i, value) in palette.iter_mut().enumerate().skip(2) {
+            *value = ((8 - i) as f32 * e0 + (i - 1) as f32 * e1) / 7.0;
+        }
+    } else {
+        for (i, value) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *value = ((6 - i) as f32 * e0 + (i - 1) as f32 * e1) / 5.0;
+        }
+    }
+
+    let mut bits = 0u64;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        bits |= (*byte as u64) << (8 * i);
+    }
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        let level = palette[((bits >> (3 * i)) & 7) as usize].round();
+        *value = if signed {
+            level as i8 as u8
+        } else {
+            level as u8
+        };
+    }
+    values
+}
+
+struct VEBc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+}
+
+static BC7_MODES: [VEBc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+#[allow(clippy::too_many_arguments)]
+const fn bc7_mode(
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+) -> VEBc7Mode {
+    VEBc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        index2_bits,
+    }
+}
+
+// Bit i is the subset of texel i.
+static BC7_PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// Two bits per texel, texel 0 in the lowest bits.
+static BC7_PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+static BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+static BC7_ANCHORS_3_1: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+static BC7_ANCHORS_3_2: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+static BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+static BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+static BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn get_bc7_weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+struct VEBitReader {
+    bits: u128,
+}
+
+impl VEBitReader {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits & ((1u128 << count) - 1)) as u32;
+        self.bits >>= count;
+        value
+    }
+}
+
+fn decode_bc7_block(block: &[u8]) -> VEBlockTexels {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&block[..16]);
+    let mode_index = bytes[0].trailing_zeros();
+    let Some(mode) = BC7_MODES.get(mode_index as usize) else {
+        // reserved mode, decodes to transparent black
+        return [[0; 4]; 16];
+    };
+    let mut reader = VEBitReader {
+        bits: u128::from_le_bytes(bytes),
+    };
+    reader.read(mode_index + 1);
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[c] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = reader.read(mode.alpha_bits);
+    }
+
+    let mut pbits = [0u32; 6];
+    if mode.endpoint_pbits {
+        for pbit in pbits.iter_mut().take(endpoint_count) {
+            *pbit = reader.read(1);
+        }
+    }
+    if mode.shared_pbits {
+        for subset in 0..mode.subsets {
+            let pbit = reader.read(1);
+            pbits[subset * 2] = pbit;
+            pbits[subset * 2 + 1] = pbit;
+        }
+    }
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+
+    let mut colors = [[0u8; 4]; 6];
+    for (e, color) in colors.iter_mut().enumerate().take(endpoint_count) {
+        for (c, channel) in color.iter_mut().enumerate() {
+            let bits = if c < 3 {
+                mode.color_bits
+            } else {
+                mode.alpha_bits
+            };
+            if bits == 0 {
+                *channel = 255;
+                continue;
+            }
+            let (mut value, mut bits) = (endpoints[e][c], bits);
+            if has_pbits {
+                value = (value << 1) | pbits[e];
+                bits += 1;
+            }
+            value <<= 8 - bits;
+            *channel = (value | (value >> bits)) as u8;
+        }
+    }
+
+    let subset_of = |texel: usize| -> usize {
+        match mode.subsets {
+            2 => ((BC7_PARTITIONS_2[partition] >> texel) & 1) as usize,
+            3 => ((BC7_PARTITIONS_3[partition] >> (2 * texel)) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |texel: usize| -> bool {
+        texel == 0
+            || match mode.subsets {
+                2 => texel == BC7_ANCHORS_2[partition] as usize,
+                3 => {
+                    texel == BC7_ANCHORS_3_1[partition] as usize
+                        || texel == BC7_ANCHORS_3_2[partition] as usize
+                }
+                _ => false,
+            }
+    };
+
+    let mut indices = [0u32; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        *index = reader.read(mode.index_bits - is_anchor(texel) as u32);
+    }
+    let mut indices2 = [0u32; 16];
+    if mode.index2_bits > 0 {
+        for (texel, index) in indices2.iter_mut().enumerate() {
+            *index = reader.read(mode.index2_bits - (texel == 0) as u32);
+        }
+    }
+
+    let mut texels = [[0u8; 4]; 16];
+    for (texel, rgba) in texels.iter_mut().enumerate() {
+        let subset = subset_of(texel);
+        let (e0, e1) = (colors[subset * 2], colors[subset * 2 + 1]);
+        let (color_weight, alpha_weight) = if mode.index2_bits == 0 {
+            let weight = get_bc7_weight(mode.index_bits, indices[texel]);
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                get_bc7_weight(mode.index_bits, indices[texel]),
+                get_bc7_weight(mode.index2_bits, indices2[texel]),
+            )
+        } else {
+            (
+                get_bc7_weight(mode.index2_bits, indices2[texel]),
+                get_bc7_weight(mode.index_bits, indices[texel]),
+            )
+        };
+        for c in 0..4 {
+            let weight = if c < 3 { color_weight } else { alpha_weight };
+            rgba[c] = (((64 - weight) * e0[c] as u32 + weight * e1[c] as u32 + 32) >> 6) as u8;
+        }
+        match rotation {
+            1 => rgba.swap(0, 3),
+            2 => rgba.swap(1, 3),
+            3 => rgba.swap(2, 3),
+            _ => (),
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds blocks least significant bit first, the order BC7 fields are stored in.
+    struct BitWriter {
+        bits: u128,
+        position: u32,
+    }
+
+    impl BitWriter {
+        fn new() -> Self {
+            BitWriter {
+                bits: 0,
+                position: 0,
+            }
+        }
+
+        fn write(&mut self, value: u32, count: u32) -> &mut Self {
+            self.bits |= u128::from(value) << self.position;
+            self.position += count;
+            self
+        }
+
+        fn bytes(&self) -> [u8; 16] {
+            assert_eq!(self.position, 128);
+            self.bits.to_le_bytes()
+        }
+    }
+
+    fn channel_block(e0: u8, e1: u8, indices: [u64; 16]) -> [u8; 8] {
+        let mut bits = 0u64;
+        for (i, index) in indices.iter().enumerate() {
+            bits |= index << (3 * i);
+        }
+        let mut block = [0; 8];
+        block[0] = e0;
+        block[1] = e1;
+        block[2..].copy_from_slice(&bits.to_le_bytes()[..6]);
+        block
+    }
+
+    fn texel(data: &[u8], index: usize) -> [u8; 4] {
+        [
+            data[index * 4],
+            data[index * 4 + 1],
+            data[index * 4 + 2],
+            data[index * 4 + 3],
+        ]
+    }
+
+    static PALETTE_INDICES: [u64; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 0, 1, 2, 3, 4, 5, 6, 7];
+
+    #[test]
+    fn bc1_three_color_blocks_have_transparent_black() {
+        // blue <= red selects the three color mode, texel i uses index i % 4
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4];
+        let Some(data) = decode_bc(VEImageFormat::BC1unorm, &block, 4, 4, 1) else {
+            panic!("block was not decoded");
+        };
+        assert_eq!(data.len(), 64);
+        assert_eq!(texel(&data, 0), [0, 0, 255, 255]);
+        assert_eq!(texel(&data, 1), [255, 0, 0, 255]);
+        assert_eq!(texel(&data, 2), [128, 0, 128, 255]);
+        assert_eq!(texel(&data, 3), [0, 0, 0, 0]);
+        assert_eq!(texel(&data, 15), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc1_four_color_blocks_interpolate_thirds() {
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+        let Some(data) = decode_bc(VEImageFormat::BC1unorm, &block, 4, 4, 1) else {
+            panic!("block was not decoded");
+        };
+        assert_eq!(texel(&data, 0), [255, 0, 0, 255]);
+        assert_eq!(texel(&data, 1), [0, 0, 255, 255]);
+        assert_eq!(texel(&data, 2), [170, 0, 85, 255]);
+        assert_eq!(texel(&data, 3), [85, 0, 170, 255]);
+    }
+
+    #[test]
+    fn bc3_alpha_uses_eight_and_six_value_palettes() {
+        // white color half, every texel uses color index 0
+        let color = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+        let eight = [255, 0, 219, 182, 146, 109, 73, 36];
+        let six = [0, 255, 51, 102, 153, 204, 0, 255];
+        for (e0, e1, palette) in [(255, 0, eight), (0, 255, six)] {
+            let mut block = channel_block(e0, e1, PALETTE_INDICES).to_vec();
+            block.extend_from_slice(&color);
+            let Some(data) = decode_bc(VEImageFormat::BC3unorm, &block, 4, 4, 1) else {
+                panic!("block was not decoded");
+            };
+            for i in 0..16 {
+                assert_eq!(texel(&data, i), [255, 255, 255, palette[i % 8]]);
+            }
+        }
+    }
+
+    #[test]
+    fn bc4_uses_eight_and_six_value_palettes() {
+        let eight = [255, 0, 219, 182, 146, 109, 73, 36];
+        let six = [0, 255, 51, 102, 153, 204, 0, 255];
+        for (e0, e1, palette) in [(255, 0, eight), (0, 255, six)] {
+            let block = channel_block(e0, e1, PALETTE_INDICES);
+            let Some(data) = decode_bc(VEImageFormat::BC4unorm, &block, 4, 4, 1) else {
+                panic!("block was not decoded");
+            };
+            assert_eq!(data.len(), 16);
+            for (i, value) in data.iter().enumerate() {
+                assert_eq!(*value, palette[i % 8]);
+            }
+        }
+    }
+
+    #[test]
+    fn signed_bc4_and_bc5_decode_to_twos_complement() {
+        // 127 and -127, the eight value palette steps by 127 * 2 / 7
+        let palette: [i8; 8] = [127, -127, 91, 54, 18, -18, -54, -91];
+        let red = channel_block(0x7f, 0x81, PALETTE_INDICES);
+        let Some(data) = decode_bc(VEImageFormat::BC4inorm, &red, 4, 4, 1) else {
+            panic!("block was not decoded");
+        };
+        for (i, value) in data.iter().enumerate() {
+            assert_eq!(*value as i8, palette[i % 8]);
+        }
+
+        // -128 is clamped to -127
+        let green = channel_block(0x80, 0x80, [0; 16]);
+        let mut block = red.to_vec();
+        block.extend_from_slice(&green);
+        let Some(data) = decode_bc(VEImageFormat::BC5inorm, &block, 4, 4, 1) else {
+            panic!("block was not decoded");
+        };
+        assert_eq!(data.len(), 32);
+        for i in 0..16 {
+            assert_eq!(data[i * 2] as i8, palette[i % 8]);
+            assert_eq!(data[i * 2 + 1] as i8, -127);
+        }
+    }
+
+    #[test]
+    fn bc7_mode_1_reads_partitions_shared_pbits_and_anchors() {
+        let mut writer = BitWriter::new();
+        // mode 1, partition 0 puts columns 2 and 3 into subset 1 with its anchor at texel 15
+        writer.write(0b10, 2).write(0, 6);
+        // red, green and blue of subset 0 (red to green) and subset 1 (blue to red)
+        for value in [63, 0, 0, 63, 0, 63, 0, 0, 0, 0, 63, 0] {
+            writer.write(value, 6);
+        }
+        writer.write(0, 1).write(1, 1);
+        for texel in 0..16 {
+            let index = match texel {
+                1 => 7,
+                15 => 3,
+                _ => 0,
+            };
+            let anchor = texel == 0 || texel == 15;
+            writer.write(index, 3 - anchor as u32);
+        }
+        let texels = decode_bc7_block(&writer.bytes());
+
+        assert_eq!(texels[0], [253, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 253, 0, 255]);
+        assert_eq!(texels[2], [2, 2, 255, 255]);
+        assert_eq!(texels[4], [253, 0, 0, 255]);
+        assert_eq!(texels[15], [109, 2, 148, 255]);
+    }
+
+    fn bc7_mode_4_block(rotation: u32, index_selection: u32) -> [u8; 16] {
+        let mut writer = BitWriter::new();
+        writer.write(0b10000, 5).write(rotation, 2);
+        writer.write(index_selection, 1);
+        // red 0 to 31, green and blue 0, alpha 0 to 63
+        for value in [0, 31, 0, 0, 0, 0] {
+            writer.write(value, 5);
+        }
+        writer.write(0, 6).write(63, 6);
+        // primary indices all 1, secondary indices all 3
+        writer.write(1, 1);
+        for _ in 1..16 {
+            writer.write(1, 2);
+        }
+        writer.write(3, 2);
+        for _ in 1..16 {
+            writer.write(3, 3);
+        }
+        writer.bytes()
+    }
+
+    #[test]
+    fn bc7_mode_4_applies_index_selection_and_rotation() {
+        // color from the two bit indices (weight 21), alpha from the three bit ones (weight 27)
+        let texels = decode_bc7_block(&bc7_mode_4_block(0, 0));
+        assert!(texels.iter().all(|texel| *texel == [84, 0, 0, 108]));
+
+        // swapped index sets, then red and alpha swapped by the rotation
+        let texels = decode_bc7_block(&bc7_mode_4_block(1, 1));
+        assert!(texels.iter().all(|texel| *texel == [84, 0, 0, 108]));
+
+        let texels = decode_bc7_block(&bc7_mode_4_block(0, 1));
+        assert!(texels.iter().all(|texel| *texel == [108, 0, 0, 84]));
+
+        let texels = decode_bc7_block(&bc7_mode_4_block(3, 0));
+        assert!(texels.iter().all(|texel| *texel == [84, 0, 108, 0]));
+    }
+
+    #[test]
+    fn bc7_mode_6_reads_rgba_endpoints_with_pbits() {
+        let mut writer = BitWriter::new();
+        writer.write(0b1000000, 7);
+        for value in [0x10, 0x7f, 0x20, 0, 0x30, 0x40, 0x7f, 0x7f] {
+            writer.write(value, 7);
+        }
+        writer.write(0, 1).write(1, 1);
+        // texel i uses index i, texel 0 only has three bits
+        writer.write(0, 3);
+        for index in 1..16 {
+            writer.write(index, 4);
+        }
+        let texels = decode_bc7_block(&writer.bytes());
+
+        assert_eq!(texels[0], [32, 64, 96, 254]);
+        assert_eq!(texels[15], [255, 1, 129, 255]);
+        // weight 34 of 64
+        assert_eq!(texels[8], [150, 31, 114, 255]);
+    }
+
+    #[test]
+    fn bc7_reserved_mode_is_transparent_black() {
+        assert_eq!(decode_bc7_block(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn partial_blocks_are_clipped_at_the_edges() {
+        // 5x3 texels need two blocks, red on the left and blue on the right
+        let block = |color: [u8; 2]| [color[0], color[1], color[0], color[1], 0, 0, 0, 0];
+        let mut data = block([0x00, 0xf8]).to_vec();
+        data.extend_from_slice(&block([0x1f, 0x00]));
+        let Some(decoded) = decode_bc(VEImageFormat::BC1unorm, &data, 5, 3, 1) else {
+            panic!("blocks were not decoded");
+        };
+        assert_eq!(decoded.len(), 5 * 3 * 4);
+        for y in 0..3 {
+            for x in 0..5 {
+                let expected = if x < 4 {
+                    [255, 0, 0, 255]
+                } else {
+                    [0, 0, 255, 255]
+                };
+                assert_eq!(texel(&decoded, y * 5 + x), expected);
+            }
+        }
+
+        assert!(decode_bc(VEImageFormat::BC1unorm, &data[..8], 5, 3, 1).is_none());
+    }
+}
@@ -1,7 +1,15 @@
 use crate::buffer::buffer::VEBufferError;
+use crate::compute::compute_stage::VEComputeStageError;
 use crate::core::command_buffer::{VECommandBuffer, VECommandBufferError};
+use crate::core::descriptor_set::VEDescriptorSetError;
+use crate::core::descriptor_set_layout::VEDescriptorSetLayoutError;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::{VEMainDeviceQueue, VEMainDeviceQueueError};
+use crate::core::resource_state::{VEResourceState, VESubresourceRange};
+use crate::core::shader_module::VEShaderModuleError;
+use crate::image::image_format::{get_format_info, VEImageFormat};
+use crate::image::sampler::VESamplerError;
+use crate::image::texture_container::VETextureContainerError;
 use crate::image::transition_image_layout::transition_image_layout;
 use crate::memory::memory_chunk::{VEMemoryChunkError, VESingleAllocation};
 use crate::memory::memory_manager::VEMemoryManagerError;
@@ -13,6 +21,10 @@ use std::io;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
+#[path = "./image_from_container.rs"]
+mod image_from_container;
+#[path = "./image_from_cube.rs"]
+mod image_from_cube;
 #[path = "./image_from_data.rs"]
 mod image_from_data;
 #[path = "./image_from_file.rs"]
@@ -21,6 +33,8 @@ mod image_from_file;
 mod image_from_full;
 #[path = "./image_from_swapchain.rs"]
 mod image_from_swapchain;
+#[path = "./image_mipmaps.rs"]
+mod image_mipmaps;
 
 #[derive(Error, Debug)]
 pub enum VEImageError {
@@ -59,18 +73,207 @@ pub enum VEImageError {
 
     #[error("queue locking failed")]
     QueueLockingFailed,
+
+    #[error("sample count {0:?} is not supported for this image")]
+    UnsupportedSampleCount(vk::SampleCountFlags),
+
+    #[error("invalid mip level count {0}")]
+    InvalidMipLevelCount(u32),
+
+    #[error("mipmaps cannot be generated for {0:?}")]
+    MipmapGenerationNotSupported(vk::Format),
+
+    #[error("shader module error")]
+    ShaderModuleError(#[from] VEShaderModuleError),
+
+    #[error("descriptor set layout error")]
+    DescriptorSetLayoutError(#[from] VEDescriptorSetLayoutError),
+
+    #[error("descriptor set error")]
+    DescriptorSetError(#[from] VEDescriptorSetError),
+
+    #[error("compute stage error")]
+    ComputeStageError(#[from] VEComputeStageError),
+
+    #[error("sampler error")]
+    SamplerError(#[from] VESamplerError),
+
+    #[error("invalid layer count {0}")]
+    InvalidLayerCount(u32),
+
+    #[error("layer {0} is out of bounds")]
+    LayerOutOfBounds(u32),
+
+    #[error("cube faces must be square, got {0}x{1}")]
+    CubeFaceNotSquare(u32, u32),
+
+    #[error("all cube faces must have the same size")]
+    CubeFaceSizeMismatch,
+
+    #[error("expected {expected} bytes of image data, got {actual}")]
+    DataSizeMismatch { expected: usize, actual: usize },
+
+    #[error("texture container error")]
+    TextureContainerError(#[from] VETextureContainerError),
+
+    #[error("format {0:?} cannot be sampled on this device")]
+    FormatNotSupported(vk::Format),
+
+    #[error("files cannot be loaded as {0:?}")]
+    UnsupportedLoadFormat(VEImageFormat),
+
+    #[error("format {0:?} does not support the usages {1:?}")]
+    UnsupportedUsages(VEImageFormat, Vec<VEImageUsage>),
+
+    #[error("image with aspects {0:?} has no {1:?} aspect")]
+    ViewAspectNotInImage(vk::ImageAspectFlags, VEImageViewAspect),
 }
 
-#[derive(Debug, Clone)]
+// By default the image keeps the channels and bit depth of the file, with 8 bit color and gray
+// read as linear unless `srgb` is set. Gray images are sampled as gray through the view swizzle.
+// KTX2 and DDS files always keep their own format.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VELoadOptions {
+    // converts the pixels to this format instead, `srgb` is ignored then
+    pub format: Option<VEImageFormat>,
+    pub srgb: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum VEImageUsage {
     ColorAttachment,
     DepthAttachment,
+    InputAttachment,
     Sampled,
     Storage,
     TransferDestination,
     TransferSource,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VESampleCount {
+    Count1,
+    Count2,
+    Count4,
+    Count8,
+    Count16,
+    Count32,
+    Count64,
+}
+
+pub(crate) fn get_sample_count_flags(count: VESampleCount) -> vk::SampleCountFlags {
+    match count {
+        VESampleCount::Count1 => vk::SampleCountFlags::TYPE_1,
+        VESampleCount::Count2 => vk::SampleCountFlags::TYPE_2,
+        VESampleCount::Count4 => vk::SampleCountFlags::TYPE_4,
+        VESampleCount::Count8 => vk::SampleCountFlags::TYPE_8,
+        VESampleCount::Count16 => vk::SampleCountFlags::TYPE_16,
+        VESampleCount::Count32 => vk::SampleCountFlags::TYPE_32,
+        VESampleCount::Count64 => vk::SampleCountFlags::TYPE_64,
+    }
+}
+
+// Format features an image in a format with `aspect` needs for `usage`.
+pub(crate) fn get_usage_format_features(
+    usage: &VEImageUsage,
+    aspect: vk::ImageAspectFlags,
+) -> vk::FormatFeatureFlags {
+    match usage {
+        VEImageUsage::ColorAttachment => vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+        VEImageUsage::DepthAttachment => vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        VEImageUsage::InputAttachment if aspect == vk::ImageAspectFlags::COLOR => {
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT
+        }
+        VEImageUsage::InputAttachment => vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        VEImageUsage::Sampled => vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        VEImageUsage::Storage => vk::FormatFeatureFlags::STORAGE_IMAGE,
+        VEImageUsage::TransferDestination => vk::FormatFeatureFlags::TRANSFER_DST,
+        VEImageUsage::TransferSource => vk::FormatFeatureFlags::TRANSFER_SRC,
+    }
+}
+
+// Usages optimally tiled images in `format` cannot have on `device`, without duplicates.
+pub(crate) fn get_unsupported_usages(
+    device: &VEDevice,
+    format: VEImageFormat,
+    usages: &[VEImageUsage],
+) -> Vec<VEImageUsage> {
+    let info = get_format_info(format);
+    let features = info.features(device);
+    let mut unsupported = vec![];
+    for usage in usages {
+        if !features.contains(get_usage_format_features(usage, info.aspect_flags()))
+            && !unsupported.contains(usage)
+        {
+            unsupported.push(usage.clone());
+        }
+    }
+    unsupported
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VEMipLevels {
+    Count(u32),
+    // every level down to 1x1
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VEImageLayers {
+    Array(u32),
+    // six layers per cube, faces in +X, -X, +Y, -Y, +Z, -Z order
+    Cube(u32),
+}
+
+// A 2D image with one sample, mip level and layer unless changed by the `with_*` methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VEImageDesc {
+    pub width: u32,
+    pub height: u32,
+    // more than 1 makes a 3D image
+    pub depth: u32,
+    pub format: VEImageFormat,
+    pub usages: Vec<VEImageUsage>,
+    pub samples: VESampleCount,
+    pub mip_levels: VEMipLevels,
+    pub layers: VEImageLayers,
+}
+
+impl VEImageDesc {
+    pub fn new(width: u32, height: u32, format: VEImageFormat, usages: &[VEImageUsage]) -> Self {
+        VEImageDesc {
+            width,
+            height,
+            depth: 1,
+            format,
+            usages: usages.to_vec(),
+            samples: VESampleCount::Count1,
+            mip_levels: VEMipLevels::Count(1),
+            layers: VEImageLayers::Array(1),
+        }
+    }
+
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: VESampleCount) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_mip_levels(mut self, mip_levels: VEMipLevels) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: VEImageLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum VEImageViewType {
     View1D,
@@ -82,6 +285,37 @@ pub enum VEImageViewType {
     ViewCubeArray,
 }
 
+// Sampled and input attachment views of combined depth/stencil formats may only see one of the
+// two aspects, attachment views need both.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum VEImageViewAspect {
+    // color, or depth for formats with a depth aspect
+    Default,
+    Depth,
+    Stencil,
+    // every aspect of the format, for attachment views
+    All,
+}
+
+pub(crate) fn get_view_aspect_flags(
+    image_aspect: vk::ImageAspectFlags,
+    aspect: VEImageViewAspect,
+) -> Result<vk::ImageAspectFlags, VEImageError> {
+    let flags = match aspect {
+        VEImageViewAspect::All => image_aspect,
+        VEImageViewAspect::Default if image_aspect.contains(vk::ImageAspectFlags::DEPTH) => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        VEImageViewAspect::Default => image_aspect,
+        VEImageViewAspect::Depth => image_aspect & vk::ImageAspectFlags::DEPTH,
+        VEImageViewAspect::Stencil => image_aspect & vk::ImageAspectFlags::STENCIL,
+    };
+    if flags.is_empty() {
+        return Err(VEImageError::ViewAspectNotInImage(image_aspect, aspect));
+    }
+    Ok(flags)
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub struct VEImageViewCreateInfo {
     pub typ: VEImageViewType,
@@ -89,6 +323,7 @@ pub struct VEImageViewCreateInfo {
     pub mipmap_count: u32,
     pub base_layer: u32,
     pub layer_count: u32,
+    pub aspect: VEImageViewAspect,
 }
 
 impl VEImageViewCreateInfo {
@@ -99,6 +334,53 @@ impl VEImageViewCreateInfo {
             layer_count: 1,
             base_mipmap: 0,
             mipmap_count: 1,
+            aspect: VEImageViewAspect::Default,
+        }
+    }
+
+    // 2D view over every mip level, for sampling mipmapped images.
+    pub fn mipmapped_2d() -> VEImageViewCreateInfo {
+        VEImageViewCreateInfo {
+            typ: VEImageViewType::View2D,
+            base_layer: 0,
+            layer_count: 1,
+            base_mipmap: 0,
+            mipmap_count: vk::REMAINING_MIP_LEVELS,
+            aspect: VEImageViewAspect::Default,
+        }
+    }
+
+    // Single layer of an array or cube image, for rendering into it.
+    pub fn layer_2d(layer: u32) -> VEImageViewCreateInfo {
+        VEImageViewCreateInfo {
+            typ: VEImageViewType::View2D,
+            base_layer: layer,
+            layer_count: 1,
+            base_mipmap: 0,
+            mipmap_count: 1,
+            aspect: VEImageViewAspect::Default,
+        }
+    }
+
+    pub fn array_2d(layer_count: u32) -> VEImageViewCreateInfo {
+        VEImageViewCreateInfo {
+            typ: VEImageViewType::View2DArray,
+            base_layer: 0,
+            layer_count,
+            base_mipmap: 0,
+            mipmap_count: vk::REMAINING_MIP_LEVELS,
+            aspect: VEImageViewAspect::Default,
+        }
+    }
+
+    pub fn cube() -> VEImageViewCreateInfo {
+        VEImageViewCreateInfo {
+            typ: VEImageViewType::ViewCube,
+            base_layer: 0,
+            layer_count: 6,
+            base_mipmap: 0,
+            mipmap_count: vk::REMAINING_MIP_LEVELS,
+            aspect: VEImageViewAspect::Default,
         }
     }
 
@@ -109,8 +391,14 @@ impl VEImageViewCreateInfo {
             layer_count: 1,
             base_mipmap: 0,
             mipmap_count: 1,
+            aspect: VEImageViewAspect::Default,
         }
     }
+
+    pub fn with_aspect(mut self, aspect: VEImageViewAspect) -> Self {
+        self.aspect = aspect;
+        self
+    }
 }
 
 #[derive(Clone)]
@@ -123,14 +411,23 @@ pub struct VEImage {
     pub depth: u32,
 
     pub format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    pub mip_levels: u32,
+    pub array_layers: u32,
 
-    aspect: vk::ImageAspectFlags,
+    pub(crate) aspect: vk::ImageAspectFlags,
 
+    // Layout of mip 0 and layer 0, `states` has every subresource.
     pub current_layout: vk::ImageLayout,
+    pub(crate) states: Vec<VEResourceState>,
 
     allocation: Option<VESingleAllocation>,
+    // bound to memory owned by someone else, like the transient images of a render graph
+    aliased: bool,
     pub handle: vk::Image,
     views: HashMap<VEImageViewCreateInfo, vk::ImageView>,
+    // applied to every view, like gray images read back as gray from a single channel
+    pub(crate) swizzle: vk::ComponentMapping,
 }
 
 impl Debug for VEImage {
@@ -141,7 +438,9 @@ impl Debug for VEImage {
 
 impl VEImage {
     pub fn is_depth(&self) -> bool {
-        self.format == vk::Format::D16_UNORM || self.format == vk::Format::D32_SFLOAT
+        // stencil only formats are bound as depth/stencil attachments as well
+        self.aspect
+            .intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL)
     }
 
     pub fn transition_layout(
@@ -160,10 +459,35 @@ impl VEImage {
         )?;
 
         self.current_layout = to_layout;
+        self.states.fill(VEResourceState::unknown(to_layout));
 
         Ok(())
     }
 
+    // The subresources were moved to `layout` outside of the tracking, like by the final layout of
+    // a render pass.
+    pub(crate) fn set_range_layout(&mut self, range: VESubresourceRange, layout: vk::ImageLayout) {
+        for mip in range.base_mip..range.base_mip.saturating_add(range.mip_count) {
+            for layer in range.base_layer..range.base_layer.saturating_add(range.layer_count) {
+                if let Some(index) = self.state_index(mip, layer) {
+                    self.states[index] = VEResourceState::unknown(layout);
+                }
+            }
+        }
+        self.current_layout = self.states[0].layout;
+    }
+
+    pub fn layout_at(&self, mip: u32, layer: u32) -> Option<vk::ImageLayout> {
+        self.states
+            .get(self.state_index(mip, layer)?)
+            .map(|s| s.layout)
+    }
+
+    pub(crate) fn state_index(&self, mip: u32, layer: u32) -> Option<usize> {
+        (mip < self.mip_levels && layer < self.array_layers)
+            .then_some((mip * self.array_layers + layer) as usize)
+    }
+
     pub fn get_view(&mut self, info: VEImageViewCreateInfo) -> Result<vk::ImageView, VEImageError> {
         let existing = self.views.get(&info);
         match existing {
@@ -183,18 +507,13 @@ impl VEImage {
                     .format(self.format)
                     .subresource_range(
                         vk::ImageSubresourceRange::default()
-                            .aspect_mask(self.aspect)
+                            .aspect_mask(get_view_aspect_flags(self.aspect, info.aspect)?)
                             .base_mip_level(info.base_mipmap)
                             .level_count(info.mipmap_count)
                             .base_array_layer(info.base_layer)
                             .layer_count(info.layer_count),
                     )
-                    .components(vk::ComponentMapping {
-                        r: vk::ComponentSwizzle::IDENTITY,
-                        g: vk::ComponentSwizzle::IDENTITY,
-                        b: vk::ComponentSwizzle::IDENTITY,
-                        a: vk::ComponentSwizzle::IDENTITY,
-                    });
+                    .components(self.swizzle);
 
                 let image_view_handle = unsafe {
                     self.device
@@ -212,9 +531,8 @@ impl VEImage {
 
 impl Drop for VEImage {
     fn drop(&mut self) {
-        if let Some(_) = self.allocation {
-            // only free the ones that app allocated, not swapchain, for example
-            // probably this should be handled differently
+        if self.allocation.is_some() || self.aliased {
+            // only free the ones that app created, not swapchain, for example
             unsafe {
                 for view in self.views.iter() {
                     self.device.device.destroy_image_view(*view.1, None);
@@ -224,3 +542,119 @@ impl Drop for VEImage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_desc_defaults_to_a_single_2d_image() {
+        let desc = VEImageDesc::new(64, 32, VEImageFormat::RGBA8unorm, &[VEImageUsage::Sampled]);
+        assert_eq!((desc.width, desc.height, desc.depth), (64, 32, 1));
+        assert_eq!(desc.samples, VESampleCount::Count1);
+        assert_eq!(desc.mip_levels, VEMipLevels::Count(1));
+        assert_eq!(desc.layers, VEImageLayers::Array(1));
+
+        let desc = desc
+            .with_depth(4)
+            .with_samples(VESampleCount::Count4)
+            .with_mip_levels(VEMipLevels::Full)
+            .with_layers(VEImageLayers::Cube(2));
+        assert_eq!(desc.depth, 4);
+        assert_eq!(desc.samples, VESampleCount::Count4);
+        assert_eq!(desc.mip_levels, VEMipLevels::Full);
+        assert_eq!(desc.layers, VEImageLayers::Cube(2));
+        assert_eq!(desc.usages, vec![VEImageUsage::Sampled]);
+    }
+
+    #[test]
+    fn sampled_views_of_depth_stencil_images_see_depth_only() {
+        let depth_stencil = vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL;
+        assert!(matches!(
+            get_view_aspect_flags(depth_stencil, VEImageViewAspect::Default),
+            Ok(vk::ImageAspectFlags::DEPTH)
+        ));
+        assert!(matches!(
+            get_view_aspect_flags(depth_stencil, VEImageViewAspect::Stencil),
+            Ok(vk::ImageAspectFlags::STENCIL)
+        ));
+        assert!(get_view_aspect_flags(depth_stencil, VEImageViewAspect::All)
+            .is_ok_and(|flags| flags == depth_stencil));
+        assert!(matches!(
+            get_view_aspect_flags(vk::ImageAspectFlags::STENCIL, VEImageViewAspect::Default),
+            Ok(vk::ImageAspectFlags::STENCIL)
+        ));
+    }
+
+    #[test]
+    fn view_aspects_must_exist_in_the_image() {
+        assert!(matches!(
+            get_view_aspect_flags(vk::ImageAspectFlags::COLOR, VEImageViewAspect::Default),
+            Ok(vk::ImageAspectFlags::COLOR)
+        ));
+        assert!(matches!(
+            get_view_aspect_flags(vk::ImageAspectFlags::COLOR, VEImageViewAspect::Depth),
+            Err(VEImageError::ViewAspectNotInImage(
+                _,
+                VEImageViewAspect::Depth
+            ))
+        ));
+        assert!(matches!(
+            get_view_aspect_flags(vk::ImageAspectFlags::DEPTH, VEImageViewAspect::Stencil),
+            Err(VEImageError::ViewAspectNotInImage(
+                _,
+                VEImageViewAspect::Stencil
+            ))
+        ));
+    }
+
+    #[test]
+    fn usage_features_of_color_formats() {
+        let color = vk::ImageAspectFlags::COLOR;
+        let expected = [
+            (
+                VEImageUsage::ColorAttachment,
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+            ),
+            (
+                VEImageUsage::InputAttachment,
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+            ),
+            (VEImageUsage::Sampled, vk::FormatFeatureFlags::SAMPLED_IMAGE),
+            (VEImageUsage::Storage, vk::FormatFeatureFlags::STORAGE_IMAGE),
+            (
+                VEImageUsage::TransferDestination,
+                vk::FormatFeatureFlags::TRANSFER_DST,
+            ),
+            (
+                VEImageUsage::TransferSource,
+                vk::FormatFeatureFlags::TRANSFER_SRC,
+            ),
+        ];
+        for (usage, features) in expected {
+            assert_eq!(get_usage_format_features(&usage, color), features);
+        }
+    }
+
+    #[test]
+    fn usage_features_of_depth_stencil_formats() {
+        for aspect in [
+            vk::ImageAspectFlags::DEPTH,
+            vk::ImageAspectFlags::STENCIL,
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+        ] {
+            assert_eq!(
+                get_usage_format_features(&VEImageUsage::DepthAttachment, aspect),
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            );
+            assert_eq!(
+                get_usage_format_features(&VEImageUsage::InputAttachment, aspect),
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            );
+            assert_eq!(
+                get_usage_format_features(&VEImageUsage::Sampled, aspect),
+                vk::FormatFeatureFlags::SAMPLED_IMAGE
+            );
+        }
+    }
+}
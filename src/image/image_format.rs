@@ -1,5 +1,7 @@
+use crate::core::device::VEDevice;
 use ash::vk;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VEImageFormat {
     R8inorm,
     RG8inorm,
@@ -7,8 +9,26 @@ pub enum VEImageFormat {
 
     R8unorm,
     RG8unorm,
+    RGB8unorm,
     RGBA8unorm,
 
+    R8srgb,
+    RG8srgb,
+    RGB8srgb,
+    RGBA8srgb,
+
+    BGRA8unorm,
+    BGRA8srgb,
+
+    R16inorm,
+    RG16inorm,
+    RGBA16inorm,
+
+    R16unorm,
+    RG16unorm,
+    RGB16unorm,
+    RGBA16unorm,
+
     R16i,
     RG16i,
     RGBA16i,
@@ -31,47 +51,798 @@ pub enum VEImageFormat {
 
     R32f,
     RG32f,
+    RGB32f,
     RGBA32f,
 
+    A2B10G10R10unorm,
+    A2R10G10B10unorm,
+    A2B10G10R10u,
+    B10G11R11ufloat,
+    E5B9G9R9ufloat,
+
     Depth16u,
     Depth32f,
+    Depth24uStencil8u,
+    Depth32fStencil8u,
+    Stencil8u,
+
+    BC1unorm,
+    BC1srgb,
+    BC2unorm,
+    BC2srgb,
+    BC3unorm,
+    BC3srgb,
+    BC4unorm,
+    BC4inorm,
+    BC5unorm,
+    BC5inorm,
+    BC6Hufloat,
+    BC6Hfloat,
+    BC7unorm,
+    BC7srgb,
+
+    ETC2RGB8unorm,
+    ETC2RGB8srgb,
+    ETC2RGB8A1unorm,
+    ETC2RGB8A1srgb,
+    ETC2RGBA8unorm,
+    ETC2RGBA8srgb,
+
+    ASTC4x4unorm,
+    ASTC4x4srgb,
+    ASTC6x6unorm,
+    ASTC6x6srgb,
+    ASTC8x8unorm,
+    ASTC8x8srgb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VEFormatAspect {
+    Color,
+    Depth,
+    Stencil,
+    DepthStencil,
+}
+
+// How the shader sees the values, srgb formats are unorm with the srgb curve applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VEFormatType {
+    Unorm,
+    Snorm,
+    Uint,
+    Sint,
+    Float,
+    Srgb,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VEFormatInfo {
+    pub format: VEImageFormat,
+    pub vk_format: vk::Format,
+    pub aspect: VEFormatAspect,
+    pub typ: VEFormatType,
+    // texel block in texels and bytes, 1x1 for uncompressed formats
+    pub block_width: u32,
+    pub block_height: u32,
+    pub block_size: u32,
+}
+
+const fn color(
+    format: VEImageFormat,
+    vk_format: vk::Format,
+    typ: VEFormatType,
+    block_size: u32,
+) -> VEFormatInfo {
+    VEFormatInfo {
+        format,
+        vk_format,
+        aspect: VEFormatAspect::Color,
+        typ,
+        block_width: 1,
+        block_height: 1,
+        block_size,
+    }
+}
+
+const fn depth_stencil(
+    format: VEImageFormat,
+    vk_format: vk::Format,
+    aspect: VEFormatAspect,
+    typ: VEFormatType,
+    block_size: u32,
+) -> VEFormatInfo {
+    VEFormatInfo {
+        format,
+        vk_format,
+        aspect,
+        typ,
+        block_width: 1,
+        block_height: 1,
+        block_size,
+    }
+}
+
+const fn compressed(
+    format: VEImageFormat,
+    vk_format: vk::Format,
+    typ: VEFormatType,
+    block_extent: u32,
+    block_size: u32,
+) -> VEFormatInfo {
+    VEFormatInfo {
+        format,
+        vk_format,
+        aspect: VEFormatAspect::Color,
+        typ,
+        block_width: block_extent,
+        block_height: block_extent,
+        block_size,
+    }
+}
+
+// In declaration order of VEImageFormat, `get_format_info` indexes it with the variant.
+static FORMAT_INFOS: [VEFormatInfo; 75] = [
+    color(
+        VEImageFormat::R8inorm,
+        vk::Format::R8_SNORM,
+        VEFormatType::Snorm,
+        1,
+    ),
+    color(
+        VEImageFormat::RG8inorm,
+        vk::Format::R8G8_SNORM,
+        VEFormatType::Snorm,
+        2,
+    ),
+    color(
+        VEImageFormat::RGBA8inorm,
+        vk::Format::R8G8B8A8_SNORM,
+        VEFormatType::Snorm,
+        4,
+    ),
+    color(
+        VEImageFormat::R8unorm,
+        vk::Format::R8_UNORM,
+        VEFormatType::Unorm,
+        1,
+    ),
+    color(
+        VEImageFormat::RG8unorm,
+        vk::Format::R8G8_UNORM,
+        VEFormatType::Unorm,
+        2,
+    ),
+    color(
+        VEImageFormat::RGB8unorm,
+        vk::Format::R8G8B8_UNORM,
+        VEFormatType::Unorm,
+        3,
+    ),
+    color(
+        VEImageFormat::RGBA8unorm,
+        vk::Format::R8G8B8A8_UNORM,
+        VEFormatType::Unorm,
+        4,
+    ),
+    color(
+        VEImageFormat::R8srgb,
+        vk::Format::R8_SRGB,
+        VEFormatType::Srgb,
+        1,
+    ),
+    color(
+        VEImageFormat::RG8srgb,
+        vk::Format::R8G8_SRGB,
+        VEFormatType::Srgb,
+        2,
+    ),
+    color(
+        VEImageFormat::RGB8srgb,
+        vk::Format::R8G8B8_SRGB,
+        VEFormatType::Srgb,
+        3,
+    ),
+    color(
+        VEImageFormat::RGBA8srgb,
+        vk::Format::R8G8B8A8_SRGB,
+        VEFormatType::Srgb,
+        4,
+    ),
+    color(
+        VEImageFormat::BGRA8unorm,
+        vk::Format::B8G8R8A8_UNORM,
+        VEFormatType::Unorm,
+        4,
+    ),
+    color(
+        VEImageFormat::BGRA8srgb,
+        vk::Format::B8G8R8A8_SRGB,
+        VEFormatType::Srgb,
+        4,
+    ),
+    color(
+        VEImageFormat::R16inorm,
+        vk::Format::R16_SNORM,
+        VEFormatType::Snorm,
+        2,
+    ),
+    color(
+        VEImageFormat::RG16inorm,
+        vk::Format::R16G16_SNORM,
+        VEFormatType::Snorm,
+        4,
+    ),
+    color(
+        VEImageFormat::RGBA16inorm,
+        vk::Format::R16G16B16A16_SNORM,
+        VEFormatType::Snorm,
+        8,
+    ),
+    color(
+        VEImageFormat::R16unorm,
+        vk::Format::R16_UNORM,
+        VEFormatType::Unorm,
+        2,
+    ),
+    color(
+        VEImageFormat::RG16unorm,
+        vk::Format::R16G16_UNORM,
+        VEFormatType::Unorm,
+        4,
+    ),
+    color(
+        VEImageFormat::RGB16unorm,
+        vk::Format::R16G16B16_UNORM,
+        VEFormatType::Unorm,
+        6,
+    ),
+    color(
+        VEImageFormat::RGBA16unorm,
+        vk::Format::R16G16B16A16_UNORM,
+        VEFormatType::Unorm,
+        8,
+    ),
+    color(
+        VEImageFormat::R16i,
+        vk::Format::R16_SINT,
+        VEFormatType::Sint,
+        2,
+    ),
+    color(
+        VEImageFormat::RG16i,
+        vk::Format::R16G16_SINT,
+        VEFormatType::Sint,
+        4,
+    ),
+    color(
+        VEImageFormat::RGBA16i,
+        vk::Format::R16G16B16A16_SINT,
+        VEFormatType::Sint,
+        8,
+    ),
+    color(
+        VEImageFormat::R16u,
+        vk::Format::R16_UINT,
+        VEFormatType::Uint,
+        2,
+    ),
+    color(
+        VEImageFormat::RG16u,
+        vk::Format::R16G16_UINT,
+        VEFormatType::Uint,
+        4,
+    ),
+    color(
+        VEImageFormat::RGBA16u,
+        vk::Format::R16G16B16A16_UINT,
+        VEFormatType::Uint,
+        8,
+    ),
+    color(
+        VEImageFormat::R16f,
+        vk::Format::R16_SFLOAT,
+        VEFormatType::Float,
+        2,
+    ),
+    color(
+        VEImageFormat::RG16f,
+        vk::Format::R16G16_SFLOAT,
+        VEFormatType::Float,
+        4,
+    ),
+    color(
+        VEImageFormat::RGBA16f,
+        vk::Format::R16G16B16A16_SFLOAT,
+        VEFormatType::Float,
+        8,
+    ),
+    color(
+        VEImageFormat::R32i,
+        vk::Format::R32_SINT,
+        VEFormatType::Sint,
+        4,
+    ),
+    color(
+        VEImageFormat::RG32i,
+        vk::Format::R32G32_SINT,
+        VEFormatType::Sint,
+        8,
+    ),
+    color(
+        VEImageFormat::RGBA32i,
+        vk::Format::R32G32B32A32_SINT,
+        VEFormatType::Sint,
+        16,
+    ),
+    color(
+        VEImageFormat::R32u,
+        vk::Format::R32_UINT,
+        VEFormatType::Uint,
+        4,
+    ),
+    color(
+        VEImageFormat::RG32u,
+        vk::Format::R32G32_UINT,
+        VEFormatType::Uint,
+        8,
+    ),
+    color(
+        VEImageFormat::RGBA32u,
+        vk::Format::R32G32B32A32_UINT,
+        VEFormatType::Uint,
+        16,
+    ),
+    color(
+        VEImageFormat::R32f,
+        vk::Format::R32_SFLOAT,
+        VEFormatType::Float,
+        4,
+    ),
+    color(
+        VEImageFormat::RG32f,
+        vk::Format::R32G32_SFLOAT,
+        VEFormatType::Float,
+        8,
+    ),
+    color(
+        VEImageFormat::RGB32f,
+        vk::Format::R32G32B32_SFLOAT,
+        VEFormatType::Float,
+        12,
+    ),
+    color(
+        VEImageFormat::RGBA32f,
+        vk::Format::R32G32B32A32_SFLOAT,
+        VEFormatType::Float,
+        16,
+    ),
+    color(
+        VEImageFormat::A2B10G10R10unorm,
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+        VEFormatType::Unorm,
+        4,
+    ),
+    color(
+        VEImageFormat::A2R10G10B10unorm,
+        vk::Format::A2R10G10B10_UNORM_PACK32,
+        VEFormatType::Unorm,
+        4,
+    ),
+    color(
+        VEImageFormat::A2B10G10R10u,
+        vk::Format::A2B10G10R10_UINT_PACK32,
+        VEFormatType::Uint,
+        4,
+    ),
+    color(
+        VEImageFormat::B10G11R11ufloat,
+        vk::Format::B10G11R11_UFLOAT_PACK32,
+        VEFormatType::Float,
+        4,
+    ),
+    color(
+        VEImageFormat::E5B9G9R9ufloat,
+        vk::Format::E5B9G9R9_UFLOAT_PACK32,
+        VEFormatType::Float,
+        4,
+    ),
+    depth_stencil(
+        VEImageFormat::Depth16u,
+        vk::Format::D16_UNORM,
+        VEFormatAspect::Depth,
+        VEFormatType::Unorm,
+        2,
+    ),
+    depth_stencil(
+        VEImageFormat::Depth32f,
+        vk::Format::D32_SFLOAT,
+        VEFormatAspect::Depth,
+        VEFormatType::Float,
+        4,
+    ),
+    depth_stencil(
+        VEImageFormat::Depth24uStencil8u,
+        vk::Format::D24_UNORM_S8_UINT,
+        VEFormatAspect::DepthStencil,
+        VEFormatType::Unorm,
+        4,
+    ),
+    depth_stencil(
+        VEImageFormat::Depth32fStencil8u,
+        vk::Format::D32_SFLOAT_S8_UINT,
+        VEFormatAspect::DepthStencil,
+        VEFormatType::Float,
+        5,
+    ),
+    depth_stencil(
+        VEImageFormat::Stencil8u,
+        vk::Format::S8_UINT,
+        VEFormatAspect::Stencil,
+        VEFormatType::Uint,
+        1,
+    ),
+    compressed(
+        VEImageFormat::BC1unorm,
+        vk::Format::BC1_RGBA_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::BC1srgb,
+        vk::Format::BC1_RGBA_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::BC2unorm,
+        vk::Format::BC2_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC2srgb,
+        vk::Format::BC2_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC3unorm,
+        vk::Format::BC3_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC3srgb,
+        vk::Format::BC3_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC4unorm,
+        vk::Format::BC4_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::BC4inorm,
+        vk::Format::BC4_SNORM_BLOCK,
+        VEFormatType::Snorm,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::BC5unorm,
+        vk::Format::BC5_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC5inorm,
+        vk::Format::BC5_SNORM_BLOCK,
+        VEFormatType::Snorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC6Hufloat,
+        vk::Format::BC6H_UFLOAT_BLOCK,
+        VEFormatType::Float,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC6Hfloat,
+        vk::Format::BC6H_SFLOAT_BLOCK,
+        VEFormatType::Float,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC7unorm,
+        vk::Format::BC7_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::BC7srgb,
+        vk::Format::BC7_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ETC2RGB8unorm,
+        vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::ETC2RGB8srgb,
+        vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::ETC2RGB8A1unorm,
+        vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::ETC2RGB8A1srgb,
+        vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        8,
+    ),
+    compressed(
+        VEImageFormat::ETC2RGBA8unorm,
+        vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ETC2RGBA8srgb,
+        vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ASTC4x4unorm,
+        vk::Format::ASTC_4X4_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ASTC4x4srgb,
+        vk::Format::ASTC_4X4_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        4,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ASTC6x6unorm,
+        vk::Format::ASTC_6X6_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        6,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ASTC6x6srgb,
+        vk::Format::ASTC_6X6_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        6,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ASTC8x8unorm,
+        vk::Format::ASTC_8X8_UNORM_BLOCK,
+        VEFormatType::Unorm,
+        8,
+        16,
+    ),
+    compressed(
+        VEImageFormat::ASTC8x8srgb,
+        vk::Format::ASTC_8X8_SRGB_BLOCK,
+        VEFormatType::Srgb,
+        8,
+        16,
+    ),
+];
+
+impl VEFormatInfo {
+    pub fn aspect_flags(&self) -> vk::ImageAspectFlags {
+        match self.aspect {
+            VEFormatAspect::Color => vk::ImageAspectFlags::COLOR,
+            VEFormatAspect::Depth => vk::ImageAspectFlags::DEPTH,
+            VEFormatAspect::Stencil => vk::ImageAspectFlags::STENCIL,
+            VEFormatAspect::DepthStencil => {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            }
+        }
+    }
+
+    pub fn has_depth(&self) -> bool {
+        matches!(
+            self.aspect,
+            VEFormatAspect::Depth | VEFormatAspect::DepthStencil
+        )
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        matches!(
+            self.aspect,
+            VEFormatAspect::Stencil | VEFormatAspect::DepthStencil
+        )
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.block_width > 1 || self.block_height > 1
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self.typ, VEFormatType::Uint | VEFormatType::Sint)
+    }
+
+    // Features of optimally tiled images in this format on `device`.
+    pub fn features(&self, device: &VEDevice) -> vk::FormatFeatureFlags {
+        device.format_features(self.vk_format, vk::ImageTiling::OPTIMAL)
+    }
+
+    pub fn supports(&self, device: &VEDevice, features: vk::FormatFeatureFlags) -> bool {
+        self.features(device).contains(features)
+    }
+}
+
+pub fn get_format_info(format: VEImageFormat) -> &'static VEFormatInfo {
+    &FORMAT_INFOS[format as usize]
+}
+
+pub fn get_format_info_from_vk(format: vk::Format) -> Option<&'static VEFormatInfo> {
+    FORMAT_INFOS.iter().find(|info| info.vk_format == format)
 }
 
 pub fn get_image_format(format: VEImageFormat) -> vk::Format {
-    match format {
-        VEImageFormat::R8inorm => vk::Format::R8_SNORM,
-        VEImageFormat::RG8inorm => vk::Format::R8G8_SNORM,
-        VEImageFormat::RGBA8inorm => vk::Format::R8G8B8A8_SNORM,
+    get_format_info(format).vk_format
+}
 
-        VEImageFormat::R8unorm => vk::Format::R8_UNORM,
-        VEImageFormat::RG8unorm => vk::Format::R8G8_UNORM,
-        VEImageFormat::RGBA8unorm => vk::Format::R8G8B8A8_UNORM,
+pub(crate) fn get_image_format_from_vk(format: vk::Format) -> Option<VEImageFormat> {
+    get_format_info_from_vk(format).map(|info| info.format)
+}
 
-        VEImageFormat::R16i => vk::Format::R16_SINT,
-        VEImageFormat::RG16i => vk::Format::R16G16_SINT,
-        VEImageFormat::RGBA16i => vk::Format::R16G16B16A16_SINT,
+// Formats the table does not know, like exotic swapchain formats, are treated as color.
+pub(crate) fn get_aspect_from_vk(format: vk::Format) -> vk::ImageAspectFlags {
+    get_format_info_from_vk(format).map_or(vk::ImageAspectFlags::COLOR, |info| info.aspect_flags())
+}
 
-        VEImageFormat::R16u => vk::Format::R16_UINT,
-        VEImageFormat::RG16u => vk::Format::R16G16_UINT,
-        VEImageFormat::RGBA16u => vk::Format::R16G16B16A16_UINT,
+// Bytes of one mip level of one layer, None if the size does not fit in memory.
+pub fn get_level_size(format: VEImageFormat, width: u32, height: u32, depth: u32) -> Option<usize> {
+    let info = get_format_info(format);
+    let size = u64::from(width.div_ceil(info.block_width))
+        .checked_mul(u64::from(height.div_ceil(info.block_height)))?
+        .checked_mul(u64::from(depth))?
+        .checked_mul(u64::from(info.block_size))?;
+    usize::try_from(size).ok()
+}
 
-        VEImageFormat::R16f => vk::Format::R16_SFLOAT,
-        VEImageFormat::RG16f => vk::Format::R16G16_SFLOAT,
-        VEImageFormat::RGBA16f => vk::Format::R16G16B16A16_SFLOAT,
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        VEImageFormat::R32i => vk::Format::R32_SINT,
-        VEImageFormat::RG32i => vk::Format::R32G32_SINT,
-        VEImageFormat::RGBA32i => vk::Format::R32G32B32A32_SINT,
+    #[test]
+    fn table_is_indexed_by_format() {
+        assert_eq!(FORMAT_INFOS.len(), VEImageFormat::ASTC8x8srgb as usize + 1);
+        for (index, info) in FORMAT_INFOS.iter().enumerate() {
+            assert_eq!(info.format as usize, index);
+            assert_eq!(get_format_info(info.format).format, info.format);
+        }
+    }
 
-        VEImageFormat::R32u => vk::Format::R32_UINT,
-        VEImageFormat::RG32u => vk::Format::R32G32_UINT,
-        VEImageFormat::RGBA32u => vk::Format::R32G32B32A32_UINT,
+    #[test]
+    fn vk_formats_round_trip() {
+        for info in FORMAT_INFOS.iter() {
+            assert!(
+                get_format_info_from_vk(info.vk_format).is_some_and(|i| i.format == info.format)
+            );
+            assert_eq!(
+                get_image_format_from_vk(get_image_format(info.format)),
+                Some(info.format)
+            );
+        }
+        assert!(get_format_info_from_vk(vk::Format::UNDEFINED).is_none());
+        assert_eq!(
+            get_aspect_from_vk(vk::Format::UNDEFINED),
+            vk::ImageAspectFlags::COLOR
+        );
+    }
 
-        VEImageFormat::R32f => vk::Format::R32_SFLOAT,
-        VEImageFormat::RG32f => vk::Format::R32G32_SFLOAT,
-        VEImageFormat::RGBA32f => vk::Format::R32G32B32A32_SFLOAT,
+    #[test]
+    fn depth_stencil_entries() {
+        let info = get_format_info(VEImageFormat::Depth24uStencil8u);
+        assert_eq!(info.aspect, VEFormatAspect::DepthStencil);
+        assert_eq!(
+            info.aspect_flags(),
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        );
+        assert!(info.has_depth() && info.has_stencil());
+        assert_eq!(info.block_size, 4);
+
+        let info = get_format_info(VEImageFormat::Depth32fStencil8u);
+        assert_eq!(info.aspect, VEFormatAspect::DepthStencil);
+        assert_eq!(info.block_size, 5);
+
+        let info = get_format_info(VEImageFormat::Depth32f);
+        assert_eq!(info.aspect, VEFormatAspect::Depth);
+        assert!(info.has_depth() && !info.has_stencil());
+
+        let info = get_format_info(VEImageFormat::Stencil8u);
+        assert_eq!(info.aspect, VEFormatAspect::Stencil);
+        assert_eq!(info.aspect_flags(), vk::ImageAspectFlags::STENCIL);
+        assert!(!info.has_depth() && info.has_stencil());
+        assert_eq!(info.block_size, 1);
+    }
+
+    #[test]
+    fn packed_entries() {
+        for format in [
+            VEImageFormat::A2B10G10R10unorm,
+            VEImageFormat::A2R10G10B10unorm,
+            VEImageFormat::A2B10G10R10u,
+            VEImageFormat::B10G11R11ufloat,
+            VEImageFormat::E5B9G9R9ufloat,
+        ] {
+            let info = get_format_info(format);
+            assert_eq!(info.aspect, VEFormatAspect::Color);
+            assert_eq!((info.block_width, info.block_height), (1, 1));
+            assert_eq!(info.block_size, 4);
+        }
+        assert_eq!(
+            get_format_info(VEImageFormat::E5B9G9R9ufloat).vk_format,
+            vk::Format::E5B9G9R9_UFLOAT_PACK32
+        );
+    }
 
-        VEImageFormat::Depth16u => vk::Format::D16_UNORM,
-        VEImageFormat::Depth32f => vk::Format::D32_SFLOAT,
+    #[test]
+    fn block_compressed_entries() {
+        let expected = [
+            (VEImageFormat::BC1unorm, 4, 8),
+            (VEImageFormat::BC1srgb, 4, 8),
+            (VEImageFormat::BC3unorm, 4, 16),
+            (VEImageFormat::BC4unorm, 4, 8),
+            (VEImageFormat::BC5unorm, 4, 16),
+            (VEImageFormat::BC6Hufloat, 4, 16),
+            (VEImageFormat::BC7srgb, 4, 16),
+            (VEImageFormat::ETC2RGB8unorm, 4, 8),
+            (VEImageFormat::ETC2RGBA8srgb, 4, 16),
+            (VEImageFormat::ASTC4x4unorm, 4, 16),
+            (VEImageFormat::ASTC6x6srgb, 6, 16),
+            (VEImageFormat::ASTC8x8unorm, 8, 16),
+        ];
+        for (format, extent, size) in expected {
+            let info = get_format_info(format);
+            assert_eq!(info.aspect, VEFormatAspect::Color);
+            assert_eq!((info.block_width, info.block_height), (extent, extent));
+            assert_eq!(info.block_size, size);
+        }
+        assert_eq!(
+            get_level_size(VEImageFormat::ASTC6x6unorm, 13, 7, 1),
+            Some(3 * 2 * 16)
+        );
+        assert_eq!(get_level_size(VEImageFormat::BC1unorm, 1, 1, 1), Some(8));
     }
 }
@@ -0,0 +1,164 @@
+use crate::core::command_pool::VECommandPool;
+use crate::core::device::VEDevice;
+use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::image::bc_decode::{decode_bc, get_decoded_format};
+use crate::image::image::{VEImage, VEImageDesc, VEImageError, VEImageUsage, VEMipLevels};
+use crate::image::image_format::{get_format_info, get_image_format, VEImageFormat};
+use crate::image::texture_container::{VETextureContainer, VETextureContainerError};
+use crate::memory::memory_manager::VEMemoryManager;
+use ash::vk;
+use std::sync::{Arc, Mutex};
+
+// Compressed formats also need their device feature, the format properties alone are not
+// enough on every driver.
+pub(crate) fn supports_sampling(device: &VEDevice, format: VEImageFormat) -> bool {
+    let feature = match format {
+        VEImageFormat::BC1unorm
+        | VEImageFormat::BC1srgb
+        | VEImageFormat::BC2unorm
+        | VEImageFormat::BC2srgb
+        | VEImageFormat::BC3unorm
+        | VEImageFormat::BC3srgb
+        | VEImageFormat::BC4unorm
+        | VEImageFormat::BC4inorm
+        | VEImageFormat::BC5unorm
+        | VEImageFormat::BC5inorm
+        | VEImageFormat::BC6Hufloat
+        | VEImageFormat::BC6Hfloat
+        | VEImageFormat::BC7unorm
+        | VEImageFormat::BC7srgb => device.features.texture_compression_bc,
+        VEImageFormat::ETC2RGB8unorm
+        | VEImageFormat::ETC2RGB8srgb
+        | VEImageFormat::ETC2RGB8A1unorm
+        | VEImageFormat::ETC2RGB8A1srgb
+        | VEImageFormat::ETC2RGBA8unorm
+        | VEImageFormat::ETC2RGBA8srgb => device.features.texture_compression_etc2,
+        VEImageFormat::ASTC4x4unorm
+        | VEImageFormat::ASTC4x4srgb
+        | VEImageFormat::ASTC6x6unorm
+        | VEImageFormat::ASTC6x6srgb
+        | VEImageFormat::ASTC8x8unorm
+        | VEImageFormat::ASTC8x8srgb => device.features.texture_compression_astc_ldr,
+        _ => true,
+    };
+    feature
+        && device
+            .format_features(get_image_format(format), vk::ImageTiling::OPTIMAL)
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE | vk::FormatFeatureFlags::TRANSFER_DST)
+}
+
+// Mip levels of the image a container is loaded into and whether they are generated. Block
+// compressed levels cannot be blitted, their chain stays as the file has it.
+fn get_container_mip_levels(container: &VETextureContainer) -> (VEMipLevels, bool) {
+    if container.generate_mipmaps && !get_format_info(container.format).is_compressed() {
+        (VEMipLevels::Full, true)
+    } else {
+        (VEMipLevels::Count(container.levels.len() as u32), false)
+    }
+}
+
+impl VEImage {
+    // Loads a KTX2 or DDS file with all of its mip levels and layers. BC1 to BC5 and BC7 are
+    // decoded on the CPU if the device cannot sample them, other unsupported formats fail.
+    pub fn from_container(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        bytes: &[u8],
+        usages: &[VEImageUsage],
+    ) -> Result<VEImage, VEImageError> {
+        let mut container = VETextureContainer::parse(bytes)?;
+
+        if !supports_sampling(&device, container.format) {
+            let decoded_format = get_decoded_format(container.format).ok_or(
+                VEImageError::FormatNotSupported(get_image_format(container.format)),
+            )?;
+            let layer_count = container.layer_count()?;
+            for (level, data) in container.levels.iter_mut().enumerate() {
+                let level = level as u32;
+                *data = decode_bc(
+                    container.format,
+                    data,
+                    (container.width >> level).max(1),
+                    (container.height >> level).max(1),
+                    layer_count * (container.depth >> level).max(1),
+                )
+                .ok_or(VETextureContainerError::Truncated)?;
+            }
+            container.format = decoded_format;
+        }
+
+        let (mip_levels, generate_mipmaps) = get_container_mip_levels(&container);
+
+        let mut usages = usages.to_vec();
+        usages.push(VEImageUsage::TransferDestination);
+        let mut result = VEImage::from_full(
+            device,
+            queue,
+            command_pool.clone(),
+            memory_manager.clone(),
+            &VEImageDesc::new(container.width, container.height, container.format, &usages)
+                .with_depth(container.depth)
+                .with_mip_levels(mip_levels)
+                .with_layers(container.layers),
+        )?;
+
+        let layer_count = container.layer_count()?;
+        for (level, data) in container.levels.iter().enumerate() {
+            result.upload_level(
+                command_pool.clone(),
+                memory_manager.clone(),
+                data,
+                level as u32,
+                0,
+                layer_count,
+            )?;
+        }
+        if generate_mipmaps {
+            result.generate_mipmaps(command_pool)?;
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::image::VEImageLayers;
+
+    fn bc1_container(levels: usize, generate_mipmaps: bool) -> VETextureContainer {
+        VETextureContainer {
+            format: VEImageFormat::BC1unorm,
+            width: 16,
+            height: 16,
+            depth: 1,
+            layers: VEImageLayers::Array(1),
+            levels: vec![vec![0; 8]; levels],
+            generate_mipmaps,
+        }
+    }
+
+    #[test]
+    fn block_compressed_chains_are_uploaded_as_stored() {
+        assert_eq!(
+            get_container_mip_levels(&bc1_container(3, false)),
+            (VEMipLevels::Count(3), false)
+        );
+        assert_eq!(
+            get_container_mip_levels(&bc1_container(1, true)),
+            (VEMipLevels::Count(1), false)
+        );
+    }
+
+    #[test]
+    fn uncompressed_containers_can_generate_mipmaps() {
+        let mut container = bc1_container(1, true);
+        container.format = VEImageFormat::RGBA8unorm;
+        assert_eq!(
+            get_container_mip_levels(&container),
+            (VEMipLevels::Full, true)
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::compute::compute_stage::VEComputeStage;
+use crate::core::command_buffer::VECommandBuffer;
+use crate::core::command_pool::VECommandPool;
+use crate::core::descriptor_set_layout::{
+    VEDescriptorSetFieldStage, VEDescriptorSetFieldType, VEDescriptorSetLayout,
+    VEDescriptorSetLayoutField,
+};
+use crate::core::device::VEDevice;
+use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::resource_state::VEImageAccess;
+use crate::core::shader_module::{VEShaderModule, VEShaderModuleType};
+use crate::image::filtering::VEFiltering;
+use crate::image::image::{
+    VEImage, VEImageDesc, VEImageError, VEImageLayers, VEImageUsage, VEImageViewAspect,
+    VEImageViewCreateInfo, VEImageViewType, VEMipLevels,
+};
+use crate::image::image_format::VEImageFormat;
+use crate::image::sampler::{VESampler, VESamplerAddressMode};
+use crate::memory::memory_manager::VEMemoryManager;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use image::{EncodableLayout, ImageReader};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+static EQUIRECTANGULAR_TO_CUBE_SPV: &[u8] = include_bytes!("shaders/equirectangular_to_cube.spv");
+static EQUIRECTANGULAR_GROUP_SIZE: u32 = 8;
+
+// Push constants of the conversion shader, the direction through the face center and the
+// directions along its image x and y axes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct VECubeFace {
+    forward: [f32; 4],
+    right: [f32; 4],
+    down: [f32; 4],
+    layer: i32,
+}
+
+unsafe impl Zeroable for VECubeFace {}
+unsafe impl Pod for VECubeFace {}
+
+static CUBE_FACES: [([f32; 4], [f32; 4], [f32; 4]); 6] = [
+    (
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+    ),
+    (
+        [-1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+    ),
+    (
+        [0.0, 1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ),
+    (
+        [0.0, -1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, -1.0, 0.0],
+    ),
+    (
+        [0.0, 0.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+    ),
+    (
+        [0.0, 0.0, -1.0, 0.0],
+        [-1.0, 0.0, 0.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+    ),
+];
+
+impl VEImage {
+    // Faces in +X, -X, +Y, -Y, +Z, -Z order, all square and of the same size.
+    pub fn cube_from_files(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        paths: &[&str; 6],
+        usages: &[VEImageUsage],
+    ) -> Result<VEImage, VEImageError> {
+        let mut faces = vec![];
+        for path in paths {
+            let img = ImageReader::open(path)
+                .map_err(VEImageError::OpeningFileFailed)?
+                .decode()
+                .map_err(VEImageError::ImageDecodingFailed)?;
+            faces.push(img.to_rgba8());
+        }
+        let (width, height) = faces[0].dimensions();
+        if faces
+            .iter()
+            .any(|face| face.dimensions() != (width, height))
+        {
+            return Err(VEImageError::CubeFaceSizeMismatch);
+        }
+
+        let mut usages = usages.to_vec();
+        usages.push(VEImageUsage::TransferDestination);
+        let mut result = VEImage::from_full(
+            device,
+            queue,
+            command_pool.clone(),
+            memory_manager.clone(),
+            &VEImageDesc::new(width, height, VEImageFormat::RGBA8unorm, &usages)
+                .with_mip_levels(VEMipLevels::Full)
+                .with_layers(VEImageLayers::Cube(1)),
+        )?;
+
+        for (layer, face) in faces.iter().enumerate() {
+            result.upload_layer(
+                command_pool.clone(),
+                memory_manager.clone(),
+                face.as_bytes(),
+                layer as u32,
+            )?;
+        }
+        result.generate_mipmaps(command_pool)?;
+
+        Ok(result)
+    }
+
+    // Projects an equirectangular panorama, usually an HDR file, onto a RGBA16f cube with
+    // `face_size` texels per side.
+    pub fn cube_from_equirectangular(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        path: &str,
+        face_size: u32,
+        usages: &[VEImageUsage],
+    ) -> Result<VEImage, VEImageError> {
+        let img = ImageReader::open(path)
+            .map_err(VEImageError::OpeningFileFailed)?
+            .decode()
+            .map_err(VEImageError::ImageDecodingFailed)?
+            .to_rgba32f();
+
+        let mut panorama = VEImage::from_data(
+            device.clone(),
+            queue.clone(),
+            command_pool.clone(),
+            memory_manager.clone(),
+            img.as_bytes(),
+            &VEImageDesc::new(
+                img.width(),
+                img.height(),
+                VEImageFormat::RGBA32f,
+                &[VEImageUsage::Sampled],
+            ),
+        )?;
+
+        let mut usages = usages.to_vec();
+        usages.push(VEImageUsage::Storage);
+        let mut result = VEImage::from_full(
+            device.clone(),
+            queue.clone(),
+            command_pool.clone(),
+            memory_manager,
+            &VEImageDesc::new(face_size, face_size, VEImageFormat::RGBA16f, &usages)
+                .with_mip_levels(VEMipLevels::Full)
+                .with_layers(VEImageLayers::Cube(1)),
+        )?;
+
+        // linear filtering of 32 bit float formats is optional
+        let filtering = || {
+            if device
+                .format_features(panorama.format, vk::ImageTiling::OPTIMAL)
+                .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+            {
+                VEFiltering::Linear
+            } else {
+                VEFiltering::Nearest
+            }
+        };
+        let sampler = VESampler::new(
+            device.clone(),
+            VESamplerAddressMode::Repeat,
+            filtering(),
+            filtering(),
+            false,
+        )?;
+
+        let mut set_layout = VEDescriptorSetLayout::new(
+            device.clone(),
+            &[
+                VEDescriptorSetLayoutField {
+                    binding: 0,
+                    typ: VEDescriptorSetFieldType::Sampler,
+                    stage: VEDescriptorSetFieldStage::Compute,
+                    count: 1,
+                },
+                VEDescriptorSetLayoutField {
+                    binding: 1,
+                    typ: VEDescriptorSetFieldType::StorageImage,
+                    stage: VEDescriptorSetFieldStage::Compute,
+                    count: 1,
+                },
+            ],
+        )?;
+        let shader = VEShaderModule::from_stream(
+            device.clone(),
+            &mut Cursor::new(EQUIRECTANGULAR_TO_CUBE_SPV),
+            VEShaderModuleType::Compute,
+        )?;
+        let stage = VEComputeStage::new(
+            device.clone(),
+            command_pool.clone(),
+            &[&set_layout],
+            &[],
+            &shader,
+        )?;
+
+        let command_buffer = VECommandBuffer::new(device, command_pool.clone())?;
+        command_buffer.begin()?;
+        command_buffer.require(&mut panorama, VEImageAccess::ComputeSampled)?;
+        command_buffer.require(&mut result, VEImageAccess::ComputeStorageWrite)?;
+
+        let panorama_view = panorama.get_view(VEImageViewCreateInfo::simple_2d())?;
+        let faces_view = result.get_view(VEImageViewCreateInfo {
+            typ: VEImageViewType::View2DArray,
+            base_mipmap: 0,
+            mipmap_count: 1,
+            base_layer: 0,
+            layer_count: 6,
+            aspect: VEImageViewAspect::Default,
+        })?;
+        let set = set_layout.create_descriptor_set()?;
+        set.bind_image_sampler(0, &panorama, panorama_view, &sampler)?;
+        set.bind_image_storage(1, &result, faces_view)?;
+
+        stage.bind(&command_buffer);
+        stage.set_descriptor_set(&command_buffer, 0, &set);
+        for (layer, (forward, right, down)) in CUBE_FACES.iter().enumerate() {
+            stage.push_constants(
+                &command_buffer,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &VECubeFace {
+                    forward: *forward,
+                    right: *right,
+                    down: *down,
+                    layer: layer as i32,
+                },
+            )?;
+            stage.dispatch(
+                &command_buffer,
+                face_size.div_ceil(EQUIRECTANGULAR_GROUP_SIZE),
+                face_size.div_ceil(EQUIRECTANGULAR_GROUP_SIZE),
+                1,
+            );
+        }
+        command_buffer.end()?;
+
+        {
+            let queue = queue.lock().map_err(|_| VEImageError::QueueLockingFailed)?;
+            command_buffer.submit(&queue, vec![], vec![])?;
+            queue.wait_idle()?;
+        }
+
+        result.generate_mipmaps(command_pool)?;
+        Ok(result)
+    }
+}
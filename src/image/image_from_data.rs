@@ -4,49 +4,107 @@ use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::VEMainDeviceQueue;
 use crate::core::memory_properties::VEMemoryProperties;
-use crate::image::image::{VEImage, VEImageError, VEImageUsage};
-use crate::image::image_format::VEImageFormat;
+use crate::core::resource_state::{VEImageAccess, VESubresourceRange};
+use crate::image::image::{
+    get_sample_count_flags, VEImage, VEImageDesc, VEImageError, VEImageUsage, VESampleCount,
+};
+use crate::image::image_format::{get_format_info_from_vk, get_level_size};
 use crate::memory::memory_manager::VEMemoryManager;
 use ash::vk;
 use ash::vk::CommandBufferUsageFlags;
 use std::sync::{Arc, Mutex};
 
 impl VEImage {
+    // `data` holds mip 0 of every layer back to back, the other mips are generated from it.
     pub fn from_data(
         device: Arc<VEDevice>,
         queue: Arc<Mutex<VEMainDeviceQueue>>,
         command_pool: Arc<VECommandPool>,
         memory_manager: Arc<Mutex<VEMemoryManager>>,
-
         data: &[u8],
-
-        width: u32,
-        height: u32,
-        depth: u32,
-
-        format: VEImageFormat,
-
-        usages: &[VEImageUsage],
+        desc: &VEImageDesc,
     ) -> Result<VEImage, VEImageError> {
-        let mut usages = usages.to_vec();
-        usages.push(VEImageUsage::TransferDestination);
+        // buffer to image copies only write single sampled images
+        if desc.samples != VESampleCount::Count1 {
+            return Err(VEImageError::UnsupportedSampleCount(
+                get_sample_count_flags(desc.samples),
+            ));
+        }
+        let mut desc = desc.clone();
+        desc.usages.push(VEImageUsage::TransferDestination);
         let mut result = VEImage::from_full(
             device.clone(),
             queue.clone(),
             command_pool.clone(),
             memory_manager.clone(),
-            width,
-            height,
-            depth,
-            format,
-            usages.as_slice(),
+            &desc,
+        )?;
+
+        let layer_count = result.array_layers;
+        result.upload_level(
+            command_pool.clone(),
+            memory_manager,
+            data,
+            0,
+            0,
+            layer_count,
         )?;
+        result.generate_mipmaps(command_pool)?;
+
+        Ok(result)
+    }
+
+    // Copies `data` into mip 0 of `layer`, the other mips are left alone until
+    // `generate_mipmaps`. The image needs the TransferDestination usage.
+    pub fn upload_layer(
+        &mut self,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        data: &[u8],
+        layer: u32,
+    ) -> Result<(), VEImageError> {
+        self.upload_level(command_pool, memory_manager, data, 0, layer, 1)
+    }
+
+    // Copies `data`, the layers of one mip level back to back, into `mip_level`.
+    pub(crate) fn upload_level(
+        &mut self,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        data: &[u8],
+        mip_level: u32,
+        base_layer: u32,
+        layer_count: u32,
+    ) -> Result<(), VEImageError> {
+        match base_layer.checked_add(layer_count) {
+            Some(end) if layer_count > 0 && end <= self.array_layers => {}
+            _ => {
+                return Err(VEImageError::LayerOutOfBounds(
+                    base_layer.saturating_add(layer_count.saturating_sub(1)),
+                ))
+            }
+        }
+        let info = get_format_info_from_vk(self.format)
+            .ok_or(VEImageError::FormatNotSupported(self.format))?;
+        let expected = get_level_size(
+            info.format,
+            (self.width >> mip_level).max(1),
+            (self.height >> mip_level).max(1),
+            (self.depth >> mip_level).max(1),
+        )
+        .and_then(|size| size.checked_mul(layer_count as usize));
+        if expected != Some(data.len()) {
+            return Err(VEImageError::DataSizeMismatch {
+                expected: expected.unwrap_or(usize::MAX),
+                actual: data.len(),
+            });
+        }
 
         let mut staging_buffer = VEBuffer::new(
-            device.clone(),
-            queue.clone(),
+            self.device.clone(),
+            self.queue.clone(),
             command_pool.clone(),
-            memory_manager.clone(),
+            memory_manager,
             &[VEBufferUsage::TransferSource],
             data.len() as vk::DeviceSize,
             Some(VEMemoryProperties::HostCoherent),
@@ -55,57 +113,51 @@ impl VEImage {
         unsafe {
             let mem = staging_buffer.map()? as *mut u8;
             std::ptr::copy(data.as_ptr(), mem, data.len());
-            // staging_buffer.unmap()?;
         }
 
-        let command_buffer = VECommandBuffer::new(device.clone(), command_pool.clone())?;
-        //command_buffer.begin(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        let command_buffer = VECommandBuffer::new(self.device.clone(), command_pool)?;
         command_buffer.begin()?;
 
-        result.transition_layout(
-            &command_buffer,
-            result.current_layout,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        )?;
+        let range = VESubresourceRange::new(mip_level, 1, base_layer, layer_count);
+        command_buffer.require_range(self, VEImageAccess::TransferWrite, range)?;
 
         let region = vk::BufferImageCopy::default()
             .image_subresource(
                 vk::ImageSubresourceLayers::default()
-                    .aspect_mask(result.aspect)
-                    .base_array_layer(0)
-                    .layer_count(1),
+                    .aspect_mask(self.aspect)
+                    .mip_level(mip_level)
+                    .base_array_layer(base_layer)
+                    .layer_count(layer_count),
             )
             .image_offset(vk::Offset3D::default())
             .image_extent(
                 vk::Extent3D::default()
-                    .width(width)
-                    .height(height)
-                    .depth(depth),
+                    .width((self.width >> mip_level).max(1))
+                    .height((self.height >> mip_level).max(1))
+                    .depth((self.depth >> mip_level).max(1)),
             );
 
         unsafe {
-            device.device.cmd_copy_buffer_to_image(
+            self.device.device.cmd_copy_buffer_to_image(
                 command_buffer.handle,
                 staging_buffer.buffer,
-                result.handle,
+                self.handle,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 &[region],
             );
         }
 
-        result.transition_layout(
-            &command_buffer,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::GENERAL,
-        )?;
-
+        command_buffer.require_range(self, VEImageAccess::General, range)?;
         command_buffer.end()?;
 
-        let queue = queue.lock().map_err(|_| VEImageError::QueueLockingFailed)?;
+        let queue = self
+            .queue
+            .lock()
+            .map_err(|_| VEImageError::QueueLockingFailed)?;
 
         command_buffer.submit(&queue, vec![], vec![])?;
         queue.wait_idle()?;
 
-        Ok(result)
+        Ok(())
     }
 }
@@ -1,12 +1,102 @@
 use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::VEMainDeviceQueue;
-use crate::image::image::{VEImage, VEImageError, VEImageUsage};
-use crate::image::image_format::VEImageFormat;
+use crate::image::image::image_from_container::supports_sampling;
+use crate::image::image::image_mipmaps::get_device_mipmap_path;
+use crate::image::image::{
+    VEImage, VEImageDesc, VEImageError, VEImageUsage, VELoadOptions, VEMipLevels,
+};
+use crate::image::image_format::{get_image_format, VEImageFormat};
+use crate::image::texture_container::VETextureContainer;
 use crate::memory::memory_manager::VEMemoryManager;
-use image::{EncodableLayout, ImageReader};
+use ash::vk;
+use image::{ColorType, DynamicImage, ImageReader};
+use std::io::Cursor;
 use std::sync::{Arc, Mutex};
 
+fn get_source_format(color: ColorType, srgb: bool) -> VEImageFormat {
+    match color {
+        ColorType::L8 if srgb => VEImageFormat::R8srgb,
+        ColorType::L8 => VEImageFormat::R8unorm,
+        ColorType::La8 if srgb => VEImageFormat::RG8srgb,
+        ColorType::La8 => VEImageFormat::RG8unorm,
+        ColorType::Rgb8 if srgb => VEImageFormat::RGB8srgb,
+        ColorType::Rgb8 => VEImageFormat::RGB8unorm,
+        ColorType::Rgba8 if srgb => VEImageFormat::RGBA8srgb,
+        ColorType::L16 => VEImageFormat::R16unorm,
+        ColorType::La16 => VEImageFormat::RG16unorm,
+        ColorType::Rgb16 => VEImageFormat::RGB16unorm,
+        ColorType::Rgba16 => VEImageFormat::RGBA16unorm,
+        ColorType::Rgb32F => VEImageFormat::RGB32f,
+        ColorType::Rgba32F => VEImageFormat::RGBA32f,
+        _ => VEImageFormat::RGBA8unorm,
+    }
+}
+
+// Three channel and srgb gray formats are optional, these are used when the device lacks them.
+fn get_padded_format(format: VEImageFormat) -> Option<VEImageFormat> {
+    match format {
+        VEImageFormat::R8srgb | VEImageFormat::RG8srgb => Some(VEImageFormat::RGBA8srgb),
+        VEImageFormat::RGB8unorm => Some(VEImageFormat::RGBA8unorm),
+        VEImageFormat::RGB8srgb => Some(VEImageFormat::RGBA8srgb),
+        VEImageFormat::RGB16unorm => Some(VEImageFormat::RGBA16unorm),
+        VEImageFormat::RGB32f => Some(VEImageFormat::RGBA32f),
+        _ => None,
+    }
+}
+
+fn convert_image(img: DynamicImage, format: VEImageFormat) -> Result<DynamicImage, VEImageError> {
+    Ok(match format {
+        VEImageFormat::R8unorm | VEImageFormat::R8srgb => DynamicImage::ImageLuma8(img.to_luma8()),
+        VEImageFormat::RG8unorm | VEImageFormat::RG8srgb => {
+            DynamicImage::ImageLumaA8(img.to_luma_alpha8())
+        }
+        VEImageFormat::RGB8unorm | VEImageFormat::RGB8srgb => {
+            DynamicImage::ImageRgb8(img.to_rgb8())
+        }
+        VEImageFormat::RGBA8unorm | VEImageFormat::RGBA8srgb => {
+            DynamicImage::ImageRgba8(img.to_rgba8())
+        }
+        VEImageFormat::R16unorm => DynamicImage::ImageLuma16(img.to_luma16()),
+        VEImageFormat::RG16unorm => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        VEImageFormat::RGB16unorm => DynamicImage::ImageRgb16(img.to_rgb16()),
+        VEImageFormat::RGBA16unorm => DynamicImage::ImageRgba16(img.to_rgba16()),
+        VEImageFormat::RGB32f => DynamicImage::ImageRgb32F(img.to_rgb32f()),
+        VEImageFormat::RGBA32f => DynamicImage::ImageRgba32F(img.to_rgba32f()),
+        _ => return Err(VEImageError::UnsupportedLoadFormat(format)),
+    })
+}
+
+// Gray images are stored in one or two channels and read back as gray with alpha. Storage and
+// attachment views need the identity swizzle, those images are read as stored.
+fn get_gray_swizzle(format: VEImageFormat, usages: &[VEImageUsage]) -> vk::ComponentMapping {
+    let identity_only = usages.iter().any(|usage| {
+        matches!(
+            usage,
+            VEImageUsage::ColorAttachment
+                | VEImageUsage::DepthAttachment
+                | VEImageUsage::InputAttachment
+                | VEImageUsage::Storage
+        )
+    });
+    let alpha = match format {
+        _ if identity_only => return vk::ComponentMapping::default(),
+        VEImageFormat::R8unorm | VEImageFormat::R8srgb | VEImageFormat::R16unorm => {
+            vk::ComponentSwizzle::ONE
+        }
+        VEImageFormat::RG8unorm | VEImageFormat::RG8srgb | VEImageFormat::RG16unorm => {
+            vk::ComponentSwizzle::G
+        }
+        _ => return vk::ComponentMapping::default(),
+    };
+    vk::ComponentMapping {
+        r: vk::ComponentSwizzle::R,
+        g: vk::ComponentSwizzle::R,
+        b: vk::ComponentSwizzle::R,
+        a: alpha,
+    }
+}
+
 impl VEImage {
     pub fn from_file(
         device: Arc<VEDevice>,
@@ -16,36 +106,120 @@ impl VEImage {
         path: &str,
         usages: &[VEImageUsage],
     ) -> Result<VEImage, VEImageError> {
-        let img = ImageReader::open(path)
+        VEImage::from_file_with_options(
+            device,
+            queue,
+            command_pool,
+            memory_manager,
+            path,
+            usages,
+            &VELoadOptions::default(),
+        )
+    }
+
+    pub fn from_file_with_options(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        command_pool: Arc<VECommandPool>,
+        memory_manager: Arc<Mutex<VEMemoryManager>>,
+        path: &str,
+        usages: &[VEImageUsage],
+        options: &VELoadOptions,
+    ) -> Result<VEImage, VEImageError> {
+        let bytes = std::fs::read(path).map_err(VEImageError::OpeningFileFailed)?;
+        if VETextureContainer::is_container(&bytes) {
+            return VEImage::from_container(
+                device,
+                queue,
+                command_pool,
+                memory_manager,
+                &bytes,
+                usages,
+            );
+        }
+
+        let img = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
             .map_err(VEImageError::OpeningFileFailed)?
             .decode()
             .map_err(VEImageError::ImageDecodingFailed)?;
-        let img = img.to_rgba8(); // error handling... TODO
-                                  // let format = match img.color() {
-                                  //     ColorType::L8 => vk::Format::R8_UNORM,
-                                  //     ColorType::La8 => vk::Format::R8G8_UNORM,
-                                  //     ColorType::L16 => vk::Format::R16_UNORM,
-                                  //     ColorType::La16 => vk::Format::R16G16_UNORM,
-                                  //     ColorType::Rgb8 => vk::Format::R8G8B8_UNORM,
-                                  //     ColorType::Rgba8 => vk::Format::R8G8B8A8_UNORM,
-                                  //     ColorType::Rgb16 => vk::Format::R16G16B16_UNORM,
-                                  //     ColorType::Rgba16 => vk::Format::R16G16B16A16_UNORM,
-                                  //     ColorType::Rgb32F => vk::Format::R32G32B32_SFLOAT,
-                                  //     ColorType::Rgba32F => vk::Format::R32G32B32A32_SFLOAT,
-                                  //     _ => panic!("Unknown format"),
-                                  // };
-        let format = VEImageFormat::RGBA8unorm;
-        VEImage::from_data(
+
+        let is_gray = options.format.is_none()
+            && matches!(
+                img.color(),
+                ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16
+            );
+        let mut format = options
+            .format
+            .unwrap_or_else(|| get_source_format(img.color(), options.srgb));
+        if let Some(padded) = get_padded_format(format) {
+            if !supports_sampling(&device, format) {
+                format = padded;
+            }
+        }
+        let img = convert_image(img, format)?;
+
+        // formats that can be neither blitted nor downsampled keep a single level
+        let mip_levels = match get_device_mipmap_path(&device, get_image_format(format), 1) {
+            Some(_) => VEMipLevels::Full,
+            None => VEMipLevels::Count(1),
+        };
+
+        let mut image = VEImage::from_data(
             device,
             queue,
             command_pool,
             memory_manager,
-            &Vec::from(img.as_bytes()),
-            img.width(),
-            img.height(),
-            1,
-            format,
-            usages,
-        )
+            img.as_bytes(),
+            &VEImageDesc::new(img.width(), img.height(), format, usages)
+                .with_mip_levels(mip_levels),
+        )?;
+        if is_gray {
+            image.swizzle = get_gray_swizzle(format, usages);
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_sources_keep_their_channels_and_srgb() {
+        assert_eq!(
+            get_source_format(ColorType::L8, false),
+            VEImageFormat::R8unorm
+        );
+        assert_eq!(
+            get_source_format(ColorType::L8, true),
+            VEImageFormat::R8srgb
+        );
+        assert_eq!(
+            get_source_format(ColorType::La8, true),
+            VEImageFormat::RG8srgb
+        );
+        assert_eq!(
+            get_padded_format(VEImageFormat::R8srgb),
+            Some(VEImageFormat::RGBA8srgb)
+        );
+    }
+
+    #[test]
+    fn gray_images_are_swizzled_for_sampling_only() {
+        let swizzle = get_gray_swizzle(VEImageFormat::R8unorm, &[VEImageUsage::Sampled]);
+        assert_eq!(swizzle.g, vk::ComponentSwizzle::R);
+        assert_eq!(swizzle.a, vk::ComponentSwizzle::ONE);
+
+        let swizzle = get_gray_swizzle(VEImageFormat::RG16unorm, &[VEImageUsage::Sampled]);
+        assert_eq!(swizzle.b, vk::ComponentSwizzle::R);
+        assert_eq!(swizzle.a, vk::ComponentSwizzle::G);
+
+        let storage = [VEImageUsage::Sampled, VEImageUsage::Storage];
+        let swizzle = get_gray_swizzle(VEImageFormat::R8unorm, &storage);
+        assert_eq!(swizzle.g, vk::ComponentSwizzle::IDENTITY);
+        assert_eq!(swizzle.a, vk::ComponentSwizzle::IDENTITY);
+        let swizzle = get_gray_swizzle(VEImageFormat::RGBA8srgb, &[VEImageUsage::Sampled]);
+        assert_eq!(swizzle.g, vk::ComponentSwizzle::IDENTITY);
     }
 }
@@ -3,9 +3,15 @@ use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::VEMainDeviceQueue;
 use crate::core::memory_properties::{get_memory_properties_flags, VEMemoryProperties};
-use crate::image::aspect_from_format::aspect_from_format;
-use crate::image::image::{VEImage, VEImageError, VEImageUsage};
-use crate::image::image_format::{get_image_format, VEImageFormat};
+use crate::core::resource_state::VEResourceState;
+use crate::image::image::image_mipmaps::{
+    get_device_mipmap_path, get_mip_level_count, get_mipmap_usages, VEMipmapPath,
+};
+use crate::image::image::{
+    get_sample_count_flags, get_unsupported_usages, VEImage, VEImageDesc, VEImageError,
+    VEImageLayers, VEImageUsage,
+};
+use crate::image::image_format::{get_aspect_from_vk, get_image_format};
 use crate::memory::memory_manager::VEMemoryManager;
 use ash::vk;
 use std::collections::HashMap;
@@ -19,6 +25,7 @@ fn get_image_usage_flags(usages: &[VEImageUsage]) -> vk::ImageUsageFlags {
             VEImageUsage::DepthAttachment => {
                 flags = flags | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
             }
+            VEImageUsage::InputAttachment => flags = flags | vk::ImageUsageFlags::INPUT_ATTACHMENT,
             VEImageUsage::Sampled => flags = flags | vk::ImageUsageFlags::SAMPLED,
             VEImageUsage::Storage => flags = flags | vk::ImageUsageFlags::STORAGE,
             VEImageUsage::TransferDestination => flags = flags | vk::ImageUsageFlags::TRANSFER_DST,
@@ -28,54 +35,164 @@ fn get_image_usage_flags(usages: &[VEImageUsage]) -> vk::ImageUsageFlags {
     flags
 }
 
+// Intersection of the sample counts the device supports for every requested usage.
+fn get_supported_sample_counts(
+    device: &VEDevice,
+    aspect: vk::ImageAspectFlags,
+    usages: &[VEImageUsage],
+) -> vk::SampleCountFlags {
+    let limits = &device.properties.limits;
+    let is_depth = aspect.contains(vk::ImageAspectFlags::DEPTH);
+    let mut counts = vk::SampleCountFlags::from_raw(!0);
+    for usage in usages {
+        match usage {
+            VEImageUsage::ColorAttachment => counts &= limits.framebuffer_color_sample_counts,
+            VEImageUsage::DepthAttachment => counts &= limits.framebuffer_depth_sample_counts,
+            VEImageUsage::Sampled if is_depth => counts &= limits.sampled_image_depth_sample_counts,
+            VEImageUsage::Sampled => counts &= limits.sampled_image_color_sample_counts,
+            VEImageUsage::Storage => counts &= limits.storage_image_sample_counts,
+            VEImageUsage::InputAttachment
+            | VEImageUsage::TransferDestination
+            | VEImageUsage::TransferSource => (),
+        }
+    }
+    counts
+}
+
+// Requested usages plus the ones `generate_mipmaps` needs. Without a mipmap path the levels
+// can still be uploaded one by one, `generate_mipmaps` fails if it is called.
+fn get_created_usages(
+    usages: &[VEImageUsage],
+    mip_levels: u32,
+    mipmap_path: Option<VEMipmapPath>,
+) -> Vec<VEImageUsage> {
+    let mut usages = usages.to_vec();
+    if let Some(path) = mipmap_path.filter(|_| mip_levels > 1) {
+        for usage in get_mipmap_usages(path) {
+            if !usages.contains(&usage) {
+                usages.push(usage);
+            }
+        }
+    }
+    usages
+}
+
+// `mip_levels` and `array_layers` are the resolved counts of `desc`.
+fn create_image_handle(
+    device: &VEDevice,
+    desc: &VEImageDesc,
+    mip_levels: u32,
+    array_layers: u32,
+    cube: bool,
+    initial_layout: vk::ImageLayout,
+) -> Result<(vk::Image, vk::SampleCountFlags), VEImageError> {
+    let (width, height, depth) = (desc.width, desc.height, desc.depth);
+    let format = get_image_format(desc.format);
+    let aspect = get_aspect_from_vk(format);
+    let samples = get_sample_count_flags(desc.samples);
+    if samples != vk::SampleCountFlags::TYPE_1
+        && (depth != 1
+            || !get_supported_sample_counts(device, aspect, &desc.usages).contains(samples))
+    {
+        return Err(VEImageError::UnsupportedSampleCount(samples));
+    }
+    if samples != vk::SampleCountFlags::TYPE_1 && mip_levels != 1 {
+        return Err(VEImageError::InvalidMipLevelCount(mip_levels));
+    }
+    if array_layers == 0
+        || array_layers > device.properties.limits.max_image_array_layers
+        || (depth != 1 && array_layers != 1)
+    {
+        return Err(VEImageError::InvalidLayerCount(array_layers));
+    }
+    if cube && width != height {
+        return Err(VEImageError::CubeFaceNotSquare(width, height));
+    }
+    if cube && samples != vk::SampleCountFlags::TYPE_1 {
+        return Err(VEImageError::UnsupportedSampleCount(samples));
+    }
+
+    let queue_family_indices = [device.queue_family_index];
+
+    let image_create_info = vk::ImageCreateInfo::default()
+        .flags(if cube {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        })
+        .image_type(if depth == 1 {
+            vk::ImageType::TYPE_2D
+        } else {
+            vk::ImageType::TYPE_3D
+        })
+        .extent(
+            vk::Extent3D::default()
+                .width(width)
+                .height(height)
+                .depth(depth),
+        )
+        .mip_levels(mip_levels)
+        .array_layers(array_layers)
+        .format(format)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(get_image_usage_flags(&desc.usages))
+        .samples(samples)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .queue_family_indices(&queue_family_indices)
+        .initial_layout(initial_layout);
+
+    let image_handle = unsafe {
+        device
+            .device
+            .create_image(&image_create_info, None)
+            .map_err(VEImageError::ImageCreationFailed)?
+    };
+    Ok((image_handle, samples))
+}
+
 impl VEImage {
     pub fn from_full(
         device: Arc<VEDevice>,
         queue: Arc<Mutex<VEMainDeviceQueue>>,
         command_pool: Arc<VECommandPool>,
         memory_manager: Arc<Mutex<VEMemoryManager>>,
+        desc: &VEImageDesc,
+    ) -> Result<VEImage, VEImageError> {
+        let unsupported = get_unsupported_usages(&device, desc.format, &desc.usages);
+        if !unsupported.is_empty() {
+            return Err(VEImageError::UnsupportedUsages(desc.format, unsupported));
+        }
 
-        width: u32,
-        height: u32,
-        depth: u32,
+        let (width, height, depth) = (desc.width, desc.height, desc.depth);
+        let format = get_image_format(desc.format);
+        let aspect = get_aspect_from_vk(format);
+        let mip_levels = get_mip_level_count(desc.mip_levels, width, height, depth)?;
+        let (array_layers, cube) = match desc.layers {
+            VEImageLayers::Array(count) => (count, false),
+            VEImageLayers::Cube(count) => (
+                count
+                    .checked_mul(6)
+                    .ok_or(VEImageError::InvalidLayerCount(count))?,
+                true,
+            ),
+        };
 
-        format: VEImageFormat,
+        // The internal usages come from a supported mip path, only the caller's ones are checked.
+        let mut desc = desc.clone();
+        desc.usages = get_created_usages(
+            &desc.usages,
+            mip_levels,
+            get_device_mipmap_path(&device, format, depth),
+        );
 
-        usages: &[VEImageUsage],
-    ) -> Result<VEImage, VEImageError> {
-        let format = get_image_format(format);
-        let aspect = aspect_from_format(format);
-
-        let queue_family_indices = [device.queue_family_index];
-
-        let image_create_info = vk::ImageCreateInfo::default()
-            .image_type(if depth == 1 {
-                vk::ImageType::TYPE_2D
-            } else {
-                vk::ImageType::TYPE_3D
-            })
-            .extent(
-                vk::Extent3D::default()
-                    .width(width)
-                    .height(height)
-                    .depth(depth),
-            )
-            .mip_levels(1)
-            .array_layers(1)
-            .format(format)
-            .tiling(vk::ImageTiling::OPTIMAL)
-            .usage(get_image_usage_flags(usages))
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .queue_family_indices(&queue_family_indices)
-            .initial_layout(vk::ImageLayout::PREINITIALIZED);
-
-        let image_handle = unsafe {
-            device
-                .device
-                .create_image(&image_create_info, None)
-                .map_err(VEImageError::ImageCreationFailed)?
-        };
+        let (image_handle, samples) = create_image_handle(
+            &device,
+            &desc,
+            mip_levels,
+            array_layers,
+            cube,
+            vk::ImageLayout::PREINITIALIZED,
+        )?;
 
         let mem_reqs = unsafe { device.device.get_image_memory_requirements(image_handle) };
         let mem_index = device.find_memory_type(
@@ -96,18 +213,27 @@ impl VEImage {
             queue: queue.clone(),
 
             allocation: Some(allocation),
+            aliased: false,
 
             width,
             height,
             depth,
 
             format,
+            samples,
+            mip_levels,
+            array_layers,
 
             aspect,
 
             handle: image_handle,
             views: HashMap::new(),
+            swizzle: vk::ComponentMapping::default(),
             current_layout: vk::ImageLayout::PREINITIALIZED,
+            states: vec![
+                VEResourceState::new(vk::ImageLayout::PREINITIALIZED);
+                (mip_levels * array_layers) as usize
+            ],
         };
         let command_buffer = VECommandBuffer::new(device, command_pool)?;
         command_buffer.begin()?;
@@ -125,4 +251,99 @@ impl VEImage {
 
         Ok(image)
     }
+
+    // Image without memory, the caller binds it with vkBindImageMemory before use. Used for
+    // transient images that share memory with each other.
+    pub(crate) fn unbound(
+        device: Arc<VEDevice>,
+        queue: Arc<Mutex<VEMainDeviceQueue>>,
+        desc: &VEImageDesc,
+    ) -> Result<VEImage, VEImageError> {
+        let (width, height) = (desc.width, desc.height);
+        let format = get_image_format(desc.format);
+        let aspect = get_aspect_from_vk(format);
+
+        let (image_handle, samples) =
+            create_image_handle(&device, desc, 1, 1, false, vk::ImageLayout::UNDEFINED)?;
+
+        Ok(VEImage {
+            device,
+            queue,
+
+            allocation: None,
+            aliased: true,
+
+            width,
+            height,
+            depth: 1,
+
+            format,
+            samples,
+            mip_levels: 1,
+            array_layers: 1,
+
+            aspect,
+
+            handle: image_handle,
+            views: HashMap::new(),
+            swizzle: vk::ComponentMapping::default(),
+            current_layout: vk::ImageLayout::UNDEFINED,
+            states: vec![VEResourceState::new(vk::ImageLayout::UNDEFINED)],
+        })
+    }
+
+    pub(crate) fn memory_requirements(&self) -> vk::MemoryRequirements {
+        unsafe {
+            self.device
+                .device
+                .get_image_memory_requirements(self.handle)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::image::image_mipmaps::get_mipmap_path;
+
+    #[test]
+    fn uploaded_block_compressed_chains_need_no_mipmap_path() {
+        // what drivers report for BC1, blits are never a destination for compressed formats
+        let features = vk::FormatFeatureFlags::SAMPLED_IMAGE
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+            | vk::FormatFeatureFlags::TRANSFER_SRC
+            | vk::FormatFeatureFlags::TRANSFER_DST
+            | vk::FormatFeatureFlags::BLIT_SRC;
+        let path = get_mipmap_path(features, vk::Format::BC1_RGBA_UNORM_BLOCK, 1);
+        assert_eq!(path, None);
+
+        // a container with four levels it uploads itself
+        let usages = [VEImageUsage::Sampled, VEImageUsage::TransferDestination];
+        assert_eq!(get_created_usages(&usages, 4, path), usages.to_vec());
+    }
+
+    #[test]
+    fn created_usages_only_add_the_chosen_path() {
+        let usages = [VEImageUsage::Sampled, VEImageUsage::TransferDestination];
+        assert_eq!(
+            get_created_usages(&usages, 4, Some(VEMipmapPath::Blit)),
+            vec![
+                VEImageUsage::Sampled,
+                VEImageUsage::TransferDestination,
+                VEImageUsage::TransferSource,
+            ]
+        );
+        assert_eq!(
+            get_created_usages(&usages, 4, Some(VEMipmapPath::ComputeDownsample)),
+            vec![
+                VEImageUsage::Sampled,
+                VEImageUsage::TransferDestination,
+                VEImageUsage::Storage,
+            ]
+        );
+        assert_eq!(
+            get_created_usages(&usages, 1, Some(VEMipmapPath::Blit)),
+            usages.to_vec()
+        );
+    }
 }
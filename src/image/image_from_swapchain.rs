@@ -2,6 +2,7 @@ use crate::core::command_buffer::VECommandBuffer;
 use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::resource_state::VEResourceState;
 use crate::image::image::{VEImage, VEImageError};
 use ash::vk;
 use std::collections::HashMap;
@@ -24,18 +25,24 @@ impl VEImage {
             queue: queue.clone(),
 
             allocation: None,
+            aliased: false,
 
             width,
             height,
             depth: 1,
 
             format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+            array_layers: 1,
 
             aspect: vk::ImageAspectFlags::COLOR,
 
             handle: image_handle,
             views: HashMap::new(),
+            swizzle: vk::ComponentMapping::default(),
             current_layout: vk::ImageLayout::UNDEFINED,
+            states: vec![VEResourceState::new(vk::ImageLayout::UNDEFINED)],
         };
 
         let command_buffer = VECommandBuffer::new(device, command_pool)?;
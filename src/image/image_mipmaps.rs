@@ -0,0 +1,398 @@
+use crate::compute::compute_stage::VEComputeStage;
+use crate::core::command_buffer::VECommandBuffer;
+use crate::core::command_pool::VECommandPool;
+use crate::core::descriptor_set::VEDescriptorSet;
+use crate::core::descriptor_set_layout::{
+    VEDescriptorSetFieldStage, VEDescriptorSetFieldType, VEDescriptorSetLayout,
+    VEDescriptorSetLayoutField,
+};
+use crate::core::device::VEDevice;
+use crate::core::resource_state::{VEImageAccess, VESubresourceRange};
+use crate::core::shader_module::{VEShaderModule, VEShaderModuleType};
+use crate::image::image::{
+    VEImage, VEImageError, VEImageUsage, VEImageViewAspect, VEImageViewCreateInfo, VEImageViewType,
+    VEMipLevels,
+};
+use crate::image::image_format::get_aspect_from_vk;
+use ash::vk;
+use std::io::Cursor;
+use std::sync::Arc;
+
+static DOWNSAMPLE_RGBA32F_SPV: &[u8] = include_bytes!("shaders/downsample_rgba32f.spv");
+static DOWNSAMPLE_RGBA16F_SPV: &[u8] = include_bytes!("shaders/downsample_rgba16f.spv");
+static DOWNSAMPLE_R32F_SPV: &[u8] = include_bytes!("shaders/downsample_r32f.spv");
+static DOWNSAMPLE_RGBA8_SPV: &[u8] = include_bytes!("shaders/downsample_rgba8.spv");
+static DOWNSAMPLE_RGBA8_SNORM_SPV: &[u8] = include_bytes!("shaders/downsample_rgba8_snorm.spv");
+static DOWNSAMPLE_GROUP_SIZE: u32 = 8;
+
+pub(crate) fn get_mip_level_count(
+    mip_levels: VEMipLevels,
+    width: u32,
+    height: u32,
+    depth: u32,
+) -> Result<u32, VEImageError> {
+    let full = 32 - width.max(height).max(depth).max(1).leading_zeros();
+    match mip_levels {
+        VEMipLevels::Full => Ok(full),
+        VEMipLevels::Count(count) if count == 0 || count > full => {
+            Err(VEImageError::InvalidMipLevelCount(count))
+        }
+        VEMipLevels::Count(count) => Ok(count),
+    }
+}
+
+fn is_depth_stencil(format: vk::Format) -> bool {
+    get_aspect_from_vk(format)
+        .intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL)
+}
+
+// Depth and stencil formats can only be blitted with the nearest filter.
+fn get_blit_filter(format: vk::Format) -> vk::Filter {
+    if is_depth_stencil(format) {
+        vk::Filter::NEAREST
+    } else {
+        vk::Filter::LINEAR
+    }
+}
+
+fn supports_blit(features: vk::FormatFeatureFlags, format: vk::Format) -> bool {
+    let mut required = vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST;
+    if get_blit_filter(format) == vk::Filter::LINEAR {
+        required |= vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+    }
+    features.contains(required)
+}
+
+// Downsample shader compiled for the storage format, only float formats it can average.
+fn get_downsample_shader(format: vk::Format) -> Option<&'static [u8]> {
+    match format {
+        vk::Format::R32G32B32A32_SFLOAT => Some(DOWNSAMPLE_RGBA32F_SPV),
+        vk::Format::R16G16B16A16_SFLOAT => Some(DOWNSAMPLE_RGBA16F_SPV),
+        vk::Format::R32_SFLOAT => Some(DOWNSAMPLE_R32F_SPV),
+        vk::Format::R8G8B8A8_UNORM => Some(DOWNSAMPLE_RGBA8_SPV),
+        vk::Format::R8G8B8A8_SNORM => Some(DOWNSAMPLE_RGBA8_SNORM_SPV),
+        _ => None,
+    }
+}
+
+// Access that puts the image back into `layout` after the mipmaps are generated. Images that
+// had no defined layout, or were left in general by the upload, end up ready for sampling.
+fn get_restore_access(layout: vk::ImageLayout) -> VEImageAccess {
+    match layout {
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => VEImageAccess::ColorAttachment,
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => VEImageAccess::DepthAttachment,
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => VEImageAccess::DepthRead,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => VEImageAccess::TransferRead,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => VEImageAccess::TransferWrite,
+        vk::ImageLayout::PRESENT_SRC_KHR => VEImageAccess::Present,
+        _ => VEImageAccess::FragmentSampled,
+    }
+}
+
+fn supports_compute_downsample(
+    features: vk::FormatFeatureFlags,
+    format: vk::Format,
+    depth: u32,
+) -> bool {
+    depth == 1
+        && !is_depth_stencil(format)
+        && get_downsample_shader(format).is_some()
+        && features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum VEMipmapPath {
+    Blit,
+    ComputeDownsample,
+}
+
+// How `generate_mipmaps` fills the levels of an image in `format` with optimal tiling
+// `features`. Block compressed and most integer formats have no path.
+pub(crate) fn get_mipmap_path(
+    features: vk::FormatFeatureFlags,
+    format: vk::Format,
+    depth: u32,
+) -> Option<VEMipmapPath> {
+    if supports_blit(features, format) {
+        Some(VEMipmapPath::Blit)
+    } else if supports_compute_downsample(features, format, depth) {
+        Some(VEMipmapPath::ComputeDownsample)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn get_device_mipmap_path(
+    device: &VEDevice,
+    format: vk::Format,
+    depth: u32,
+) -> Option<VEMipmapPath> {
+    get_mipmap_path(
+        device.format_features(format, vk::ImageTiling::OPTIMAL),
+        format,
+        depth,
+    )
+}
+
+// Usages `generate_mipmaps` needs on top of the ones the caller asked for.
+pub(crate) fn get_mipmap_usages(path: VEMipmapPath) -> Vec<VEImageUsage> {
+    match path {
+        VEMipmapPath::Blit => vec![
+            VEImageUsage::TransferSource,
+            VEImageUsage::TransferDestination,
+        ],
+        VEMipmapPath::ComputeDownsample => vec![VEImageUsage::Storage],
+    }
+}
+
+fn load_downsample_shader(
+    device: Arc<VEDevice>,
+    format: vk::Format,
+) -> Result<VEShaderModule, VEImageError> {
+    let bytes =
+        get_downsample_shader(format).ok_or(VEImageError::MipmapGenerationNotSupported(format))?;
+    Ok(VEShaderModule::from_stream(
+        device,
+        &mut Cursor::new(bytes),
+        VEShaderModuleType::Compute,
+    )?)
+}
+
+fn get_mip_extent(image: &VEImage, level: u32) -> vk::Offset3D {
+    vk::Offset3D {
+        x: (image.width >> level).max(1) as i32,
+        y: (image.height >> level).max(1) as i32,
+        z: (image.depth >> level).max(1) as i32,
+    }
+}
+
+// Everything the downsample dispatches use, kept alive until the command buffer finished.
+struct VEDownsampleResources {
+    _sets: Vec<VEDescriptorSet>,
+    _stage: VEComputeStage,
+    _shader: VEShaderModule,
+    _set_layout: VEDescriptorSetLayout,
+}
+
+impl VEImage {
+    // Fills every mip level from level 0, with blits if the format allows it and a compute
+    // downsample otherwise. Leaves the whole image in the layout level 0 was in, or ready for
+    // sampling if that layout was undefined or general.
+    pub fn generate_mipmaps(
+        &mut self,
+        command_pool: Arc<VECommandPool>,
+    ) -> Result<(), VEImageError> {
+        if self.mip_levels <= 1 {
+            return Ok(());
+        }
+
+        let restore_access = get_restore_access(self.current_layout);
+        let command_buffer = VECommandBuffer::new(self.device.clone(), command_pool.clone())?;
+        command_buffer.begin()?;
+
+        let resources = match get_device_mipmap_path(&self.device, self.format, self.depth) {
+            Some(VEMipmapPath::Blit) => {
+                self.record_blits(&command_buffer)?;
+                None
+            }
+            Some(VEMipmapPath::ComputeDownsample) => {
+                Some(self.record_downsample(&command_buffer, command_pool)?)
+            }
+            None => return Err(VEImageError::MipmapGenerationNotSupported(self.format)),
+        };
+        command_buffer.require(self, restore_access)?;
+        command_buffer.end()?;
+
+        let queue = self
+            .queue
+            .lock()
+            .map_err(|_| VEImageError::QueueLockingFailed)?;
+        command_buffer.submit(&queue, vec![], vec![])?;
+        queue.wait_idle()?;
+
+        drop(resources);
+        Ok(())
+    }
+
+    fn record_blits(&mut self, command_buffer: &VECommandBuffer) -> Result<(), VEImageError> {
+        for level in 1..self.mip_levels {
+            command_buffer.require_range(
+                self,
+                VEImageAccess::TransferRead,
+                VESubresourceRange::new(level - 1, 1, 0, self.array_layers),
+            )?;
+            command_buffer.require_range(
+                self,
+                VEImageAccess::TransferWrite,
+                VESubresourceRange::new(level, 1, 0, self.array_layers),
+            )?;
+
+            let blit = vk::ImageBlit::default()
+                .src_subresource(
+                    vk::ImageSubresourceLayers::default()
+                        .aspect_mask(self.aspect)
+                        .mip_level(level - 1)
+                        .base_array_layer(0)
+                        .layer_count(self.array_layers),
+                )
+                .src_offsets([vk::Offset3D::default(), get_mip_extent(self, level - 1)])
+                .dst_subresource(
+                    vk::ImageSubresourceLayers::default()
+                        .aspect_mask(self.aspect)
+                        .mip_level(level)
+                        .base_array_layer(0)
+                        .layer_count(self.array_layers),
+                )
+                .dst_offsets([vk::Offset3D::default(), get_mip_extent(self, level)]);
+
+            unsafe {
+                self.device.device.cmd_blit_image(
+                    command_buffer.handle,
+                    self.handle,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.handle,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    get_blit_filter(self.format),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn record_downsample(
+        &mut self,
+        command_buffer: &VECommandBuffer,
+        command_pool: Arc<VECommandPool>,
+    ) -> Result<VEDownsampleResources, VEImageError> {
+        let mut set_layout = VEDescriptorSetLayout::new(
+            self.device.clone(),
+            &[
+                VEDescriptorSetLayoutField {
+                    binding: 0,
+                    typ: VEDescriptorSetFieldType::StorageImage,
+                    stage: VEDescriptorSetFieldStage::Compute,
+                    count: 1,
+                },
+                VEDescriptorSetLayoutField {
+                    binding: 1,
+                    typ: VEDescriptorSetFieldType::StorageImage,
+                    stage: VEDescriptorSetFieldStage::Compute,
+                    count: 1,
+                },
+            ],
+        )?;
+        let shader = load_downsample_shader(self.device.clone(), self.format)?;
+        let stage = VEComputeStage::new(
+            self.device.clone(),
+            command_pool,
+            &[&set_layout],
+            &[],
+            &shader,
+        )?;
+
+        // Descriptors are written with the current layout, so everything goes to general first.
+        command_buffer.require(self, VEImageAccess::ComputeStorageRead)?;
+        stage.bind(command_buffer);
+
+        let mut sets = vec![];
+        for level in 1..self.mip_levels {
+            for layer in 0..self.array_layers {
+                let src = self.get_view(VEImageViewCreateInfo {
+                    typ: VEImageViewType::View2D,
+                    base_mipmap: level - 1,
+                    mipmap_count: 1,
+                    base_layer: layer,
+                    layer_count: 1,
+                    aspect: VEImageViewAspect::Default,
+                })?;
+                let dst = self.get_view(VEImageViewCreateInfo {
+                    typ: VEImageViewType::View2D,
+                    base_mipmap: level,
+                    mipmap_count: 1,
+                    base_layer: layer,
+                    layer_count: 1,
+                    aspect: VEImageViewAspect::Default,
+                })?;
+                let set = set_layout.create_descriptor_set()?;
+                set.bind_image_storage(0, self, src)?;
+                set.bind_image_storage(1, self, dst)?;
+
+                command_buffer.require_range(
+                    self,
+                    VEImageAccess::ComputeStorageRead,
+                    VESubresourceRange::new(level - 1, 1, layer, 1),
+                )?;
+                command_buffer.require_range(
+                    self,
+                    VEImageAccess::ComputeStorageWrite,
+                    VESubresourceRange::new(level, 1, layer, 1),
+                )?;
+
+                let extent = get_mip_extent(self, level);
+                stage.set_descriptor_set(command_buffer, 0, &set);
+                stage.dispatch(
+                    command_buffer,
+                    (extent.x as u32).div_ceil(DOWNSAMPLE_GROUP_SIZE),
+                    (extent.y as u32).div_ceil(DOWNSAMPLE_GROUP_SIZE),
+                    1,
+                );
+                sets.push(set);
+            }
+        }
+
+        Ok(VEDownsampleResources {
+            _sets: sets,
+            _stage: stage,
+            _shader: shader,
+            _set_layout: set_layout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_formats_are_blitted_with_nearest_filter() {
+        assert_eq!(get_blit_filter(vk::Format::D32_SFLOAT), vk::Filter::NEAREST);
+        assert_eq!(
+            get_blit_filter(vk::Format::D24_UNORM_S8_UINT),
+            vk::Filter::NEAREST
+        );
+        assert_eq!(
+            get_blit_filter(vk::Format::R8G8B8A8_UNORM),
+            vk::Filter::LINEAR
+        );
+    }
+
+    #[test]
+    fn generated_mipmaps_restore_the_previous_layout() {
+        for layout in [
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        ] {
+            assert_eq!(get_restore_access(layout), VEImageAccess::FragmentSampled);
+        }
+        assert_eq!(
+            get_restore_access(vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+            VEImageAccess::TransferRead
+        );
+    }
+
+    #[test]
+    fn downsample_shaders_exist_for_every_storage_format() {
+        for format in [
+            vk::Format::R32G32B32A32_SFLOAT,
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::R32_SFLOAT,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::Format::R8G8B8A8_SNORM,
+        ] {
+            assert!(get_downsample_shader(format).is_some_and(|spv| spv.len() % 4 == 0));
+        }
+        assert!(get_downsample_shader(vk::Format::D32_SFLOAT).is_none());
+    }
+}
@@ -1,6 +1,7 @@
-mod aspect_from_format;
+pub(crate) mod bc_decode;
 pub mod filtering;
 pub mod image;
 pub mod image_format;
 pub mod sampler;
+pub mod texture_container;
 mod transition_image_layout;
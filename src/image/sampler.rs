@@ -56,7 +56,8 @@ impl VESampler {
             .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
             .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
             .min_lod(0.0)
-            .max_lod(1.0)
+            // the mip range of the sampled view decides how many levels are used
+            .max_lod(vk::LOD_CLAMP_NONE)
             .mip_lod_bias(0.0);
 
         let handle = unsafe {
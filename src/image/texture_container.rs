@@ -0,0 +1,485 @@
+use crate::image::image::VEImageLayers;
+use crate::image::image_format::{get_image_format_from_vk, get_level_size, VEImageFormat};
+use ash::vk;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VETextureContainerError {
+    #[error("not a KTX2 or DDS file")]
+    UnknownContainer,
+
+    #[error("texture container is truncated")]
+    Truncated,
+
+    #[error("unsupported texture format {0}")]
+    UnsupportedFormat(String),
+
+    #[error("unsupported KTX2 supercompression scheme {0}")]
+    UnsupportedSupercompression(u32),
+
+    #[error("invalid texture container: {0}")]
+    InvalidHeader(String),
+}
+
+static KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+static KTX2_HEADER_SIZE: usize = 80;
+static KTX2_LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+static DDS_MAGIC: &[u8; 4] = b"DDS ";
+static DDS_HEADER_SIZE: usize = 128;
+static DDS_DX10_HEADER_SIZE: usize = 20;
+static DDSD_MIPMAPCOUNT: u32 = 0x20000;
+static DDPF_FOURCC: u32 = 0x4;
+static DDPF_RGB: u32 = 0x40;
+static DDSCAPS2_CUBEMAP: u32 = 0x200;
+static DDSCAPS2_VOLUME: u32 = 0x200000;
+static DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+static DDS_DIMENSION_TEXTURE3D: u32 = 4;
+
+// Parsed KTX2 or DDS file. Every level holds all of its layers back to back, cube faces in
+// +X, -X, +Y, -Y, +Z, -Z order within each cube.
+#[derive(Debug, Clone)]
+pub struct VETextureContainer {
+    pub format: VEImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub layers: VEImageLayers,
+    pub levels: Vec<Vec<u8>>,
+    // the file asks for mipmaps to be generated after loading
+    pub generate_mipmaps: bool,
+}
+
+impl VETextureContainer {
+    pub fn parse(bytes: &[u8]) -> Result<VETextureContainer, VETextureContainerError> {
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            parse_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            parse_dds(bytes)
+        } else {
+            Err(VETextureContainerError::UnknownContainer)
+        }
+    }
+
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(&KTX2_IDENTIFIER) || bytes.starts_with(DDS_MAGIC)
+    }
+
+    pub fn layer_count(&self) -> Result<u32, VETextureContainerError> {
+        match self.layers {
+            VEImageLayers::Array(count) => Ok(count),
+            VEImageLayers::Cube(count) => count
+                .checked_mul(6)
+                .ok_or_else(|| VETextureContainerError::InvalidHeader(format!("{count} cubes"))),
+        }
+    }
+
+    // Bytes of one layer of `level`.
+    pub fn level_layer_size(&self, level: u32) -> Result<usize, VETextureContainerError> {
+        let (width, height, depth) = (
+            (self.width >> level).max(1),
+            (self.height >> level).max(1),
+            (self.depth >> level).max(1),
+        );
+        get_level_size(self.format, width, height, depth).ok_or_else(|| {
+            VETextureContainerError::InvalidHeader(format!(
+                "{width}x{height}x{depth} level is too large"
+            ))
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, VETextureContainerError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(VETextureContainerError::Truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, VETextureContainerError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+fn read_slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], VETextureContainerError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or(VETextureContainerError::Truncated)
+}
+
+fn get_full_mip_count(width: u32, height: u32, depth: u32) -> u32 {
+    32 - width.max(height).max(depth).max(1).leading_zeros()
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<VETextureContainer, VETextureContainerError> {
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    let depth = read_u32(bytes, 28)?.max(1);
+    let layer_count = read_u32(bytes, 32)?.max(1);
+    let face_count = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?;
+    let supercompression = read_u32(bytes, 44)?;
+
+    if supercompression != 0 {
+        return Err(VETextureContainerError::UnsupportedSupercompression(
+            supercompression,
+        ));
+    }
+    let format =
+        get_image_format_from_vk(vk::Format::from_raw(vk_format as i32)).ok_or_else(|| {
+            VETextureContainerError::UnsupportedFormat(format!("VkFormat {vk_format}"))
+        })?;
+    if width == 0 || (face_count != 1 && face_count != 6) {
+        return Err(VETextureContainerError::InvalidHeader(format!(
+            "{width} texels wide with {face_count} faces"
+        )));
+    }
+    if level_count > get_full_mip_count(width, height, depth) {
+        return Err(VETextureContainerError::InvalidHeader(format!(
+            "{level_count} mip levels"
+        )));
+    }
+
+    let mut container = VETextureContainer {
+        format,
+        width,
+        height,
+        depth,
+        layers: if face_count == 6 {
+            VEImageLayers::Cube(layer_count)
+        } else {
+            VEImageLayers::Array(layer_count)
+        },
+        levels: vec![],
+        // a level count of 0 asks the loader to generate the mip chain
+        generate_mipmaps: level_count == 0,
+    };
+
+    for level in 0..level_count.max(1) {
+        let entry = KTX2_HEADER_SIZE + level as usize * KTX2_LEVEL_INDEX_ENTRY_SIZE;
+        // Offsets and lengths past the address space cannot be inside the file.
+        let offset = usize::try_from(read_u64(bytes, entry)?)
+            .map_err(|_| VETextureContainerError::Truncated)?;
+        let length = read_u64(bytes, entry + 8)?;
+        let expected = container
+            .level_layer_size(level)?
+            .checked_mul(container.layer_count()? as usize)
+            .ok_or_else(|| {
+                VETextureContainerError::InvalidHeader(format!("level {level} is too large"))
+            })?;
+        if length != expected as u64 {
+            return Err(VETextureContainerError::InvalidHeader(format!(
+                "level {level} has {length} bytes instead of {expected}"
+            )));
+        }
+        container
+            .levels
+            .push(read_slice(bytes, offset, expected)?.to_vec());
+    }
+    Ok(container)
+}
+
+fn get_dds_fourcc_format(fourcc: &[u8]) -> Option<VEImageFormat> {
+    match fourcc {
+        b"DXT1" => Some(VEImageFormat::BC1unorm),
+        b"DXT2" | b"DXT3" => Some(VEImageFormat::BC2unorm),
+        b"DXT4" | b"DXT5" => Some(VEImageFormat::BC3unorm),
+        b"ATI1" | b"BC4U" => Some(VEImageFormat::BC4unorm),
+        b"BC4S" => Some(VEImageFormat::BC4inorm),
+        b"ATI2" | b"BC5U" => Some(VEImageFormat::BC5unorm),
+        b"BC5S" => Some(VEImageFormat::BC5inorm),
+        _ => None,
+    }
+}
+
+fn get_dxgi_format(dxgi_format: u32) -> Option<VEImageFormat> {
+    match dxgi_format {
+        2 => Some(VEImageFormat::RGBA32f),
+        6 => Some(VEImageFormat::RGB32f),
+        10 => Some(VEImageFormat::RGBA16f),
+        11 => Some(VEImageFormat::RGBA16unorm),
+        13 => Some(VEImageFormat::RGBA16inorm),
+        24 => Some(VEImageFormat::A2B10G10R10unorm),
+        26 => Some(VEImageFormat::B10G11R11ufloat),
+        28 => Some(VEImageFormat::RGBA8unorm),
+        29 => Some(VEImageFormat::RGBA8srgb),
+        31 => Some(VEImageFormat::RGBA8inorm),
+        34 => Some(VEImageFormat::RG16f),
+        35 => Some(VEImageFormat::RG16unorm),
+        41 => Some(VEImageFormat::R32f),
+        49 => Some(VEImageFormat::RG8unorm),
+        54 => Some(VEImageFormat::R16f),
+        56 => Some(VEImageFormat::R16unorm),
+        67 => Some(VEImageFormat::E5B9G9R9ufloat),
+        61 => Some(VEImageFormat::R8unorm),
+        71 => Some(VEImageFormat::BC1unorm),
+        72 => Some(VEImageFormat::BC1srgb),
+        74 => Some(VEImageFormat::BC2unorm),
+        75 => Some(VEImageFormat::BC2srgb),
+        77 => Some(VEImageFormat::BC3unorm),
+        78 => Some(VEImageFormat::BC3srgb),
+        80 => Some(VEImageFormat::BC4unorm),
+        81 => Some(VEImageFormat::BC4inorm),
+        83 => Some(VEImageFormat::BC5unorm),
+        84 => Some(VEImageFormat::BC5inorm),
+        87 => Some(VEImageFormat::BGRA8unorm),
+        91 => Some(VEImageFormat::BGRA8srgb),
+        95 => Some(VEImageFormat::BC6Hufloat),
+        96 => Some(VEImageFormat::BC6Hfloat),
+        98 => Some(VEImageFormat::BC7unorm),
+        99 => Some(VEImageFormat::BC7srgb),
+        _ => None,
+    }
+}
+
+fn parse_dds(bytes: &[u8]) -> Result<VETextureContainer, VETextureContainerError> {
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?.max(1);
+    let width = read_u32(bytes, 16)?.max(1);
+    let depth = read_u32(bytes, 24)?;
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1)
+    } else {
+        1
+    };
+    let pixel_flags = read_u32(bytes, 80)?;
+    let fourcc = read_slice(bytes, 84, 4)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    let volume = caps2 & DDSCAPS2_VOLUME != 0;
+    let mut data_offset = DDS_HEADER_SIZE;
+    let (format, layers, volume) = if pixel_flags & DDPF_FOURCC != 0 && fourcc == b"DX10" {
+        let dxgi_format = read_u32(bytes, DDS_HEADER_SIZE)?;
+        let dimension = read_u32(bytes, DDS_HEADER_SIZE + 4)?;
+        let misc_flags = read_u32(bytes, DDS_HEADER_SIZE + 8)?;
+        let array_size = read_u32(bytes, DDS_HEADER_SIZE + 12)?.max(1);
+        data_offset += DDS_DX10_HEADER_SIZE;
+        let format = get_dxgi_format(dxgi_format).ok_or_else(|| {
+            VETextureContainerError::UnsupportedFormat(format!("DXGI format {dxgi_format}"))
+        })?;
+        let layers = if misc_flags & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+            VEImageLayers::Cube(array_size)
+        } else {
+            VEImageLayers::Array(array_size)
+        };
+        (format, layers, dimension == DDS_DIMENSION_TEXTURE3D)
+    } else {
+        let format = if pixel_flags & DDPF_FOURCC != 0 {
+            get_dds_fourcc_format(fourcc)
+        } else if pixel_flags & DDPF_RGB != 0
+            && read_u32(bytes, 88)? == 32
+            && read_u32(bytes, 92)? == 0x000000ff
+            && read_u32(bytes, 96)? == 0x0000ff00
+            && read_u32(bytes, 100)? == 0x00ff0000
+        {
+            Some(VEImageFormat::RGBA8unorm)
+        } else {
+            None
+        };
+        let format = format.ok_or_else(|| {
+            VETextureContainerError::UnsupportedFormat(format!(
+                "DDS pixel format {}",
+                String::from_utf8_lossy(fourcc)
+            ))
+        })?;
+        let layers = if caps2 & DDSCAPS2_CUBEMAP != 0 {
+            VEImageLayers::Cube(1)
+        } else {
+            VEImageLayers::Array(1)
+        };
+        (format, layers, volume)
+    };
+    let depth = if volume { depth.max(1) } else { 1 };
+    if mip_count > get_full_mip_count(width, height, depth) {
+        return Err(VETextureContainerError::InvalidHeader(format!(
+            "{mip_count} mip levels"
+        )));
+    }
+
+    let mut container = VETextureContainer {
+        format,
+        width,
+        height,
+        depth,
+        layers,
+        levels: vec![vec![]; mip_count as usize],
+        generate_mipmaps: false,
+    };
+
+    // DDS stores every mip chain of a layer before the next layer.
+    let mut offset = data_offset;
+    for _ in 0..container.layer_count()? {
+        for level in 0..mip_count {
+            let size = container.level_layer_size(level)?;
+            container.levels[level as usize].extend_from_slice(read_slice(bytes, offset, size)?);
+            offset += size;
+        }
+    }
+    Ok(container)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ktx2_file(
+        format: vk::Format,
+        size: (u32, u32),
+        layer_count: u32,
+        face_count: u32,
+        levels: &[(u64, u64)],
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        for value in [
+            format.as_raw() as u32,
+            1,
+            size.0,
+            size.1,
+            0,
+            layer_count,
+            face_count,
+            levels.len() as u32,
+            0,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(KTX2_HEADER_SIZE, 0);
+        for (offset, length) in levels {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.extend_from_slice(&length.to_le_bytes());
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    // RGBA8 4x4 with two mip levels.
+    fn valid_ktx2() -> Vec<u8> {
+        let data_offset = (KTX2_HEADER_SIZE + 2 * KTX2_LEVEL_INDEX_ENTRY_SIZE) as u64;
+        ktx2_file(
+            vk::Format::R8G8B8A8_UNORM,
+            (4, 4),
+            0,
+            1,
+            &[(data_offset, 64), (data_offset + 64, 16)],
+            &[7; 80],
+        )
+    }
+
+    fn dds_file(width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; DDS_HEADER_SIZE];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        let mut write = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        write(12, height);
+        write(16, width);
+        write(80, DDPF_FOURCC);
+        write(84, u32::from_le_bytes(*b"DXT1"));
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn parse_reads_valid_ktx2_files() {
+        let container = VETextureContainer::parse(&valid_ktx2());
+        assert!(container.is_ok_and(|c| {
+            c.format == VEImageFormat::RGBA8unorm
+                && (c.width, c.height, c.depth) == (4, 4, 1)
+                && c.levels.len() == 2
+                && c.levels[0].len() == 64
+                && c.levels[1].len() == 16
+                && !c.generate_mipmaps
+        }));
+    }
+
+    #[test]
+    fn parse_reads_valid_dds_files() {
+        // one 8 byte BC1 block for 4x4 texels
+        let container = VETextureContainer::parse(&dds_file(4, 4, &[1; 8]));
+        assert!(container.is_ok_and(|c| {
+            c.format == VEImageFormat::BC1unorm && c.levels.len() == 1 && c.levels[0].len() == 8
+        }));
+    }
+
+    #[test]
+    fn parse_rejects_truncated_files() {
+        let bytes = valid_ktx2();
+        assert!(matches!(
+            VETextureContainer::parse(&bytes[..bytes.len() - 1]),
+            Err(VETextureContainerError::Truncated)
+        ));
+        assert!(matches!(
+            VETextureContainer::parse(&bytes[..40]),
+            Err(VETextureContainerError::Truncated)
+        ));
+        assert!(matches!(
+            VETextureContainer::parse(&dds_file(4, 4, &[1; 7])),
+            Err(VETextureContainerError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_level_offsets_past_the_end() {
+        let bytes = ktx2_file(
+            vk::Format::R8G8B8A8_UNORM,
+            (1, 1),
+            0,
+            1,
+            &[(u64::MAX - 1, 4)],
+            &[0; 4],
+        );
+        assert!(matches!(
+            VETextureContainer::parse(&bytes),
+            Err(VETextureContainerError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_overflowing_sizes() {
+        // 16 bytes per texel times 2^64 texels
+        let bytes = ktx2_file(
+            vk::Format::R32G32B32A32_SFLOAT,
+            (u32::MAX, u32::MAX),
+            0,
+            1,
+            &[(0, 16)],
+            &[],
+        );
+        assert!(matches!(
+            VETextureContainer::parse(&bytes),
+            Err(VETextureContainerError::InvalidHeader(_))
+        ));
+
+        let cubes = ktx2_file(
+            vk::Format::R8G8B8A8_UNORM,
+            (1, 1),
+            u32::MAX,
+            6,
+            &[(0, 4)],
+            &[],
+        );
+        assert!(matches!(
+            VETextureContainer::parse(&cubes),
+            Err(VETextureContainerError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn level_size_does_not_overflow() {
+        assert_eq!(get_level_size(VEImageFormat::RGBA8unorm, 4, 4, 1), Some(64));
+        assert_eq!(get_level_size(VEImageFormat::BC1unorm, 5, 5, 1), Some(32));
+        assert_eq!(
+            get_level_size(VEImageFormat::RGBA32f, 65536, 65536, 1),
+            usize::try_from(1u64 << 36).ok()
+        );
+        assert_eq!(
+            get_level_size(VEImageFormat::RGBA32f, u32::MAX, u32::MAX, u32::MAX),
+            None
+        );
+    }
+}
@@ -2,6 +2,8 @@ use crate::core::command_buffer::VECommandBuffer;
 use crate::core::command_pool::VECommandPool;
 use crate::core::device::VEDevice;
 use crate::core::main_device_queue::VEMainDeviceQueue;
+use crate::core::memory_barrier::{VEDependencyInfo, VEImageMemoryBarrier2};
+use crate::core::resource_state::VESubresourceRange;
 use crate::image::image::VEImageError;
 use ash::vk;
 use ash::vk::CommandBufferUsageFlags;
@@ -15,128 +17,88 @@ pub fn transition_image_layout(
     current_layout: vk::ImageLayout,
     new_layout: vk::ImageLayout,
 ) -> Result<(), VEImageError> {
-    let mut src_access = vk::AccessFlags::empty();
-    let mut dst_access = vk::AccessFlags::empty();
+    let mut src_access = vk::AccessFlags2::empty();
+    let mut dst_access = vk::AccessFlags2::empty();
     let source_stage;
     let destination_stage;
 
     if current_layout == vk::ImageLayout::UNDEFINED
         && new_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
     {
-        dst_access = vk::AccessFlags::TRANSFER_WRITE;
+        dst_access = vk::AccessFlags2::TRANSFER_WRITE;
 
-        source_stage = vk::PipelineStageFlags::TOP_OF_PIPE;
-        destination_stage = vk::PipelineStageFlags::TRANSFER;
+        source_stage = vk::PipelineStageFlags2::TOP_OF_PIPE;
+        destination_stage = vk::PipelineStageFlags2::TRANSFER;
     } else if current_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
         && new_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
     {
-        src_access = vk::AccessFlags::TRANSFER_WRITE;
-        dst_access = vk::AccessFlags::SHADER_READ;
+        src_access = vk::AccessFlags2::TRANSFER_WRITE;
+        dst_access = vk::AccessFlags2::SHADER_READ;
 
-        source_stage = vk::PipelineStageFlags::TRANSFER;
-        destination_stage = vk::PipelineStageFlags::FRAGMENT_SHADER;
+        source_stage = vk::PipelineStageFlags2::TRANSFER;
+        destination_stage = vk::PipelineStageFlags2::FRAGMENT_SHADER;
     } else {
-        source_stage = vk::PipelineStageFlags::ALL_COMMANDS;
-        destination_stage = vk::PipelineStageFlags::ALL_COMMANDS;
+        source_stage = vk::PipelineStageFlags2::ALL_COMMANDS;
+        destination_stage = vk::PipelineStageFlags2::ALL_COMMANDS;
         match current_layout {
-            vk::ImageLayout::PREINITIALIZED => src_access = vk::AccessFlags::HOST_WRITE,
+            vk::ImageLayout::PREINITIALIZED => src_access = vk::AccessFlags2::HOST_WRITE,
 
             vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => {
-                src_access = vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                src_access = vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
             }
 
             vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => {
-                src_access = vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                src_access = vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
             }
 
-            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => src_access = vk::AccessFlags::TRANSFER_READ,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => src_access = vk::AccessFlags2::TRANSFER_READ,
 
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL => src_access = vk::AccessFlags::TRANSFER_WRITE,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => src_access = vk::AccessFlags2::TRANSFER_WRITE,
 
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => src_access = vk::AccessFlags::SHADER_READ,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => src_access = vk::AccessFlags2::SHADER_READ,
 
             _ => (),
         }
         match new_layout {
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL => dst_access = vk::AccessFlags::TRANSFER_WRITE,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => dst_access = vk::AccessFlags2::TRANSFER_WRITE,
 
-            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => dst_access = vk::AccessFlags::TRANSFER_READ,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => dst_access = vk::AccessFlags2::TRANSFER_READ,
 
             vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => {
-                dst_access = vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                dst_access = vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
             }
 
             vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => {
-                dst_access = dst_access | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                dst_access = dst_access | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
             }
 
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
-                if src_access == vk::AccessFlags::empty() {
-                    src_access = vk::AccessFlags::HOST_WRITE | vk::AccessFlags::TRANSFER_WRITE;
+                if src_access == vk::AccessFlags2::empty() {
+                    src_access = vk::AccessFlags2::HOST_WRITE | vk::AccessFlags2::TRANSFER_WRITE;
                 }
                 if current_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL {
-                    src_access = vk::AccessFlags::TRANSFER_READ;
+                    src_access = vk::AccessFlags2::TRANSFER_READ;
                 }
-                dst_access = vk::AccessFlags::SHADER_READ;
+                dst_access = vk::AccessFlags2::SHADER_READ;
             }
             _ => (),
         }
     }
 
-    image_memory_barrier(
-        device,
-        &command_buffer,
-        image_handle,
-        aspect,
-        current_layout,
-        new_layout,
-        src_access,
-        dst_access,
-        source_stage,
-        destination_stage,
-    );
+    VEDependencyInfo::new()
+        .with_image_barrier(&VEImageMemoryBarrier2 {
+            image: image_handle,
+            aspect,
+            range: VESubresourceRange::all(),
+            old_layout: current_layout,
+            new_layout,
+            src_stages: source_stage,
+            src_access,
+            dst_stages: destination_stage,
+            dst_access,
+            queue_transfer: None,
+        })
+        .submit(&device, command_buffer);
 
     Ok(())
 }
-
-fn image_memory_barrier(
-    device: Arc<VEDevice>,
-    command_buffer: &VECommandBuffer,
-    image_handle: vk::Image,
-    aspect: vk::ImageAspectFlags,
-    current_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout,
-    src_access: vk::AccessFlags,
-    dst_access: vk::AccessFlags,
-    source_stage: vk::PipelineStageFlags,
-    destination_stage: vk::PipelineStageFlags,
-) {
-    let barrier = vk::ImageMemoryBarrier::default()
-        .old_layout(current_layout)
-        .new_layout(new_layout)
-        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-        .image(image_handle)
-        .subresource_range(
-            vk::ImageSubresourceRange::default()
-                .aspect_mask(aspect)
-                .base_mip_level(0)
-                .level_count(1) // TODO mip mapping
-                .base_array_layer(0)
-                .layer_count(1),
-        )
-        .src_access_mask(src_access)
-        .dst_access_mask(dst_access);
-
-    unsafe {
-        device.device.cmd_pipeline_barrier(
-            command_buffer.handle,
-            source_stage,
-            destination_stage,
-            vk::DependencyFlags::empty(),
-            &[],
-            &[],
-            &[barrier],
-        )
-    }
-}
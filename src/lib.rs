@@ -1,6 +1,7 @@
 pub mod buffer;
 pub mod compute;
 pub mod core;
+pub mod graph;
 pub mod graphics;
 pub mod image;
 pub mod memory;
@@ -5,7 +5,7 @@ use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 use thiserror::Error;
 
-static CHUNK_SIZE: u64 = 256 * 1024 * 1024;
+pub(crate) static CHUNK_SIZE: u64 = 256 * 1024 * 1024;
 
 #[derive(Error, Debug)]
 pub enum VEMemoryChunkError {
@@ -19,6 +19,8 @@ pub enum VEMemoryChunkError {
     MappingFailed(#[source] vk::Result),
     #[error("pointer not found")]
     PointerNotFound,
+    #[error("{0} bytes do not fit in a memory chunk")]
+    AllocationTooLarge(u64),
 }
 
 #[derive(Clone, Debug)]
@@ -134,6 +136,19 @@ impl VEMemoryChunk {
         Ok(allocation)
     }
 
+    // Claims the range without binding anything to it.
+    pub fn reserve(&mut self, size: u64, offset: u64) -> VESingleAllocation {
+        self.identifier_counter += 1;
+        let allocation = VESingleAllocation {
+            chunk_identifier: self.chunk_identifier,
+            alloc_identifier: self.identifier_counter,
+            size,
+            offset,
+        };
+        self.allocations.push(allocation.clone());
+        allocation
+    }
+
     pub fn find_free_memory_offset(&self, size: u64) -> Option<u64> {
         if self.is_free_space(0, size) {
             return Some(0);
@@ -1,6 +1,8 @@
 use crate::core::device::VEDevice;
-use crate::memory::memory_chunk::{VEMemoryChunk, VEMemoryChunkError, VESingleAllocation};
-use ash::vk::{Buffer, Image};
+use crate::memory::memory_chunk::{
+    VEMemoryChunk, VEMemoryChunkError, VESingleAllocation, CHUNK_SIZE,
+};
+use ash::vk::{Buffer, DeviceMemory, Image};
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
@@ -64,6 +66,21 @@ impl VEMemoryManager {
         free.0.bind_image_memory(image, size, free.1)
     }
 
+    // Memory that several resources are bound to by the caller, like aliased transient images.
+    // The returned allocation starts at its offset inside the memory.
+    pub fn reserve_memory(
+        &mut self,
+        memory_type_index: u32,
+        size: u64,
+    ) -> Result<(DeviceMemory, VESingleAllocation), VEMemoryChunkError> {
+        let size = size + (0x1000 - (size % 0x1000));
+        if size > CHUNK_SIZE {
+            return Err(VEMemoryChunkError::AllocationTooLarge(size));
+        }
+        let free = self.find_free(memory_type_index, size)?;
+        Ok((free.0.handle, free.0.reserve(size, free.1)))
+    }
+
     fn find_free(
         &mut self,
         memory_type_index: u32,